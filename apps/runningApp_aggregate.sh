@@ -0,0 +1,10 @@
+echo "Read the environment variables"
+. ./.env # load the environment variables from the .env file for deployment
+
+echo "Update the guest programs"
+cargo build
+
+echo "Running the aggregator app (./apps/src/commands/aggregate.rs) with the following environment variables:"
+RUST_LOG=info cargo run --bin app -- aggregate --energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?} \
+                                               --smart-meter-inputs-file ${SMART_METER_INPUTS_FILE:?} \
+                                               --prover ${PROVER:-local}
@@ -0,0 +1,18 @@
+echo "Read the environment variables"
+. ./.env # load the environment variables from the .env file for deployment
+
+echo "Update the guest programs"
+cargo build
+
+echo "Running the buyer app (./apps/src/commands/buy.rs) with the following environment variables:"
+RUST_LOG=info cargo run --bin app -- buy --energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?} \
+                                         --amount-of-energy-to-be-bought ${AMOUNT_OF_ENERGY_TO_BE_BOUGHT:?} \
+                                         --consumption-window-start ${CONSUMPTION_WINDOW_START:?} \
+                                         --consumption-window-end ${CONSUMPTION_WINDOW_END:?} \
+                                         --current-time ${CURRENT_TIME:?} \
+                                         --max-monitored-time-age ${MAX_MONITORED_TIME_AGE:?} \
+                                         --consumption-merkle-root ${CONSUMPTION_MERKLE_ROOT:?} \
+                                         --consumption-readings-file ${CONSUMPTION_READINGS_FILE:?} \
+                                         --consumption-meter-public-key ${CONSUMPTION_METER_PUBLIC_KEY:?} \
+                                         --consumption-meter-signature ${CONSUMPTION_METER_SIGNATURE:?} \
+                                         --prover ${PROVER:-boundless}
@@ -4,13 +4,32 @@ echo "Read the environment variables"
 echo "Update the guest program"
 cargo build
 
-echo "Running the app (./apps/src/main.rs) with the following environment variables:"
-RUST_LOG=info cargo run --bin app -- --energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?} \
-                                  --amount-of-energy-to-be-sold ${AMOUNT_OF_ENERGY_TO_BE_SOLD:?} \
-                                  --total-exact-amount-of-energy-available ${TOTAL_EXACT_AMOUNT_OF_ENERGY_AVAILABLE:?} \
-                                  --current-time ${AMOUNT_OF_ENERGY_TO_BE_SOLD:?} \
-                                  --monitored-time ${MONITORED_TIME:?} \
-                                  --monitored-merkle-root ${MONITORED_MERKLE_ROOT:?} \
-                                  --monitored-nullifier ${MONITORED_NULLIFIER:?}                    
+# @dev - The subcommand is "sell" by default (e.g. "./runningApp_main.sh estimate --format json" only estimates the cost).
+#        The proof backend of the "sell" subcommand is read from the PROVER in the .env file (default: boundless).
+SUBCOMMAND=${1:-sell}
+[ $# -gt 0 ] && shift
+
+# @dev - The "estimate" subcommand needs neither a wallet nor the EnergyAggregator contract, but the seller and the chain ID.
+if [ "${SUBCOMMAND}" = "estimate" ]; then
+    TARGET_ARGS="--seller ${SELLER:?} --chain-id ${CHAIN_ID:?}"
+else
+    TARGET_ARGS="--energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?}"
+fi
+
+echo "Running the app (./apps/src/commands/sell.rs) with the following environment variables:"
+RUST_LOG=info cargo run --bin app -- ${SUBCOMMAND} ${TARGET_ARGS} \
+                                                   ${AMOUNT_OF_ENERGY_TO_BE_SOLD:+--amount-of-energy-to-be-sold ${AMOUNT_OF_ENERGY_TO_BE_SOLD}} \
+                                                   ${WINDOW_START:+--window-start ${WINDOW_START}} \
+                                                   ${WINDOW_END:+--window-end ${WINDOW_END}} \
+                                                   --current-time ${CURRENT_TIME:?} \
+                                                   --max-monitored-time-age ${MAX_MONITORED_TIME_AGE:?} \
+                                                   ${MONITORED_MERKLE_ROOT:+--monitored-merkle-root ${MONITORED_MERKLE_ROOT}} \
+                                                   ${METER_READINGS_FILE:+--meter-readings-file ${METER_READINGS_FILE}} \
+                                                   ${READINGS_FILE:+--readings ${READINGS_FILE}} \
+                                                   --meter-public-key ${METER_PUBLIC_KEY:?} \
+                                                   --meter-signature ${METER_SIGNATURE:?} \
+                                                   --previously-sold ${PREVIOUSLY_SOLD:-0} \
+                                                   ${MONITORED_NULLIFIER:+--monitored-nullifier ${MONITORED_NULLIFIER}} \
+                                                   "$@"
 
 #RUST_LOG=info cargo run --bin app -- --even-number-address ${EVEN_NUMBER_ADDRESS:?} --number 4
\ No newline at end of file
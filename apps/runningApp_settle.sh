@@ -0,0 +1,16 @@
+echo "Read the environment variables"
+. ./.env # load the environment variables from the .env file for deployment
+
+echo "Update the guest programs"
+cargo build
+
+echo "Running the settlement app (./apps/src/commands/settle.rs) with the following environment variables:"
+RUST_LOG=info cargo run --bin app -- settle --energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?} \
+                                            --sell-order-id ${SELL_ORDER_ID:?} \
+                                            --delivery-window-start ${DELIVERY_WINDOW_START:?} \
+                                            --delivery-window-end ${DELIVERY_WINDOW_END:?} \
+                                            --delivery-merkle-root ${DELIVERY_MERKLE_ROOT:?} \
+                                            --delivery-readings-file ${DELIVERY_READINGS_FILE:?} \
+                                            --meter-public-key ${METER_PUBLIC_KEY:?} \
+                                            --delivery-meter-signature ${DELIVERY_METER_SIGNATURE:?} \
+                                            --prover ${PROVER:-boundless}
@@ -0,0 +1,11 @@
+echo "Read the environment variables"
+. ./.env # load the environment variables from the .env file for deployment
+
+echo "Update the guest programs"
+cargo build
+
+echo "Running the settlement app of an aggregate sell order (./apps/src/commands/settle.rs) with the following environment variables:"
+RUST_LOG=info cargo run --bin app -- settle-aggregate --energy-aggregator-address ${ENERGY_AGGREGATOR_ADDRESS:?} \
+                                                      --sell-order-id ${SELL_ORDER_ID:?} \
+                                                      --settlement-inputs-file ${SETTLEMENT_INPUTS_FILE:?} \
+                                                      --prover ${PROVER:-boundless}
@@ -0,0 +1,476 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proof backends, which generate the proof (= journal and seal) of a guest program to be verified by the
+//! EnergyAggregator contract. The backend is picked by the `--prover` flag, so the rest of the flow of the apps is
+//! the same whichever backend generates the proof.
+
+use std::{fmt, future::Future, time::Duration};
+
+use alloy::{
+    primitives::{utils::parse_ether, Address, Bytes, U256},
+    signers::local::PrivateKeySigner,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::{
+    client::ClientBuilder,
+    contracts::{Input, Offer, Predicate, ProofRequestBuilder, Requirements},
+    input::GuestEnv,
+    storage::StorageProviderConfig,
+};
+use clap::ValueEnum;
+use risc0_ethereum_contracts::encode_seal;
+use risc0_zkvm::{
+    default_executor, default_prover, sha::Digestible, ExecutorEnv, FakeReceipt, InnerReceipt, ProverOpts, Receipt,
+    ReceiptClaim,
+};
+use url::Url;
+
+use crate::commands::TX_TIMEOUT;
+
+/// Price per million cycles of the offer of a proof request in the Boundless market (Unit: ETH).
+/// The market uses a reverse Dutch auction mechanism, whose price ramps up from the min price to the max price.
+pub const MIN_PRICE_PER_MCYCLE: &str = "0.001";
+/// (NOTE: If the offer is not being accepted, try increasing the max price)
+pub const MAX_PRICE_PER_MCYCLE: &str = "0.002";
+
+/// The time between blocks of the chain (Unit: seconds), by which the timeout (in blocks) of a proof request is derived
+/// from how long its proof can still be used (See: request_timeout()).
+pub const BLOCK_TIME: u64 = 12;
+/// The max timeout of a proof request (Unit: blocks), after which the request expires unfulfilled in the market.
+pub const MAX_REQUEST_TIMEOUT: u32 = 1000;
+
+/// The min and the max price (Unit: wei) of the offer of a proof request, whose guest program takes the given million cycles.
+pub fn offer_price_range(mcycles_count: u64) -> Result<(U256, U256)> {
+    let mcycles_count = U256::from(mcycles_count);
+    Ok((parse_ether(MIN_PRICE_PER_MCYCLE)? * mcycles_count, parse_ether(MAX_PRICE_PER_MCYCLE)? * mcycles_count))
+}
+
+/// The timeout (Unit: blocks) of a proof request whose proof can still be used by the EnergyAggregator contract for
+/// `valid_for` seconds, so that the request expires before its proof would be rejected as stale (leaving the time to
+/// confirm the tx with the proof). Capped at MAX_REQUEST_TIMEOUT.
+pub fn request_timeout(valid_for: u64) -> Result<u32> {
+    let blocks = valid_for.saturating_sub(TX_TIMEOUT.as_secs()) / BLOCK_TIME;
+    ensure!(
+        blocks > 0,
+        "the proof could only be used for {} more seconds, which is too short for a proof request to be fulfilled",
+        valid_for
+    );
+    Ok(u32::try_from(blocks).unwrap_or(u32::MAX).min(MAX_REQUEST_TIMEOUT))
+}
+
+/// The result of executing (= dry running) a guest program, without proving it.
+#[derive(Clone, Debug)]
+pub struct Execution {
+    /// The journal committed by the guest program.
+    pub journal: Vec<u8>,
+    /// The number of segments, each of which is proven separately.
+    pub segments: usize,
+    /// The cycles actually used by the guest program.
+    pub user_cycles: u64,
+    /// The cycles to be proven, i.e. the user cycles padded to a power of 2 per segment.
+    pub total_cycles: u64,
+    /// The total cycles in millions (rounded up), by which the offer of a proof request is priced.
+    pub mcycles_count: u64,
+}
+
+/// Execute the guest program with the input, without proving it.
+pub fn execute(elf: &[u8], input: &GuestEnv) -> Result<Execution> {
+    execute_with_assumptions(elf, input, Vec::new())
+}
+
+/// Execute the guest program with the input and the receipts it verifies with `env::verify()` (composition), without
+/// proving it.
+pub fn execute_with_assumptions(elf: &[u8], input: &GuestEnv, assumptions: Vec<Receipt>) -> Result<Execution> {
+    let env = executor_env(input, assumptions)?;
+    let session_info = default_executor().execute(env, elf).context("failed to execute the guest program")?;
+    let total_cycles = session_info.segments.iter().map(|segment| 1u64 << segment.po2).sum::<u64>();
+    Ok(Execution {
+        journal: session_info.journal.bytes,
+        segments: session_info.segments.len(),
+        user_cycles: session_info.segments.iter().map(|segment| u64::from(segment.cycles)).sum(),
+        total_cycles,
+        mcycles_count: total_cycles.div_ceil(1_000_000),
+    })
+}
+
+/// The environment of the guest program with the input (i.e. its stdin) and the receipts of its assumptions.
+fn executor_env(input: &GuestEnv, assumptions: Vec<Receipt>) -> Result<ExecutorEnv<'static>> {
+    let mut env_builder = ExecutorEnv::builder();
+    for assumption in assumptions {
+        env_builder.add_assumption(assumption);
+    }
+    env_builder.write_slice(&input.stdin).build().context("failed to build the environment of the guest program")
+}
+
+/// What the proof of a guest program is requested with: its dry run (See: execute()), whose journal and cycles price
+/// the proof request, and how long (Unit: seconds) its proof can still be used by the EnergyAggregator contract from
+/// the latest block (e.g. until the meter reading is no longer fresh).
+#[derive(Clone, Copy, Debug)]
+pub struct ProofTask<'a> {
+    pub execution: &'a Execution,
+    pub valid_for: u64,
+}
+
+/// Notified of the steps of generating a proof, e.g. to record them in the local store (See: ./apps/src/store.rs).
+pub trait ProofObserver {
+    /// Called once the proof request has been submitted to the Boundless market, before waiting for it to be fulfilled.
+    fn on_request_submitted(&mut self, request_id: U256, expires_at: u64) -> Result<()>;
+}
+
+/// No observer, i.e. the steps are not recorded.
+impl ProofObserver for () {
+    fn on_request_submitted(&mut self, _request_id: U256, _expires_at: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A backend which generates the proof of a guest program.
+pub trait ProofBackend {
+    /// Prove the guest program (ELF and image ID) with the input, and return the journal and the seal, which can be
+    /// passed to the EnergyAggregator contract. The observer is notified of the steps of generating the proof.
+    fn prove(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        task: ProofTask<'_>,
+        observer: &mut impl ProofObserver,
+    ) -> impl Future<Output = Result<(Vec<u8>, Bytes)>>;
+
+    /// Prove a receipt of the guest program, which can be added as an assumption of another guest program verifying it
+    /// with `env::verify()` (composition), e.g. the smart-meter receipts folded by the aggregator guest program.
+    fn prove_assumption(&self, elf: &[u8], image_id: [u32; 8], input: &GuestEnv) -> impl Future<Output = Result<Receipt>>;
+
+    /// Prove the guest program like [ProofBackend::prove], resolving the receipts (See: [ProofBackend::prove_assumption])
+    /// which it verifies.
+    fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        assumptions: Vec<Receipt>,
+        observer: &mut impl ProofObserver,
+    ) -> impl Future<Output = Result<(Vec<u8>, Bytes)>>;
+}
+
+/// Where the proof of a guest program is generated.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProverMode {
+    /// Submit a proof request to the Boundless market, which is fulfilled by its provers.
+    Boundless,
+    /// Prove locally with the default prover (e.g. to run the whole pipeline against a local anvil node).
+    Local,
+    /// Only execute the guest program, and produce a seal which is only accepted by a RiscZeroMockVerifier.
+    Mock,
+}
+
+impl fmt::Display for ProverMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // @dev - The same name as the value of the --prover flag.
+        f.write_str(self.to_possible_value().expect("no variant of the ProverMode is skipped").get_name())
+    }
+}
+
+/// CLI arguments of the proof backend, which are flattened into the arguments of each proving subcommand.
+#[derive(clap::Args, Debug)]
+pub struct ProverArgs {
+    /// Where the proof is generated. The local and the mock provers do not need a storage provider nor the Boundless market.
+    #[clap(long, env, value_enum, default_value_t = ProverMode::Boundless)]
+    pub prover: ProverMode,
+    /// Submit the request offchain via the provided order stream service url.
+    #[clap(short, long, requires = "order_stream_url")]
+    pub offchain: bool,
+    /// Offchain order stream service URL to submit offchain requests to.
+    #[clap(long, env)]
+    pub order_stream_url: Option<Url>,
+    /// Storage provider to use
+    #[clap(flatten)]
+    pub storage_config: Option<StorageProviderConfig>,
+    /// Address of the RiscZeroSetVerifier contract (Only required with --prover boundless).
+    #[clap(short, long, env)]
+    pub set_verifier_address: Option<Address>,
+    /// Address of the BoundlessfMarket contract (Only required with --prover boundless).
+    #[clap(short, long, env)]
+    pub boundless_market_address: Option<Address>,
+}
+
+impl ProverArgs {
+    /// Create the proof backend picked by the `--prover` flag.
+    pub fn into_backend(self, rpc_url: Url, wallet_private_key: PrivateKeySigner) -> Result<Backend> {
+        Ok(match self.prover {
+            ProverMode::Boundless => Backend::Boundless(BoundlessBackend {
+                rpc_url,
+                wallet_private_key,
+                boundless_market_address: self
+                    .boundless_market_address
+                    .context("the boundless_market_address is required with --prover boundless")?,
+                set_verifier_address: self
+                    .set_verifier_address
+                    .context("the set_verifier_address is required with --prover boundless")?,
+                order_stream_url: self.offchain.then_some(self.order_stream_url).flatten(),
+                storage_config: self.storage_config,
+            }),
+            ProverMode::Local => Backend::Local(LocalBackend),
+            ProverMode::Mock => Backend::Mock(MockBackend),
+        })
+    }
+}
+
+/// Proof backend of the Boundless market, whose provers fulfill the proof request.
+pub struct BoundlessBackend {
+    rpc_url: Url,
+    wallet_private_key: PrivateKeySigner,
+    boundless_market_address: Address,
+    set_verifier_address: Address,
+    order_stream_url: Option<Url>,
+    storage_config: Option<StorageProviderConfig>,
+}
+
+impl ProofBackend for BoundlessBackend {
+    async fn prove(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        task: ProofTask<'_>,
+        observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        // The request must expire before its proof would be rejected by the contract (e.g. as stale).
+        let timeout = request_timeout(task.valid_for)?;
+
+        // Create a Boundless client from the provided parameters.
+        let boundless_client = ClientBuilder::default()
+            .with_rpc_url(self.rpc_url.clone())
+            .with_boundless_market_address(self.boundless_market_address)
+            .with_set_verifier_address(self.set_verifier_address)
+            .with_order_stream_url(self.order_stream_url.clone())
+            .with_storage_provider_config(self.storage_config.clone())
+            .with_private_key(self.wallet_private_key.clone())
+            .build()
+            .await?;
+
+        // Upload the ELF to the storage provider so that it can be fetched by the market.
+        ensure!(
+            boundless_client.storage_provider.is_some(),
+            "a storage provider is required to upload the zkVM guest ELF"
+        );
+        let image_url = boundless_client.upload_image(elf).await?;
+        tracing::info!("Uploaded image to {}\n", image_url);
+
+        // @dev - The guest program has already been dry run by the caller, whose journal and cycle count are used to
+        //        require the journal and to price the proving request.
+        let Execution { journal, mcycles_count, .. } = task.execution;
+
+        // Create a proof request with the image, input, requirements and offer.
+        // The ELF (i.e. image) is specified by the image URL.
+        // The requirements are the image ID and the digest of the journal. In this way, the market can
+        // verify that the proof is correct by checking both the committed image id and digest of the
+        // journal. The offer specifies the price range and the timeout for the request.
+        // If the input exceeds 2 kB, upload the input and provide its URL instead, as a rule of thumb.
+        let guest_env_bytes = input.encode()?;
+        let request_input = if guest_env_bytes.len() > 2 << 10 {
+            let input_url = boundless_client.upload_input(&guest_env_bytes).await?;
+            tracing::info!("Uploaded input to {} \n", input_url);
+            Input::url(input_url)
+        } else {
+            tracing::info!("Sending input inline with request \n");
+            Input::inline(guest_env_bytes)
+        };
+
+        // Each request has a price range that a prover can bid on, which is set per million cycles (See: offer_price_range()).
+        let (min_price, max_price) = offer_price_range(*mcycles_count)?;
+        let request = ProofRequestBuilder::new()
+            .with_image_url(image_url.to_string())
+            .with_input(request_input)
+            .with_requirements(Requirements::new(image_id, Predicate::digest_match(journal.digest())))
+            .with_offer(
+                Offer::default()
+                    .with_min_price(min_price)
+                    .with_max_price(max_price)
+                    // The timeout is the maximum number of blocks the request can stay
+                    // unfulfilled in the market before it expires (See: request_timeout()).
+                    .with_timeout(timeout),
+            )
+            .build()
+            .context("failed to build the proof request")?;
+
+        // Send the request and wait for it to be completed.
+        let (request_id, expires_at) = boundless_client.submit_request(&request).await?;
+        tracing::info!("Request 0x{request_id:x} submitted");
+        // @dev - The request is paid from here, so it is recorded before waiting (which may take long).
+        observer.on_request_submitted(request_id, expires_at)?;
+
+        // Wait for the request to be fulfilled by the market, returning the journal and seal.
+        tracing::info!("Waiting for 0x{request_id:x} to be fulfilled");
+        let (journal, seal) = boundless_client
+            .wait_for_request_fulfillment(request_id, Duration::from_secs(5), expires_at)
+            .await?;
+        tracing::info!("Request 0x{request_id:x} fulfilled");
+
+        Ok((journal.to_vec(), seal))
+    }
+
+    async fn prove_assumption(&self, _elf: &[u8], _image_id: [u32; 8], _input: &GuestEnv) -> Result<Receipt> {
+        bail!("the Boundless market returns the seal of a fulfilled request, not a receipt which can be added as an assumption; use --prover local (or mock)")
+    }
+
+    async fn prove_with_assumptions(
+        &self,
+        _elf: &[u8],
+        _image_id: [u32; 8],
+        _input: &GuestEnv,
+        _assumptions: Vec<Receipt>,
+        _observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        // @dev - A proof request only carries the stdin of the guest program (= GuestEnv), not the receipts of its assumptions.
+        bail!("a proof request of the Boundless market can not carry the receipts of the assumptions; use --prover local (or mock)")
+    }
+}
+
+/// Proof backend of the local default prover, which produces a Groth16 receipt whose seal can be verified by the
+/// RiscZeroVerifierRouter contract.
+/// (NOTE: With RISC0_DEV_MODE=true, the default prover produces a fake receipt instead, like the MockBackend)
+pub struct LocalBackend;
+
+impl ProofBackend for LocalBackend {
+    async fn prove(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        _task: ProofTask<'_>,
+        observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        self.prove_with_assumptions(elf, image_id, input, Vec::new(), observer).await
+    }
+
+    async fn prove_assumption(&self, elf: &[u8], _image_id: [u32; 8], input: &GuestEnv) -> Result<Receipt> {
+        // @dev - A succinct receipt, which can be resolved when the composing guest program is proven.
+        tracing::info!("Proving the guest program locally (succinct)");
+        Ok(default_prover()
+            .prove_with_opts(executor_env(input, Vec::new())?, elf, &ProverOpts::succinct())
+            .context("failed to prove the guest program")?
+            .receipt)
+    }
+
+    async fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        _image_id: [u32; 8],
+        input: &GuestEnv,
+        assumptions: Vec<Receipt>,
+        _observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        tracing::info!("Proving the guest program locally");
+        let receipt = default_prover()
+            .prove_with_opts(executor_env(input, assumptions)?, elf, &ProverOpts::groth16())
+            .context("failed to prove the guest program")?
+            .receipt;
+        let seal = encode_seal(&receipt).context("failed to encode the seal")?;
+        Ok((receipt.journal.bytes, seal.into()))
+    }
+}
+
+/// Proof backend which only executes the guest program, and produces the seal of a fake receipt of its claim
+/// (= the selector 0xFFFFFFFF and the claim digest), which is only accepted by a RiscZeroMockVerifier.
+/// This is useful to test the whole flow against a local anvil node in seconds.
+pub struct MockBackend;
+
+impl MockBackend {
+    /// Execute the guest program, and return the fake receipt of its claim.
+    fn fake_receipt(elf: &[u8], image_id: [u32; 8], input: &GuestEnv, assumptions: Vec<Receipt>) -> Result<Receipt> {
+        let Execution { journal, .. } = execute_with_assumptions(elf, input, assumptions)?;
+        let claim = ReceiptClaim::ok(image_id, journal.clone());
+        Ok(Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal))
+    }
+}
+
+impl ProofBackend for MockBackend {
+    async fn prove(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        _task: ProofTask<'_>,
+        observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        self.prove_with_assumptions(elf, image_id, input, Vec::new(), observer).await
+    }
+
+    async fn prove_assumption(&self, elf: &[u8], image_id: [u32; 8], input: &GuestEnv) -> Result<Receipt> {
+        // @dev - The executor only looks up the claims of the assumptions, so a fake receipt can be added as one.
+        Self::fake_receipt(elf, image_id, input, Vec::new())
+    }
+
+    async fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        assumptions: Vec<Receipt>,
+        _observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        let receipt = Self::fake_receipt(elf, image_id, input, assumptions)?;
+        let seal = encode_seal(&receipt).context("failed to encode the seal")?;
+        Ok((receipt.journal.bytes, seal.into()))
+    }
+}
+
+/// The proof backend picked by the `--prover` flag (See: ProverArgs::into_backend()).
+pub enum Backend {
+    Boundless(BoundlessBackend),
+    Local(LocalBackend),
+    Mock(MockBackend),
+}
+
+impl ProofBackend for Backend {
+    async fn prove(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        task: ProofTask<'_>,
+        observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        match self {
+            Backend::Boundless(backend) => backend.prove(elf, image_id, input, task, observer).await,
+            Backend::Local(backend) => backend.prove(elf, image_id, input, task, observer).await,
+            Backend::Mock(backend) => backend.prove(elf, image_id, input, task, observer).await,
+        }
+    }
+
+    async fn prove_assumption(&self, elf: &[u8], image_id: [u32; 8], input: &GuestEnv) -> Result<Receipt> {
+        match self {
+            Backend::Boundless(backend) => backend.prove_assumption(elf, image_id, input).await,
+            Backend::Local(backend) => backend.prove_assumption(elf, image_id, input).await,
+            Backend::Mock(backend) => backend.prove_assumption(elf, image_id, input).await,
+        }
+    }
+
+    async fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        image_id: [u32; 8],
+        input: &GuestEnv,
+        assumptions: Vec<Receipt>,
+        observer: &mut impl ProofObserver,
+    ) -> Result<(Vec<u8>, Bytes)> {
+        match self {
+            Backend::Boundless(backend) => backend.prove_with_assumptions(elf, image_id, input, assumptions, observer).await,
+            Backend::Local(backend) => backend.prove_with_assumptions(elf, image_id, input, assumptions, observer).await,
+            Backend::Mock(backend) => backend.prove_with_assumptions(elf, image_id, input, assumptions, observer).await,
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host flow of the aggregator guest program: prove the smart-meter guest program for every smart meter of a
+//! microgrid, fold the receipts into a single composite proof, and create one sell order of the whole microgrid.
+//! The proofs are generated by the proof backend picked by --prover (See: ./apps/src/backend.rs).
+
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, B256},
+    providers::{Provider, ProviderBuilder},
+    sol_types::SolValue,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::input::InputBuilder;
+use energy_types::{AggregateJournal, AggregatorInput, SmartMeterInput};
+use risc0_zkvm::sha::{Impl, Sha256};
+use guests::{AGGREGATOR_ELF, AGGREGATOR_ID, SMART_METER_ELF, SMART_METER_ID};
+
+use super::{check_freshness, sell::created_sell_order_id, ImageArgs, WalletArgs, TX_TIMEOUT};
+use crate::{
+    backend::{execute, ProofBackend, ProverArgs},
+    energy_aggregator::IEnergyAggregator::{self, IEnergyAggregatorInstance},
+    nullifiers::is_nullifier_used,
+    store::{NewSell, StoreArgs},
+};
+
+/// Arguments of the `aggregate` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct AggregateArgs {
+    /// Path to the JSON file of the inputs of the smart-meter guest program (an array of SmartMeterInput), one per
+    /// smart meter of the microgrid. Every input must be bound to the operator's address (= seller) and this chain.
+    #[clap(long, env)]
+    pub smart_meter_inputs_file: PathBuf,
+
+    // @dev - The smart-meter receipts are added as the assumptions of the aggregator guest program, so that they are
+    //        only proven by the local (or the mock) prover.
+    #[clap(flatten)]
+    pub prover: ProverArgs,
+
+    #[clap(flatten)]
+    pub image: ImageArgs,
+
+    // @dev - The smart-meter input of every smart meter is recorded as a sell, which share the aggregate sell order.
+    #[clap(flatten)]
+    pub store: StoreArgs,
+
+    // @dev - The wallet is the one of the operator of the microgrid.
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Run the `aggregate` subcommand.
+pub async fn run(args: AggregateArgs) -> Result<()> {
+    let seller = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key.clone()))
+        .on_http(args.wallet.rpc_url.clone());
+    let chain_id = provider.get_chain_id().await.context("failed to get the chain ID")?;
+    let energy_aggregator = IEnergyAggregatorInstance::new(args.wallet.energy_aggregator_address, provider.clone());
+
+    // The proofs would be rejected by the contract if the guest programs have drifted from the image IDs pinned by it.
+    let deployed_image_id = energy_aggregator.imageId().call().await.context("failed to get the image ID from contract")?._0;
+    args.image.check("smart-meter", SMART_METER_ID, deployed_image_id)?;
+    let deployed_aggregator_image_id = energy_aggregator
+        .aggregatorImageId()
+        .call()
+        .await
+        .context("failed to get the aggregator image ID from contract")?
+        ._0;
+    args.image.check("aggregator", AGGREGATOR_ID, deployed_aggregator_image_id)?;
+
+    // Load the inputs of the smart meters in the microgrid.
+    let file = fs::read_to_string(&args.smart_meter_inputs_file)
+        .with_context(|| format!("failed to read the smart-meter inputs file {}", args.smart_meter_inputs_file.display()))?;
+    let smart_meter_inputs: Vec<SmartMeterInput> = serde_json::from_str(&file)
+        .with_context(|| format!("failed to parse the smart-meter inputs file {}", args.smart_meter_inputs_file.display()))?;
+    ensure!(!smart_meter_inputs.is_empty(), "at least 1 smart-meter input is required to be aggregated");
+
+    // Check the inputs before proving, since the on-chain verification would fail otherwise.
+    let mut monitored_merkle_roots = HashSet::new();
+    for smart_meter_input in &smart_meter_inputs {
+        ensure!(
+            Address::from(smart_meter_input.seller) == seller && smart_meter_input.chain_id == chain_id,
+            "every smart-meter input must be bound to the operator {} and the chain ID {}",
+            seller,
+            chain_id
+        );
+        // @dev - The aggregate journal commits to the oldest current_time, so every input must still be fresh.
+        check_freshness(&energy_aggregator, smart_meter_input.current_time, smart_meter_input.max_monitored_time_age)
+            .await
+            .with_context(|| format!("the smart-meter input of the monitored merkle root {} is not fresh", B256::from(smart_meter_input.monitored_merkle_root)))?;
+        // @dev - The first sell order of each monitored merkle root must chain from its latest sold commitment on-chain.
+        if monitored_merkle_roots.insert(smart_meter_input.monitored_merkle_root) {
+            let latest_sold_commitment = energy_aggregator
+                .soldCommitments(B256::from(smart_meter_input.monitored_merkle_root))
+                .call()
+                .await
+                .context("failed to get the latest sold commitment from contract")?
+                ._0;
+            ensure!(
+                latest_sold_commitment == B256::from(smart_meter_input.previous_sold_commitment),
+                "the smart-meter input of the monitored merkle root {} does not chain from the latest sold commitment {}",
+                B256::from(smart_meter_input.monitored_merkle_root),
+                latest_sold_commitment
+            );
+        }
+        // @dev - The nullifier must not have been used by any sell order, so that a reused nullifier is refused before proving.
+        let monitored_nullifier = B256::from(smart_meter_input.monitored_nullifier);
+        if is_nullifier_used(&energy_aggregator, monitored_nullifier).await? {
+            bail!("the nullifier {} has already been used by a sell order", monitored_nullifier);
+        }
+    }
+
+    // Dry run the smart-meter guest program for every smart meter, so that an unprovable input fails before proving.
+    let mut smart_meter_envs = Vec::with_capacity(smart_meter_inputs.len());
+    let mut smart_meter_journals = Vec::with_capacity(smart_meter_inputs.len());
+    for smart_meter_input in &smart_meter_inputs {
+        let guest_env = InputBuilder::new().write(smart_meter_input)?.build_env()?;
+        smart_meter_journals.push(execute(SMART_METER_ELF, &guest_env)?.journal);
+        smart_meter_envs.push(guest_env);
+    }
+
+    // Record the smart-meter input of every smart meter as a sell in the local store, so that its nullifier is refused
+    // by the `sell` subcommand, and its energy is counted as sold from its monitored merkle root.
+    let store = args.store.open()?;
+    let prover = args.prover.prover.to_string();
+    let mut sell_ids = Vec::with_capacity(smart_meter_inputs.len());
+    for (smart_meter_input, journal) in smart_meter_inputs.iter().zip(&smart_meter_journals) {
+        sell_ids.push(store.insert_sell(&NewSell {
+            nullifier: B256::from(smart_meter_input.monitored_nullifier),
+            monitored_merkle_root: B256::from(smart_meter_input.monitored_merkle_root),
+            amount_of_energy_to_be_sold: smart_meter_input.amount_of_energy_to_be_sold,
+            window_start: smart_meter_input.window_start,
+            window_end: smart_meter_input.window_end,
+            prover: &prover,
+            input: &serde_json::to_string(smart_meter_input).context("failed to serialize the input")?,
+            journal,
+        })?);
+    }
+    tracing::info!("Sells {:?} recorded in the store {}", sell_ids, args.store.store_file.display());
+
+    // Prove the smart-meter guest program for every smart meter. The receipts are added as assumptions of the
+    // aggregator guest program (See: ProofBackend::prove_assumption()).
+    let backend = args.prover.into_backend(args.wallet.rpc_url.clone(), args.wallet.wallet_private_key.clone())?;
+    let proof = async {
+        let mut smart_meter_receipts = Vec::with_capacity(smart_meter_envs.len());
+        for (index, guest_env) in smart_meter_envs.iter().enumerate() {
+            tracing::info!("Proving the smart-meter guest program ({}/{})", index + 1, smart_meter_envs.len());
+            smart_meter_receipts.push(backend.prove_assumption(SMART_METER_ELF, SMART_METER_ID, guest_env).await?);
+        }
+
+        // Prove the aggregator guest program, which verifies all the smart-meter receipts (composition).
+        let aggregator_input = AggregatorInput {
+            smart_meter_image_id: SMART_METER_ID,
+            journals: smart_meter_receipts.iter().map(|receipt| receipt.journal.bytes.clone()).collect(),
+        };
+        let guest_env = InputBuilder::new().write(&aggregator_input)?.build_env()?;
+        tracing::info!("Proving the aggregator guest program");
+        backend
+            .prove_with_assumptions(AGGREGATOR_ELF, AGGREGATOR_ID, &guest_env, smart_meter_receipts, &mut ())
+            .await
+    }
+    .await;
+    if let Err(err) = &proof {
+        for sell_id in &sell_ids {
+            store.set_failed(*sell_id, &format!("{:#}", err))?;
+        }
+    }
+    let (journal, seal) = proof?;
+    for sell_id in &sell_ids {
+        store.set_proven(*sell_id, &seal)?;
+    }
+
+    let aggregate_journal = AggregateJournal::decode(&journal).context("failed to decode the journal")?;
+    tracing::info!("aggregated energyAmountToBeSold: {}\n", aggregate_journal.energyAmountToBeSold);
+
+    // Interact with the EnergyAggregator contract by calling the createAggregateSellOrder() function with the aggregate
+    // journal and the seal (i.e. proof) of the aggregator guest program.
+    // (NOTE: The journal is re-decoded as the contract binding's struct, which has the same ABI)
+    let aggregate_journal_of_contract = IEnergyAggregator::AggregateJournal::abi_decode(&journal, true)
+        .context("failed to decode the journal as the IEnergyAggregator.AggregateJournal")?;
+    let create_aggregate_sell_order_tx = energy_aggregator
+        .createAggregateSellOrder(aggregate_journal_of_contract, seal)
+        .from(seller);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#createAggregateSellOrder() function");
+    let pending_tx = create_aggregate_sell_order_tx.send().await.context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    for sell_id in &sell_ids {
+        store.set_submitted(*sell_id, *pending_tx.tx_hash())?;
+    }
+    // @dev - An unconfirmed tx is not recorded as failed, since it may still be included later.
+    let receipt = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .get_receipt()
+        .await
+        .context("failed to confirm tx")?;
+    let sell_order_id = created_sell_order_id(*energy_aggregator.address(), &receipt, "createAggregateSellOrder", &store, &sell_ids)?;
+
+    // We query the sell order stored at the EnergyAggregator address to check it was stored as it was proven.
+    // (NOTE: The aggregate sell order stores the sha256 digest of the aggregate journal, which commits to the nullifiers
+    // of all the smart meters)
+    let sell_order = energy_aggregator
+        .getSellOrder(sell_order_id)
+        .call()
+        .await
+        .context("failed to get the sell order from contract")?
+        ._0;
+    ensure!(
+        sell_order.energyAmountToBeSold == aggregate_journal.energyAmountToBeSold,
+        "the stored sell order {} does not have the proven amount of energy to be sold",
+        sell_order_id
+    );
+    ensure!(
+        sell_order.journal == B256::from_slice(Impl::hash_bytes(&journal).as_bytes()),
+        "the stored sell order {} does not have the proven aggregate journal",
+        sell_order_id
+    );
+    ensure!(
+        sell_order.energySeller == seller && sell_order.imageId == deployed_aggregator_image_id,
+        "the stored sell order {} is not the aggregate sell order of the operator {}",
+        sell_order_id,
+        seller
+    );
+    tracing::info!(
+        "aggregate sell order {} of {} kWh of {} smart meters for address: {:?} is stored as proven",
+        sell_order_id,
+        sell_order.energyAmountToBeSold,
+        sell_ids.len(),
+        seller
+    );
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host flow of the consumption guest program: prove that the buyer's consumption baseline is above the amount of
+//! energy to be bought (via the proof backend picked by --prover), and create a buy order with the proof.
+
+use std::path::PathBuf;
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, Bytes, B256, U256},
+    providers::{Provider, ProviderBuilder},
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::input::InputBuilder;
+use energy_types::{ConsumptionInput, ConsumptionJournal, MeterReadings};
+use guests::{ CONSUMPTION_ELF, CONSUMPTION_ID };
+
+use super::{check_freshness, ImageArgs, WalletArgs, TX_TIMEOUT};
+use crate::{
+    backend::{execute, ProofBackend, ProofTask, ProverArgs},
+    energy_aggregator::IEnergyAggregator::{self, IEnergyAggregatorInstance},
+    readings,
+};
+
+/// Arguments of the `buy` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct BuyArgs {
+    /// The energy amount that the buyer wants to buy (Unit: kWh).
+    #[clap(long, env)]
+    pub amount_of_energy_to_be_bought: u64,
+
+    /// Start of the (baseline) window in which the energy was consumed (UTC timestamp).
+    #[clap(long, env)]
+    pub consumption_window_start: u64,
+
+    /// End of the (baseline) window in which the energy was consumed (UTC timestamp).
+    #[clap(long, env)]
+    pub consumption_window_end: u64,
+
+    /// The time at which the latest meter reading is checked (UTC timestamp).
+    #[clap(short, long, env)]
+    pub current_time: u64,
+
+    /// Maximum age (in seconds) of the meter reading at the current time. Must match the EnergyAggregator contract.
+    #[clap(long, env, default_value_t = 3600)]
+    pub max_monitored_time_age: u64,
+
+    /// Merkle root of the meter readings of the buyer's smart meter.
+    #[clap(long, env)]
+    pub consumption_merkle_root: B256,
+
+    /// Path to the JSON file of the readings of the cumulative (import) register of the buyer's smart meter in the window
+    /// (ordered by timestamp), with their sibling hashes up to the consumption merkle root.
+    #[clap(long, env)]
+    pub consumption_readings_file: PathBuf,
+
+    /// SEC1-encoded secp256k1 public key of the buyer's smart meter (hex).
+    #[clap(long, env)]
+    pub consumption_meter_public_key: Bytes,
+
+    /// Signature (r || s) of the consumption merkle root, signed by the buyer's smart meter (hex).
+    #[clap(long, env)]
+    pub consumption_meter_signature: Bytes,
+
+    #[clap(flatten)]
+    pub image: ImageArgs,
+
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Run the `buy` subcommand with the proof backend picked by the --prover flag.
+pub async fn run(args: BuyArgs, prover: ProverArgs) -> Result<()> {
+    // The EnergyAggregator contract is called by the wallet of the buyer, whichever backend generates the proof.
+    let buyer: Address = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key.clone()))
+        .on_http(args.wallet.rpc_url.clone());
+    let backend = prover.into_backend(args.wallet.rpc_url.clone(), args.wallet.wallet_private_key.clone())?;
+    let energy_aggregator = IEnergyAggregatorInstance::new(
+        args.wallet.energy_aggregator_address,
+        provider.clone(),
+    );
+
+    // The proof would be rejected by the contract if the guest program has drifted from the image ID pinned by it.
+    let deployed_image_id = energy_aggregator
+        .consumptionImageId()
+        .call()
+        .await
+        .context("failed to get the consumption image ID from contract")?
+        ._0;
+    args.image.check("consumption", CONSUMPTION_ID, deployed_image_id)?;
+
+    // The consumption baseline is the readings of the cumulative (import) register, so a single register is expected.
+    let meter_readings = match readings::load_meter_readings(&args.consumption_readings_file)? {
+        MeterReadings::Gross(meter_readings) => meter_readings,
+        MeterReadings::Net(_) => bail!("the consumption readings must have a single (import) register"),
+    };
+
+    // The buyer (= caller) and the chain ID are bound into the journal, so that the proof can only be used by this
+    // caller on this chain.
+    let chain_id: u64 = provider.get_chain_id().await.context("failed to get the chain ID")?;
+
+    let input = ConsumptionInput {
+        amount_of_energy_to_be_bought: args.amount_of_energy_to_be_bought,
+        window_start: args.consumption_window_start,
+        window_end: args.consumption_window_end,
+        current_time: args.current_time,
+        max_monitored_time_age: args.max_monitored_time_age,
+        monitored_merkle_root: args.consumption_merkle_root.0,
+        meter_readings,
+        meter_public_key: args.consumption_meter_public_key.to_vec(),
+        meter_signature: args.consumption_meter_signature.to_vec(),
+        buyer: buyer.into_array(),
+        chain_id,
+    };
+    let input_builder = InputBuilder::new().write(&input)?;
+    let guest_env = input_builder.clone().build_env()?;
+
+    // Dry run the ELF with the input to get the journal, so that an unprovable proving request is not sent into
+    // the market (or the prover).
+    let execution = execute(CONSUMPTION_ELF, &guest_env)?;
+
+    let decoded_journal = ConsumptionJournal::decode(&execution.journal).context("failed to decode the journal")?;
+    tracing::info!("decoded journal: {:?}\n", decoded_journal);
+    ensure!(
+        decoded_journal.energyAmountToBeBought == U256::from(args.amount_of_energy_to_be_bought),
+        "the journal does not commit to the amount of energy to be bought"
+    );
+    ensure!(
+        decoded_journal.buyer == buyer && decoded_journal.chainId == U256::from(chain_id),
+        "the journal does not commit to the buyer and the chain ID"
+    );
+
+    // The proof must be generated (and the buy order created) while the meter reading is still fresh.
+    let valid_for = check_freshness(&energy_aggregator, args.current_time, args.max_monitored_time_age).await?;
+
+    // Generate the proof with the backend picked by the --prover flag (See: ./apps/src/backend.rs).
+    let task = ProofTask { execution: &execution, valid_for };
+    let (proven_journal, seal) = backend.prove(CONSUMPTION_ELF, CONSUMPTION_ID, &guest_env, task, &mut ()).await?;
+    ensure!(proven_journal == execution.journal, "the journal of the proof does not match the dry run");
+
+    // Interact with the EnergyAggregator contract by calling the createBuyOrder() function with the consumption
+    // proof attached.
+    let create_buy_order_tx = energy_aggregator
+        .createBuyOrder(
+            decoded_journal.energyAmountToBeBought,
+            decoded_journal.windowStart,
+            decoded_journal.windowEnd,
+            decoded_journal.currentTime,
+            decoded_journal.monitoredMerkleRoot,
+            decoded_journal.meterPublicKeyHash,
+            seal
+        )
+        .from(buyer);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#createBuyOrder() function");
+    let pending_tx = create_buy_order_tx.send().await.context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    let receipt = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .get_receipt()
+        .await
+        .context("failed to confirm tx")?;
+    // @dev - The EnergyAggregator contract reverts a buy order which no open sell order of the same amount matches.
+    ensure!(
+        receipt.status(),
+        "the tx {} calling the EnergyAggregator#createBuyOrder() was reverted (e.g. no open sell order matches the amount)",
+        receipt.transaction_hash
+    );
+    tracing::info!("Tx {:?} confirmed", receipt.transaction_hash);
+
+    // Find the matched sell order from the BuyOrderMatched event in the receipt.
+    let matched = receipt
+        .inner
+        .logs()
+        .iter()
+        .filter(|log| log.address() == *energy_aggregator.address())
+        .find_map(|log| log.log_decode::<IEnergyAggregator::BuyOrderMatched>().ok())
+        .context("the receipt does not have the BuyOrderMatched event of the EnergyAggregator contract")?
+        .inner
+        .data;
+    tracing::info!(
+        "Buy order matched with sell order {} ({} wei escrowed until it is settled)",
+        matched.sellOrderId,
+        matched.paymentAmount
+    );
+
+    Ok(())
+}
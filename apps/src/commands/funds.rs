@@ -0,0 +1,126 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deposit and withdrawal of the native ETH balance of a buyer in the EnergyAggregator contract, from which the
+//! payment of a matched buy order is taken into the escrow.
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{
+        utils::{format_ether, parse_ether},
+        Address, U256,
+    },
+    providers::{Provider, ProviderBuilder},
+    transports::Transport,
+};
+use anyhow::{ensure, Context, Result};
+
+use super::{WalletArgs, TX_TIMEOUT};
+use crate::energy_aggregator::IEnergyAggregator::IEnergyAggregatorInstance;
+
+/// Arguments of the `deposit` and the `withdraw` subcommands.
+#[derive(clap::Args, Debug)]
+pub struct FundsArgs {
+    /// The amount of native ETH to be deposited (or withdrawn) (Unit: ETH, e.g. 0.01).
+    #[clap(long, value_parser = parse_ether)]
+    pub amount: U256,
+
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Deposit native ETH into the buyer's balance.
+pub async fn deposit(args: FundsArgs) -> Result<()> {
+    let buyer: Address = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key))
+        .on_http(args.wallet.rpc_url);
+    let energy_aggregator = IEnergyAggregatorInstance::new(args.wallet.energy_aggregator_address, provider);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#depositNativeETH() function");
+    let pending_tx = energy_aggregator
+        .depositNativeETH()
+        .value(args.amount)
+        .from(buyer)
+        .send()
+        .await
+        .context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    let tx_hash = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .watch()
+        .await
+        .context("failed to confirm tx")?;
+    tracing::info!("Tx {:?} confirmed", tx_hash);
+
+    print_balance(&energy_aggregator, buyer).await
+}
+
+/// Withdraw native ETH from the buyer's balance.
+pub async fn withdraw(args: FundsArgs) -> Result<()> {
+    let buyer: Address = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key))
+        .on_http(args.wallet.rpc_url);
+    let energy_aggregator = IEnergyAggregatorInstance::new(args.wallet.energy_aggregator_address, provider);
+
+    // The balance is checked before broadcasting, since the tx would be reverted otherwise.
+    let balance = energy_aggregator
+        .buyerBalances(buyer)
+        .call()
+        .await
+        .context("failed to get the buyer balance from contract")?
+        ._0;
+    ensure!(
+        balance >= args.amount,
+        "the balance {} ETH is insufficient to withdraw {} ETH",
+        format_ether(balance),
+        format_ether(args.amount)
+    );
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#withdrawNativeETH() function");
+    let pending_tx = energy_aggregator
+        .withdrawNativeETH(args.amount)
+        .from(buyer)
+        .send()
+        .await
+        .context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    let tx_hash = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .watch()
+        .await
+        .context("failed to confirm tx")?;
+    tracing::info!("Tx {:?} confirmed", tx_hash);
+
+    print_balance(&energy_aggregator, buyer).await
+}
+
+/// Print the buyer's balance in the EnergyAggregator contract.
+async fn print_balance<T, P>(energy_aggregator: &IEnergyAggregatorInstance<T, P>, buyer: Address) -> Result<()>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let balance = energy_aggregator
+        .buyerBalances(buyer)
+        .call()
+        .await
+        .context("failed to get the buyer balance from contract")?
+        ._0;
+    println!("Balance of {}: {} ETH", buyer, format_ether(balance));
+    Ok(())
+}
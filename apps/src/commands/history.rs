@@ -0,0 +1,98 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read the sells recorded in the local store (See: ./apps/src/store.rs), e.g. what has already been sold, without
+//! scanning the chain.
+
+use alloy::primitives::B256;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+
+use crate::store::StoreArgs;
+
+/// Subcommands of the `history` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum HistoryCommand {
+    /// List the sells submitted by this app (oldest first).
+    List {
+        /// Only list the sells of the monitored merkle root, and sum up the energy sold from it.
+        #[clap(long)]
+        monitored_merkle_root: Option<B256>,
+
+        #[clap(flatten)]
+        store: StoreArgs,
+    },
+    /// Show a sell and each step of its lifecycle in detail.
+    Show {
+        /// The ID of the sell in the store.
+        id: i64,
+
+        #[clap(flatten)]
+        store: StoreArgs,
+    },
+}
+
+/// Run the `history` subcommand.
+pub fn run(command: HistoryCommand) -> Result<()> {
+    match command {
+        HistoryCommand::List { monitored_merkle_root, store } => {
+            let store = store.open()?;
+            println!("{:>4}  {:<9}  {:>10}  {:>21}  {:<9}  {:>10}  NULLIFIER", "ID", "STATUS", "AMOUNT", "WINDOW", "PROVER", "SELL ORDER");
+            for sell in store.list_sells(monitored_merkle_root)? {
+                println!(
+                    "{:>4}  {:<9}  {:>6} kWh  {:>10}-{:<10}  {:<9}  {:>10}  {}",
+                    sell.id,
+                    sell.status,
+                    sell.amount_of_energy_to_be_sold,
+                    sell.window_start,
+                    sell.window_end,
+                    sell.prover,
+                    sell.sell_order_id.map_or_else(|| "-".to_string(), |id| id.to_string()),
+                    sell.nullifier
+                );
+            }
+            if let Some(monitored_merkle_root) = monitored_merkle_root {
+                println!("Sold from {}: {} kWh", monitored_merkle_root, store.sold(monitored_merkle_root)?);
+            }
+        }
+        HistoryCommand::Show { id, store } => {
+            let store = store.open()?;
+            let sell = store.get_sell(id)?.with_context(|| format!("the sell {} is not in the store", id))?;
+            println!("Sell:                  {}", sell.id);
+            println!("Status:                {}", sell.status);
+            println!("Energy amount:         {} kWh", sell.amount_of_energy_to_be_sold);
+            println!("Window:                {} - {}", sell.window_start, sell.window_end);
+            println!("Monitored merkle root: {}", sell.monitored_merkle_root);
+            println!("Monitored nullifier:   {}", sell.nullifier);
+            println!("Prover:                {}", sell.prover);
+            if let Some(request_id) = sell.request_id {
+                println!("Proof request:         {:#x} (expires at {})", request_id, sell.expires_at.unwrap_or_default());
+            }
+            if let Some(tx_hash) = sell.tx_hash {
+                println!("Tx:                    {}", tx_hash);
+            }
+            if let Some(sell_order_id) = sell.sell_order_id {
+                println!("Sell order:            {}", sell_order_id);
+            }
+            if let Some(error) = &sell.error {
+                println!("Error:                 {}", error);
+            }
+            println!("Steps:");
+            for (status, detail, at) in store.list_steps(id)? {
+                println!("  {:>10}  {:<9}  {}", at, status, detail.unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Subcommands of the app, which cover the whole trading lifecycle: selling (and estimating) energy, buying it,
+//! settling the delivery, aggregating a microgrid, managing the deposit of a buyer, reading the state of the sell
+//! orders, of the proof requests and of the sells recorded in the local store, and resuming an interrupted sell. (Plus, generating synthetic smart-meter data for testing and demos)
+
+use std::time::Duration;
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::{Address, B256, U256},
+    providers::Provider,
+    rpc::types::BlockTransactionsKind,
+    signers::local::PrivateKeySigner,
+    transports::Transport,
+};
+use anyhow::{ensure, Context, Result};
+use risc0_zkvm::sha::Digest;
+use url::Url;
+
+use crate::energy_aggregator::IEnergyAggregator::IEnergyAggregatorInstance;
+
+pub mod aggregate;
+pub mod buy;
+pub mod funds;
+pub mod history;
+pub mod orders;
+pub mod request;
+pub mod resume;
+pub mod sell;
+pub mod settle;
+pub mod simulate;
+
+/// Timeout for the transaction to be confirmed.
+pub const TX_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Arguments of the subcommands which send txs to the EnergyAggregator contract.
+#[derive(clap::Args, Debug)]
+pub struct WalletArgs {
+    /// URL of the Ethereum RPC endpoint.
+    #[clap(short, long, env)]
+    pub rpc_url: Url,
+    /// Private key used to interact with the EnergyAggregator contract.
+    #[clap(short, long, env)]
+    pub wallet_private_key: PrivateKeySigner,
+    /// Address of the EnergyAggregator contract.
+    #[clap(short, long, env)]
+    pub energy_aggregator_address: Address,
+}
+
+/// Arguments of the subcommands which only read the EnergyAggregator contract (i.e. no wallet is needed).
+#[derive(clap::Args, Debug)]
+pub struct ContractArgs {
+    /// URL of the Ethereum RPC endpoint.
+    #[clap(short, long, env)]
+    pub rpc_url: Url,
+    /// Address of the EnergyAggregator contract.
+    #[clap(short, long, env)]
+    pub energy_aggregator_address: Address,
+}
+
+/// Argument of the subcommands which prove a guest program, whose image ID must be pinned by the deployed contract.
+#[derive(clap::Args, Debug)]
+pub struct ImageArgs {
+    /// Continue even if the image ID of the guest program does not match the one of the deployed EnergyAggregator
+    /// contract (e.g. to test a modified guest program). Otherwise, the proof would be rejected by the contract.
+    #[clap(long)]
+    pub allow_image_mismatch: bool,
+}
+
+impl ImageArgs {
+    /// Check that the image ID of the guest program matches the one of the deployed EnergyAggregator contract, before
+    /// anything is paid for a proof which would be rejected.
+    pub fn check(&self, guest_program: &str, image_id: [u32; 8], deployed_image_id: B256) -> Result<()> {
+        let image_id = B256::from_slice(Digest::from(image_id).as_bytes());
+        if image_id == deployed_image_id {
+            return Ok(());
+        }
+        ensure!(
+            self.allow_image_mismatch,
+            "the image ID {} of the {} guest program does not match the image ID {} of the deployed EnergyAggregator \
+             contract; rebuild the guests or redeploy the contract (or pass --allow-image-mismatch)",
+            image_id,
+            guest_program,
+            deployed_image_id
+        );
+        tracing::warn!(
+            "The image ID {} of the {} guest program does not match the image ID {} of the deployed EnergyAggregator contract",
+            image_id,
+            guest_program,
+            deployed_image_id
+        );
+        Ok(())
+    }
+}
+
+/// Check that a journal committing to the current_time (and the max_monitored_time_age) would still be accepted as
+/// fresh by the EnergyAggregator contract at the latest block, before anything is paid for its proof (or its tx).
+/// Returns how long (in seconds) the journal stays fresh from the latest block.
+pub async fn check_freshness<T, P>(
+    energy_aggregator: &IEnergyAggregatorInstance<T, P>,
+    current_time: u64,
+    max_monitored_time_age: u64,
+) -> Result<u64>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let deployed_max_monitored_time_age = energy_aggregator
+        .maxMonitoredTimeAge()
+        .call()
+        .await
+        .context("failed to get the max monitored time age from contract")?
+        ._0;
+    ensure!(
+        deployed_max_monitored_time_age == U256::from(max_monitored_time_age),
+        "the max_monitored_time_age {} does not match the one {} of the deployed EnergyAggregator contract",
+        max_monitored_time_age,
+        deployed_max_monitored_time_age
+    );
+    let latest_block = energy_aggregator
+        .provider()
+        .get_block_by_number(BlockNumberOrTag::Latest, BlockTransactionsKind::Hashes)
+        .await
+        .context("failed to get the latest block")?
+        .context("the latest block is not found")?;
+    let block_time = latest_block.header.timestamp;
+    let age = block_time.saturating_sub(current_time);
+    ensure!(
+        age <= max_monitored_time_age,
+        "the meter reading is too old to be accepted: its current_time {} is {} seconds before the latest block (max: {} seconds)",
+        current_time,
+        age,
+        max_monitored_time_age
+    );
+    Ok(max_monitored_time_age - age)
+}
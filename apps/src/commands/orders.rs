@@ -0,0 +1,149 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read the sell orders (and the escrows of the matched ones) stored in the EnergyAggregator contract.
+
+use alloy::{
+    primitives::{utils::format_ether, U256},
+    providers::{Provider, ProviderBuilder},
+    transports::Transport,
+};
+use anyhow::{ensure, Context, Result};
+use clap::Subcommand;
+
+use super::ContractArgs;
+use crate::energy_aggregator::IEnergyAggregator::{self, IEnergyAggregatorInstance};
+
+/// Subcommands of the `orders` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum OrdersCommand {
+    /// List all the sell orders.
+    List {
+        #[clap(flatten)]
+        contract: ContractArgs,
+    },
+    /// Show a sell order and its escrow in detail.
+    Show {
+        /// The ID of the sell order (starting from 1).
+        id: u64,
+
+        #[clap(flatten)]
+        contract: ContractArgs,
+    },
+}
+
+/// Run the `orders` subcommand.
+pub async fn run(command: OrdersCommand) -> Result<()> {
+    match command {
+        OrdersCommand::List { contract } => {
+            let provider = ProviderBuilder::new().on_http(contract.rpc_url);
+            let energy_aggregator = IEnergyAggregatorInstance::new(contract.energy_aggregator_address, provider);
+            let sell_order_count = sell_order_count(&energy_aggregator).await?;
+
+            println!("{:>4}  {:<42}  {:>10}  {:>21}  {:>8}", "ID", "SELLER", "AMOUNT", "WINDOW", "STATUS");
+            for id in 1..=sell_order_count {
+                let (sell_order, settled) = get_sell_order(&energy_aggregator, id).await?;
+                println!(
+                    "{:>4}  {:<42}  {:>6} kWh  {:>10}-{:<10}  {:>8}",
+                    id,
+                    sell_order.energySeller,
+                    sell_order.energyAmountToBeSold,
+                    sell_order.windowStart,
+                    sell_order.windowEnd,
+                    status(&sell_order, settled)
+                );
+            }
+        }
+        OrdersCommand::Show { id, contract } => {
+            let provider = ProviderBuilder::new().on_http(contract.rpc_url);
+            let energy_aggregator = IEnergyAggregatorInstance::new(contract.energy_aggregator_address, provider);
+            let sell_order_count = sell_order_count(&energy_aggregator).await?;
+            ensure!((1..=sell_order_count).contains(&id), "the sell order {} does not exist (there are {} sell orders)", id, sell_order_count);
+
+            let (sell_order, settled) = get_sell_order(&energy_aggregator, id).await?;
+            println!("Sell order:            {}", id);
+            println!("Status:                {}", status(&sell_order, settled));
+            println!("Seller:                {}", sell_order.energySeller);
+            println!("Energy amount:         {} kWh", sell_order.energyAmountToBeSold);
+            println!("Window:                {} - {}", sell_order.windowStart, sell_order.windowEnd);
+            println!("Monitored merkle root: {}", sell_order.monitoredMerkleRoot);
+            println!("Monitored nullifier:   {}", sell_order.monitoredNullifier);
+            println!("Image ID:              {}", sell_order.imageId);
+            println!("Journal digest:        {}", sell_order.journal);
+            if sell_order.orderMatched {
+                let escrow = energy_aggregator
+                    .escrows(U256::from(id))
+                    .call()
+                    .await
+                    .context("failed to get the escrow from contract")?;
+                println!("Buyer:                 {}", escrow.energyBuyer);
+                println!("Payment (in escrow):   {} ETH", format_ether(escrow.paymentAmount));
+                println!("Matched at:            {}", escrow.matchedAt);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The number of the sell orders, whose IDs are 1 to this number.
+async fn sell_order_count<T, P>(energy_aggregator: &IEnergyAggregatorInstance<T, P>) -> Result<u64>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let sell_order_id = energy_aggregator
+        .sellOrderId()
+        .call()
+        .await
+        .context("failed to get the latest sell order ID from contract")?
+        ._0;
+    u64::try_from(sell_order_id).context("the latest sell order ID does not fit in u64")
+}
+
+/// Get a sell order and whether its escrow has already been settled.
+async fn get_sell_order<T, P>(
+    energy_aggregator: &IEnergyAggregatorInstance<T, P>,
+    id: u64,
+) -> Result<(IEnergyAggregator::SellOrder, bool)>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let sell_order = energy_aggregator
+        .getSellOrder(U256::from(id))
+        .call()
+        .await
+        .with_context(|| format!("failed to get the sell order {} from contract", id))?
+        ._0;
+    let settled = if sell_order.orderMatched {
+        energy_aggregator
+            .escrows(U256::from(id))
+            .call()
+            .await
+            .with_context(|| format!("failed to get the escrow of the sell order {} from contract", id))?
+            .settled
+    } else {
+        false
+    };
+    Ok((sell_order, settled))
+}
+
+/// Status of a sell order in its lifecycle: open -> matched -> settled.
+fn status(sell_order: &IEnergyAggregator::SellOrder, settled: bool) -> &'static str {
+    match (sell_order.orderMatched, settled) {
+        (false, _) => "open",
+        (true, false) => "matched",
+        (true, true) => "settled",
+    }
+}
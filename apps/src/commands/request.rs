@@ -0,0 +1,59 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read the status of a proof request submitted to the Boundless market (e.g. by the `sell` subcommand with
+//! --prover boundless), so that it can be followed without re-running the flow.
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::ProviderBuilder,
+};
+use anyhow::{Context, Result};
+use boundless_market::contracts::boundless_market::BoundlessMarketService;
+use clap::Subcommand;
+use url::Url;
+
+/// Subcommands of the `request` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum RequestCommand {
+    /// Show the status of a proof request (unknown, locked, fulfilled or expired).
+    Status {
+        /// The ID of the proof request (hex or decimal), which is logged when the request is submitted.
+        id: U256,
+
+        /// URL of the Ethereum RPC endpoint.
+        #[clap(short, long, env)]
+        rpc_url: Url,
+        /// Address of the BoundlessMarket contract.
+        #[clap(short, long, env)]
+        boundless_market_address: Address,
+    },
+}
+
+/// Run the `request` subcommand.
+pub async fn run(command: RequestCommand) -> Result<()> {
+    match command {
+        RequestCommand::Status { id, rpc_url, boundless_market_address } => {
+            let provider = ProviderBuilder::new().on_http(rpc_url);
+            // @dev - The caller is only used to send txs, so it is not needed to read the status.
+            let boundless_market = BoundlessMarketService::new(boundless_market_address, provider, Address::ZERO);
+            let status = boundless_market
+                .get_status(id, None)
+                .await
+                .context("failed to get the status of the proof request from the BoundlessMarket contract")?;
+            println!("Request {:#x}: {:?}", id, status);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resume a sell whose app died after its proof request was submitted to the Boundless market (e.g. while waiting for
+//! the request to be fulfilled), so that the paid proof request is not orphaned. The original input and journal are
+//! reloaded from the local store, the proof is waited for (or fetched, if the request is already fulfilled), and the
+//! sell order is created with it.
+
+use std::time::Duration;
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, B256, U256},
+    providers::{Provider, ProviderBuilder},
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::contracts::{boundless_market::BoundlessMarketService, ProofStatus};
+use energy_types::{SmartMeterInput, SmartMeterJournal};
+use guests::SMART_METER_ID;
+
+use super::{check_freshness, sell::create_sell_order, ImageArgs, WalletArgs};
+use crate::{
+    energy_aggregator::IEnergyAggregator::IEnergyAggregatorInstance,
+    nullifiers::{find_sell_order_by_nullifier, is_nullifier_used},
+    store::StoreArgs,
+};
+
+/// Arguments of the `resume` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct ResumeArgs {
+    /// The ID of the proof request (hex or decimal), which is recorded in the local store (See: the `history`
+    /// subcommand).
+    #[clap(long)]
+    pub request_id: U256,
+
+    /// Address of the BoundlessMarket contract.
+    #[clap(short, long, env)]
+    pub boundless_market_address: Address,
+
+    #[clap(flatten)]
+    pub store: StoreArgs,
+
+    #[clap(flatten)]
+    pub image: ImageArgs,
+
+    // @dev - The wallet must be the one of the seller which submitted the proof request.
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Run the `resume` subcommand.
+pub async fn run(args: ResumeArgs) -> Result<()> {
+    let store = args.store.open()?;
+    let sell = store.find_sell_by_request_id(args.request_id)?.with_context(|| {
+        format!("the proof request {:#x} is not recorded in the store {}", args.request_id, args.store.store_file.display())
+    })?;
+    tracing::info!("Resuming the sell {} (status: {})", sell.id, sell.status);
+    if let Some(sell_order_id) = sell.sell_order_id {
+        bail!("the sell {} has already created the sell order {}", sell.id, sell_order_id);
+    }
+
+    // Reload the original input and journal, which must have been submitted by this wallet (= seller).
+    let seller = args.wallet.wallet_private_key.address();
+    let input: SmartMeterInput = serde_json::from_str(&sell.input).context("failed to parse the input recorded in the store")?;
+    let decoded_journal = SmartMeterJournal::decode(&sell.journal).context("failed to decode the journal recorded in the store")?;
+    ensure!(
+        Address::from(input.seller) == seller && decoded_journal.seller == seller,
+        "the sell {} was submitted by the seller {}, not by the wallet {}",
+        sell.id,
+        Address::from(input.seller),
+        seller
+    );
+    ensure!(
+        decoded_journal.monitoredNullifier == B256::from(input.monitored_nullifier) && decoded_journal.monitoredNullifier == sell.nullifier,
+        "the journal recorded in the store does not commit to the nullifier of its input"
+    );
+
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key.clone()))
+        .on_http(args.wallet.rpc_url.clone());
+    let chain_id = provider.get_chain_id().await.context("failed to get the chain ID")?;
+    ensure!(input.chain_id == chain_id, "the sell {} was submitted on the chain {}, not on the chain {}", sell.id, input.chain_id, chain_id);
+    let energy_aggregator = IEnergyAggregatorInstance::new(args.wallet.energy_aggregator_address, provider.clone());
+
+    // The proof would be rejected by the contract if the guest program has drifted from the image ID pinned by it.
+    let deployed_image_id = energy_aggregator.imageId().call().await.context("failed to get the image ID from contract")?._0;
+    args.image.check("smart-meter", SMART_METER_ID, deployed_image_id)?;
+
+    // The sell order may have been created before the app died (e.g. the tx was included after it was broadcast).
+    if is_nullifier_used(&energy_aggregator, sell.nullifier).await? {
+        let sell_order_id = find_sell_order_by_nullifier(&energy_aggregator, sell.nullifier)
+            .await?
+            .with_context(|| format!("the nullifier {} of the sell {} has already been used by an aggregate sell order", sell.nullifier, sell.id))?;
+        store.set_created(sell.id, U256::from(sell_order_id))?;
+        println!("Sell {} had already created the sell order {}", sell.id, sell_order_id);
+        return Ok(());
+    }
+
+    // The meter reading must still be fresh at the latest block, since the contract would reject the sell order
+    // otherwise. A stale sell can not be resumed, so it is marked as failed (and its nullifier can be submitted again
+    // with a fresh current_time).
+    let freshness = check_freshness(&energy_aggregator, input.current_time, input.max_monitored_time_age)
+        .await
+        .with_context(|| format!("the sell {} is stale, so its sell order can no longer be created", sell.id));
+    let valid_for = store.record_failure(sell.id, freshness)?;
+    tracing::info!("The meter reading of the sell {} stays fresh for {} seconds", sell.id, valid_for);
+
+    // The sell order must still chain from the latest sold commitment of the monitored merkle root, which may have
+    // been updated by another sell order in the meantime. Otherwise, the on-chain verification would fail.
+    let latest_sold_commitment = energy_aggregator
+        .soldCommitments(decoded_journal.monitoredMerkleRoot)
+        .call()
+        .await
+        .context("failed to get the latest sold commitment from contract")?
+        ._0;
+    ensure!(
+        latest_sold_commitment == decoded_journal.previousSoldCommitment,
+        "the proof of the sell {} does not chain from the latest sold commitment {} of the monitored merkle root",
+        sell.id,
+        latest_sold_commitment
+    );
+
+    // Wait for the proof request to be fulfilled, or fetch its proof if it is already fulfilled.
+    // (NOTE: The seal is already in the store if the app died after the proof was generated)
+    let seal = match sell.seal {
+        Some(seal) => seal,
+        None => {
+            let expires_at = sell.expires_at.with_context(|| format!("the expiry of the proof request {:#x} is not recorded", args.request_id))?;
+            let boundless_market = BoundlessMarketService::new(args.boundless_market_address, provider.clone(), seller);
+            tracing::info!("Waiting for 0x{:x} to be fulfilled", args.request_id);
+            let (journal, seal) = match boundless_market
+                .wait_for_request_fulfillment(args.request_id, Duration::from_secs(5), expires_at)
+                .await
+                .context("failed to wait for the proof request to be fulfilled")
+            {
+                Ok(fulfillment) => fulfillment,
+                // @dev - Only an expired request can no longer be fulfilled, so the sell is marked as failed. Otherwise
+                //        (e.g. the RPC endpoint is unreachable), the paid request may still be fulfilled, so the sell is
+                //        left as requested to be resumed again.
+                Err(err) => match boundless_market.get_status(args.request_id, Some(expires_at)).await {
+                    Ok(ProofStatus::Expired) => {
+                        let err = err.context(format!("the proof request {:#x} has expired", args.request_id));
+                        store.set_failed(sell.id, &format!("{:#}", err))?;
+                        return Err(err);
+                    }
+                    _ => return Err(err.context(format!("the proof request {:#x} can be resumed again", args.request_id))),
+                },
+            };
+            tracing::info!("Request 0x{:x} fulfilled", args.request_id);
+            ensure!(journal.as_ref() == sell.journal.as_slice(), "the journal of the proof does not match the one recorded in the store");
+            store.set_proven(sell.id, &seal)?;
+            seal
+        }
+    };
+
+    // Finish the on-chain submission in the same way as the `sell` subcommand.
+    let sell_order_id = create_sell_order(&energy_aggregator, seller, &decoded_journal, seal, &store, sell.id).await?;
+    println!("Sell {} resumed: the sell order {} is created", sell.id, sell_order_id);
+
+    Ok(())
+}
@@ -0,0 +1,524 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host flow of the smart-meter guest program: prove that the energy amount to be sold is backed by the meter readings
+//! of the seller's smart meter (via the proof backend picked by --prover), and create a sell order with the proof.
+//! The `estimate` subcommand only dry runs the same input, with the given seller and chain ID instead of a wallet.
+
+use std::path::PathBuf;
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, Bytes, B256, U256},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::TransactionReceipt,
+    transports::Transport,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::input::{GuestEnv, InputBuilder};
+use energy_types::{ compute_nullifier, compute_sold_commitment, SmartMeterInput, SmartMeterJournal };
+use guests::{ SMART_METER_ELF, SMART_METER_ID }; // "ELF" and "image ID" (ImageID.sol#IS_SMART_METER_ID)
+
+use super::{check_freshness, ImageArgs, WalletArgs, TX_TIMEOUT};
+use crate::{
+    backend::{execute, Execution, ProofBackend, ProofTask, ProverArgs},
+    energy_aggregator::IEnergyAggregator::{self, IEnergyAggregatorInstance},
+    estimate::{Estimate, OutputFormat},
+    nullifiers::is_nullifier_used,
+    readings,
+    store::{NewSell, SellRecorder, SellStatus, Store, StoreArgs},
+};
+
+/// Arguments of a sell order, which are shared by the `sell` and the `estimate` subcommands.
+#[derive(clap::Args, Debug)]
+pub struct SellArgs {
+    /// Path to the CSV, JSON or Green Button (ESPI XML) file of the raw meter readings of the window (ordered by
+    /// timestamp), without their hash paths. The merkle tree, the monitored merkle root, the window and the energy
+    /// available to be sold are derived from them (See: ./apps/src/readings.rs). Otherwise, all of them must be given.
+    #[clap(long, env = "READINGS_FILE")]
+    pub readings: Option<PathBuf>,
+
+    /// The energy amount to be sold (Unit: kWh). With --readings, all the energy still available in the window is
+    /// sold by default.
+    #[clap(short, long, env, required_unless_present = "readings")]
+    pub amount_of_energy_to_be_sold: Option<u64>, // @dev - Used in CLI as an option / The energyAmountToBeSold to publish to the EnergyAggregator contract.
+
+    /// Start of the window in which the energy was produced (UTC timestamp).
+    #[clap(long, env, required_unless_present = "readings")]
+    pub window_start: Option<u64>,
+
+    /// End of the window in which the energy was produced (UTC timestamp).
+    #[clap(long, env, required_unless_present = "readings")]
+    pub window_end: Option<u64>,
+
+    /// The time at which the latest meter reading is checked (UTC timestamp).
+    #[clap(short, long, env)]
+    pub current_time: u64,
+
+    /// Maximum age (in seconds) of the meter reading at the current time. Must match the EnergyAggregator contract.
+    #[clap(long, env, default_value_t = 3600)]
+    pub max_monitored_time_age: u64,
+
+    #[clap(short = 'q', long, env, required_unless_present = "readings")]
+    pub monitored_merkle_root: Option<B256>,
+
+    /// Path to the JSON file of the meter readings in the window (ordered by timestamp), with their
+    /// sibling hashes up to the monitored merkle root.
+    #[clap(long, env, required_unless_present = "readings")]
+    pub meter_readings_file: Option<PathBuf>,
+
+    /// SEC1-encoded secp256k1 public key of the smart meter (hex).
+    #[clap(long, env)]
+    pub meter_public_key: Bytes,
+
+    /// Signature (r || s) of the monitored merkle root, signed by the smart meter (hex).
+    #[clap(long, env)]
+    pub meter_signature: Bytes,
+
+    /// (Optional) Expected nullifier of the sell order. The nullifier is always computed from the input data,
+    /// and this is only used to check that the computed one is the expected one.
+    #[clap(short, long, env)]
+    pub monitored_nullifier: Option<B256>,
+
+    /// The energy amount already sold by the previous sell orders of the same meter readings (i.e. the same
+    /// monitored merkle root). Its commitment must be the latest one stored in the EnergyAggregator contract.
+    #[clap(long, env, default_value_t = 0)]
+    pub previously_sold: u64,
+}
+
+/// Arguments of the `sell` subcommand, which are not needed by the `estimate` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct SubmitArgs {
+    #[clap(flatten)]
+    pub image: ImageArgs,
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+    #[clap(flatten)]
+    pub store: StoreArgs,
+    /// Submit even if the nullifier has already been submitted by this app (e.g. the previous submission is stuck).
+    /// The nullifier is still checked against the sell orders on-chain.
+    #[clap(long)]
+    pub allow_resubmit: bool,
+}
+
+/// Arguments of the `estimate` subcommand, which stand in for the wallet of the `sell` subcommand. The seller and the
+/// chain ID are bound into the nullifier and the journal, so no signing key nor RPC endpoint is needed to estimate.
+#[derive(clap::Args, Debug)]
+pub struct EstimateArgs {
+    /// Address of the seller, i.e. of the wallet which would create the sell order.
+    #[clap(long, env)]
+    pub seller: Address,
+    /// Chain ID of the chain on which the sell order would be created.
+    #[clap(long, env)]
+    pub chain_id: u64,
+}
+
+/// Run the `estimate` subcommand: dry run the smart-meter guest program, and print the decoded journal and the cost of
+/// proving it in the given format. Nothing is read from (nor submitted to) the chain, so the on-chain checks of the
+/// `sell` subcommand (i.e. the image ID, the latest sold commitment and the nullifier) are not done.
+pub fn estimate(args: SellArgs, estimate: EstimateArgs, format: OutputFormat) -> Result<()> {
+    let input = build_input(&args, estimate.seller, estimate.chain_id)?;
+    let (_, execution, decoded_journal) = dry_run(&input)?;
+    Estimate::new(decoded_journal, &execution)?.print(format)
+}
+
+/// Run the `sell` subcommand.
+pub async fn run(args: SellArgs, submit: SubmitArgs, prover: ProverArgs) -> Result<()> {
+    // The EnergyAggregator contract is called by the wallet of the seller, whichever prover generates the proof.
+    let input_seller: Address = submit.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(submit.wallet.wallet_private_key.clone()))
+        .on_http(submit.wallet.rpc_url.clone());
+
+    // The seller (= caller) and the chain ID are bound into the nullifier and the journal, so that the proof can only
+    // be used by this caller on this chain.
+    let input_chain_id: u64 = provider.get_chain_id().await.context("failed to get the chain ID")?;
+    let input = build_input(&args, input_seller, input_chain_id)?;
+    let monitored_merkle_root = B256::from(input.monitored_merkle_root);
+    let monitored_nullifier = B256::from(input.monitored_nullifier);
+
+    // The sell order must chain from the latest sold commitment of the monitored merkle root, which is stored in the
+    // EnergyAggregator contract. Check it before proving, since the on-chain verification would fail otherwise.
+    let energy_aggregator = IEnergyAggregatorInstance::new(
+        submit.wallet.energy_aggregator_address,
+        provider.clone(), // @dev - a provider with the wallet of the seller
+    );
+
+    // The proof would be rejected by the contract if the guest program has drifted from the image ID pinned by it.
+    let deployed_image_id = energy_aggregator
+        .imageId() // @dev - Call the EnergyAggregator#imageId() getter
+        .call()
+        .await
+        .context("failed to get the image ID from contract")?
+        ._0;
+    submit.image.check("smart-meter", SMART_METER_ID, deployed_image_id)?;
+    let latest_sold_commitment = energy_aggregator
+        .soldCommitments(monitored_merkle_root) // @dev - Call the EnergyAggregator#soldCommitments() getter
+        .call()
+        .await
+        .context("failed to get the latest sold commitment from contract")?
+        ._0;
+    ensure!(
+        latest_sold_commitment == B256::from(input.previous_sold_commitment),
+        "the previously_sold {} does not match the latest sold commitment {} of the monitored merkle root",
+        input.previously_sold,
+        latest_sold_commitment
+    );
+    tracing::info!("'input_previously_sold' to publish: {}\n", input.previously_sold);
+
+    // The nullifier must not have been used by any sell order (whichever seal it was submitted with).
+    // Check it before proving, so that a reused nullifier is refused before paying for a proof.
+    if is_nullifier_used(&energy_aggregator, monitored_nullifier).await? {
+        bail!("the nullifier {} has already been used by a sell order", monitored_nullifier);
+    }
+
+    let (guest_env, execution, decoded_journal) = dry_run(&input)?;
+
+    // The proof request must be fulfilled (and the sell order created) while the meter reading is still fresh.
+    let valid_for = check_freshness(&energy_aggregator, input.current_time, input.max_monitored_time_age).await?;
+
+    // The nullifier must not have been submitted by this app before, since a proof may have been paid for already
+    // (even if its sell order has not been created yet). A failed submission can be submitted again.
+    let store = submit.store.open()?;
+    if let Some(submitted) = store.find_sell_by_nullifier(decoded_journal.monitoredNullifier)? {
+        if submitted.status == SellStatus::Failed {
+            tracing::warn!("The nullifier {} was submitted at {}, but it failed: {:?}", submitted.nullifier, submitted.created_at, submitted.error);
+        } else {
+            // @dev - A paid proof request can be resumed instead of paying for another one.
+            let resume_hint = submitted
+                .request_id
+                .filter(|_| submitted.sell_order_id.is_none())
+                .map(|request_id| format!(" (or resume its proof request with `resume --request-id {:#x}`)", request_id))
+                .unwrap_or_default();
+            ensure!(
+                submit.allow_resubmit,
+                "the nullifier {} has already been submitted at {} (status: {}, sell order: {:?}); pass --allow-resubmit to submit it again{}",
+                submitted.nullifier,
+                submitted.created_at,
+                submitted.status,
+                submitted.sell_order_id,
+                resume_hint
+            );
+            tracing::warn!("Resubmitting the nullifier {}, which has already been submitted", submitted.nullifier);
+        }
+    }
+    let sell_id = store.insert_sell(&NewSell {
+        nullifier: decoded_journal.monitoredNullifier,
+        monitored_merkle_root,
+        amount_of_energy_to_be_sold: input.amount_of_energy_to_be_sold,
+        window_start: input.window_start,
+        window_end: input.window_end,
+        prover: &prover.prover.to_string(),
+        input: &serde_json::to_string(&input).context("failed to serialize the input")?,
+        journal: &execution.journal,
+    })?;
+    tracing::info!("Sell {} recorded in the store {}", sell_id, submit.store.store_file.display());
+
+    // Generate the proof with the backend picked by the --prover flag (See: ./apps/src/backend.rs).
+    let backend = prover.into_backend(submit.wallet.rpc_url.clone(), submit.wallet.wallet_private_key.clone())?;
+    let task = ProofTask { execution: &execution, valid_for };
+    let proof = backend.prove(SMART_METER_ELF, SMART_METER_ID, &guest_env, task, &mut SellRecorder { store: &store, id: sell_id }).await;
+    let (proven_journal, seal) = store.record_failure(sell_id, proof)?;
+    ensure!(proven_journal == execution.journal, "the journal of the proof does not match the dry run");
+    store.set_proven(sell_id, &seal)?;
+
+    // Create the sell order with the proof (See: create_sell_order()).
+    create_sell_order(&energy_aggregator, input_seller, &decoded_journal, seal, &store, sell_id).await?;
+
+    Ok(())
+}
+
+/// Build the input of the smart-meter guest program from the arguments, for the given seller and chain ID, and
+/// compute its nullifier in the same way as the guest program. (Shared by the `sell` and the `estimate` subcommands)
+fn build_input(args: &SellArgs, input_seller: Address, input_chain_id: u64) -> Result<SmartMeterInput> {
+    // Encode the input.
+    tracing::info!("arg.amount_of_energy_to_be_sold: {:?}\n", args.amount_of_energy_to_be_sold);
+    tracing::info!("arg.window_start: {:?}\n", args.window_start);
+    tracing::info!("arg.window_end: {:?}\n", args.window_end);
+    tracing::info!("arg.current_time: {}\n", args.current_time);
+    tracing::info!("arg.readings: {:?}\n", args.readings);
+    tracing::info!("arg.meter_readings_file: {:?}\n", args.meter_readings_file);
+    tracing::info!("arg.monitored_merkle_root: {:?}\n", args.monitored_merkle_root);
+
+    // The meter readings are either ingested from the raw meter readings (--readings), from which all the values
+    // checked by the guest program are derived, or loaded with the hand-computed values.
+    let (input_window_start, input_window_end, monitored_merkle_root, input_meter_readings, available) = match &args.readings {
+        Some(path) => {
+            let ingested = readings::ingest_meter_readings(&readings::load_raw_meter_readings(path)?)?;
+            tracing::info!("derived monitored_merkle_root: {}\n", ingested.monitored_merkle_root);
+            tracing::info!("derived window: {} - {}\n", ingested.window_start, ingested.window_end);
+            tracing::info!("derived energy available in the window: {} kWh\n", ingested.available);
+            // @dev - The given values (e.g. left in the .env) must be the same as the derived ones.
+            for (name, given, derived) in [
+                ("window_start", args.window_start, ingested.window_start),
+                ("window_end", args.window_end, ingested.window_end),
+            ] {
+                ensure!(given.map_or(true, |given| given == derived), "the given {} {:?} does not match the {} derived from the readings", name, given, derived);
+            }
+            ensure!(
+                args.monitored_merkle_root.map_or(true, |given| given == ingested.monitored_merkle_root),
+                "the given monitored_merkle_root {:?} does not match the {} derived from the readings",
+                args.monitored_merkle_root,
+                ingested.monitored_merkle_root
+            );
+            (ingested.window_start, ingested.window_end, ingested.monitored_merkle_root, ingested.meter_readings, Some(ingested.available))
+        }
+        None => (
+            args.window_start.context("the window_start is required without --readings")?,
+            args.window_end.context("the window_end is required without --readings")?,
+            args.monitored_merkle_root.context("the monitored_merkle_root is required without --readings")?,
+            readings::load_meter_readings(args.meter_readings_file.as_deref().context("the meter_readings_file is required without --readings")?)?,
+            None,
+        ),
+    };
+
+    // Store the input data into the variables
+    // @dev - With --readings, all the energy still available in the window (= not sold yet) is sold by default.
+    let input_amount_of_energy_to_be_sold: u64 = match (args.amount_of_energy_to_be_sold, available) {
+        (Some(amount_of_energy_to_be_sold), _) => amount_of_energy_to_be_sold,
+        (None, Some(available)) => available
+            .checked_sub(args.previously_sold)
+            .filter(|remaining| *remaining > 0)
+            .context("no energy is left to be sold in the window")?,
+        (None, None) => bail!("the amount_of_energy_to_be_sold is required without --readings"),
+    };
+    if let Some(available) = available {
+        ensure!(
+            args.previously_sold.saturating_add(input_amount_of_energy_to_be_sold) <= available,
+            "the amount of energy to be sold {} (+ the previously sold {}) exceeds the energy available in the window {}",
+            input_amount_of_energy_to_be_sold,
+            args.previously_sold,
+            available
+        );
+    }
+    let input_current_time: u64 = args.current_time;
+    let input_max_monitored_time_age: u64 = args.max_monitored_time_age;
+    let input_monitored_merkle_root: [u8; 32] = monitored_merkle_root.0;
+    let input_meter_public_key: Vec<u8> = args.meter_public_key.to_vec();
+    let input_meter_signature: Vec<u8> = args.meter_signature.to_vec();
+
+    tracing::info!("'input_seller' to publish: {}\n", input_seller);
+    tracing::info!("'input_chain_id' to publish: {}\n", input_chain_id);
+
+    // The previously sold energy is committed to, and the commitment is bound into the nullifier, so that each
+    // chained sell order of the same meter readings has its own nullifier.
+    let input_previously_sold: u64 = args.previously_sold;
+    let input_previous_sold_commitment: [u8; 32] = compute_sold_commitment(&input_monitored_merkle_root, input_previously_sold);
+
+    // Calculate the monitored_nullifier from the (parsed) input data, in the same way as the guest program.
+    let input_monitored_nullifier: [u8; 32] = compute_nullifier(
+        input_amount_of_energy_to_be_sold,
+        input_window_start,
+        input_window_end,
+        &input_monitored_merkle_root,
+        &input_seller.into_array(),
+        input_chain_id,
+        &input_previous_sold_commitment,
+    );
+    tracing::info!("input_monitored_nullifier: {}\n", B256::from(input_monitored_nullifier));
+    if let Some(expected_monitored_nullifier) = args.monitored_nullifier {
+        ensure!(
+            expected_monitored_nullifier == B256::from(input_monitored_nullifier),
+            "the computed nullifier {} does not match the given monitored_nullifier {}",
+            B256::from(input_monitored_nullifier),
+            expected_monitored_nullifier
+        );
+    }
+    tracing::info!("'input_amount_of_energy_to_be_sold' to publish: {}\n", input_amount_of_energy_to_be_sold);
+    tracing::info!("'input_window_start' to publish: {}\n", input_window_start);
+    tracing::info!("'input_window_end' to publish: {}\n", input_window_end);
+    tracing::info!("'input_current_time' to publish: {}\n", input_current_time);
+    tracing::info!("'input_max_monitored_time_age' to publish: {}\n", input_max_monitored_time_age);
+    tracing::info!("'input_monitored_merkle_root' to publish: {}\n", monitored_merkle_root);
+    tracing::info!("'input_meter_readings' to publish: {:?}\n", input_meter_readings);
+    tracing::info!("'input_meter_public_key' to publish: 0x{}\n", hex::encode(&input_meter_public_key));
+
+    // The input data is written as a single SmartMeterInput, which is read by the guest program in the same layout.
+    Ok(SmartMeterInput {
+        amount_of_energy_to_be_sold: input_amount_of_energy_to_be_sold,
+        window_start: input_window_start,
+        window_end: input_window_end,
+        current_time: input_current_time,
+        max_monitored_time_age: input_max_monitored_time_age,
+        monitored_merkle_root: input_monitored_merkle_root,
+        meter_readings: input_meter_readings,
+        meter_public_key: input_meter_public_key,
+        meter_signature: input_meter_signature,
+        seller: input_seller.into_array(),
+        chain_id: input_chain_id,
+        monitored_nullifier: input_monitored_nullifier,
+        previously_sold: input_previously_sold,
+        previous_sold_commitment: input_previous_sold_commitment,
+    })
+}
+
+/// Dry run the smart-meter guest program with the input, and check that its journal commits to the input.
+/// (Shared by the `sell` and the `estimate` subcommands)
+fn dry_run(input: &SmartMeterInput) -> Result<(GuestEnv, Execution, SmartMeterJournal)> {
+    let input_builder = InputBuilder::new().write(input)?;
+
+    tracing::info!("input builder: {:?}\n", input_builder);
+
+    let guest_env = input_builder.clone().build_env()?;
+
+    // Dry run the ELF with the input to get the journal and cycle count.
+    // This can be useful to estimate the cost of the proving request.
+    // It can also be useful to ensure the guest can be executed correctly and we do not send into
+    // the market (or the prover) unprovable proving requests.
+    let execution = execute(SMART_METER_ELF, &guest_env)?;
+
+    // Decode the ABI-encoded journal and check that it commits to the same values that will be sent to
+    // the EnergyAggregator#createSellOrder(). Otherwise, the on-chain verification would fail after paying the market.
+    let decoded_journal = SmartMeterJournal::decode(&execution.journal).context("failed to decode the journal")?;
+    tracing::info!("decoded journal: {:?}\n", decoded_journal);
+    ensure!(
+        decoded_journal.energyAmountToBeSold == U256::from(input.amount_of_energy_to_be_sold),
+        "the journal does not commit to the amount of energy to be sold"
+    );
+    ensure!(
+        decoded_journal.windowStart == U256::from(input.window_start) && decoded_journal.windowEnd == U256::from(input.window_end),
+        "the journal does not commit to the window"
+    );
+    ensure!(
+        decoded_journal.currentTime == U256::from(input.current_time),
+        "the journal does not commit to the current time"
+    );
+    ensure!(
+        decoded_journal.previousSoldCommitment == B256::from(input.previous_sold_commitment),
+        "the journal does not chain from the previous sold commitment"
+    );
+    ensure!(
+        decoded_journal.seller == Address::from(input.seller) && decoded_journal.chainId == U256::from(input.chain_id),
+        "the journal does not commit to the seller and the chain ID"
+    );
+
+    Ok((guest_env, execution, decoded_journal))
+}
+
+/// Create the sell order with the proven journal and the seal (i.e. proof), and check that it is stored as proven.
+/// Each step is recorded in the local store. (Shared by the `sell` and the `resume` subcommands)
+pub async fn create_sell_order<T, P>(
+    energy_aggregator: &IEnergyAggregatorInstance<T, P>,
+    seller: Address,
+    decoded_journal: &SmartMeterJournal,
+    seal: Bytes,
+    store: &Store,
+    sell_id: i64,
+) -> Result<U256>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    // Interact with the EnergyAggregator contract by calling the createSellOrder() function with the proven journal
+    // and the seal (i.e. proof) returned by the market (or the local prover).
+    let create_sell_order_tx = energy_aggregator
+        .createSellOrder(
+            decoded_journal.energyAmountToBeSold,
+            decoded_journal.windowStart,
+            decoded_journal.windowEnd,
+            decoded_journal.currentTime,
+            decoded_journal.monitoredMerkleRoot,
+            decoded_journal.monitoredNullifier,
+            decoded_journal.meterPublicKeyHash,
+            decoded_journal.soldCommitment,
+            seal
+        ) // @dev - Call the EnergyAggregator#createSellOrder() function
+        .from(seller);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#createSellOrder() function");
+    let pending_tx = create_sell_order_tx.send().await.context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    store.set_submitted(sell_id, *pending_tx.tx_hash())?;
+    // @dev - An unconfirmed tx is not recorded as failed, since it may still be included later.
+    let receipt = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .get_receipt()
+        .await
+        .context("failed to confirm tx")?;
+    let sell_order_id = created_sell_order_id(*energy_aggregator.address(), &receipt, "createSellOrder", store, &[sell_id])?;
+
+    // We query the sell order stored at the EnergyAggregator address to check it was stored as it was proven.
+    let sell_order = energy_aggregator
+        .getSellOrder(sell_order_id) // @dev - Call the EnergyAggregator#getSellOrder() function
+        .call()
+        .await
+        .context("failed to get the sell order from contract")?
+        ._0;
+    ensure!(
+        sell_order.energyAmountToBeSold == decoded_journal.energyAmountToBeSold,
+        "the stored sell order {} does not have the proven amount of energy to be sold",
+        sell_order_id
+    );
+    ensure!(
+        sell_order.monitoredNullifier == decoded_journal.monitoredNullifier,
+        "the stored sell order {} does not have the proven nullifier",
+        sell_order_id
+    );
+    ensure!(
+        sell_order.monitoredMerkleRoot == decoded_journal.monitoredMerkleRoot,
+        "the stored sell order {} does not have the proven monitored merkle root",
+        sell_order_id
+    );
+    ensure!(
+        sell_order.energySeller == seller,
+        "the stored sell order {} does not have the seller {}",
+        sell_order_id,
+        seller
+    );
+    tracing::info!(
+        "sell order {} of {} kWh for address: {:?} is stored as proven",
+        sell_order_id,
+        sell_order.energyAmountToBeSold,
+        seller
+    );
+
+    Ok(sell_order_id)
+}
+
+/// Find the ID of the sell order created by the tx from the SellOrderCreated event in its receipt, and record it for
+/// the sells in the local store. A reverted tx is recorded as failed. (Shared by the `sell`, the `resume` and the
+/// `aggregate` subcommands, whose sell order may be created from the sells of many smart meters)
+pub fn created_sell_order_id(
+    energy_aggregator: Address,
+    receipt: &TransactionReceipt,
+    function: &str,
+    store: &Store,
+    sell_ids: &[i64],
+) -> Result<U256> {
+    if !receipt.status() {
+        let error = format!("the tx {} calling the EnergyAggregator#{}() was reverted", receipt.transaction_hash, function);
+        for sell_id in sell_ids {
+            store.set_failed(*sell_id, &error)?;
+        }
+        bail!(error);
+    }
+    tracing::info!("Tx {:?} confirmed", receipt.transaction_hash);
+
+    let sell_order_id = receipt
+        .inner
+        .logs()
+        .iter()
+        .filter(|log| log.address() == energy_aggregator)
+        .find_map(|log| log.log_decode::<IEnergyAggregator::SellOrderCreated>().ok())
+        .context("the receipt does not have the SellOrderCreated event of the EnergyAggregator contract")?
+        .inner
+        .data
+        .sellOrderId;
+    tracing::info!("Sell order {} created", sell_order_id);
+    for sell_id in sell_ids {
+        store.set_created(*sell_id, sell_order_id)?;
+    }
+    Ok(sell_order_id)
+}
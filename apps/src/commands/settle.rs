@@ -0,0 +1,375 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host flow of the settlement guest program: prove how much of the traded energy of a matched sell order was delivered
+//! (= exported by the seller's smart meter after the trade) (via the proof backend picked by --prover), and settle the
+//! sell order with the proof. A matched aggregate sell order (of a microgrid) is settled with a proof per smart meter.
+
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::EthereumWallet,
+    primitives::{Address, Bytes, B256, U256},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::BlockTransactionsKind,
+    transports::Transport,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::input::{GuestEnv, InputBuilder};
+use energy_types::{MeterReadings, SettlementInput, SettlementJournal};
+use guests::{ SETTLEMENT_ELF, SETTLEMENT_ID };
+
+use super::{ImageArgs, WalletArgs, TX_TIMEOUT};
+use crate::{
+    backend::{execute, Execution, ProofBackend, ProofTask, ProverArgs},
+    energy_aggregator::IEnergyAggregator::{self, IEnergyAggregatorInstance},
+    readings,
+};
+
+/// Arguments of the `settle` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct SettleArgs {
+    /// The ID of the matched sell order to be settled.
+    #[clap(long, env)]
+    pub sell_order_id: u64,
+
+    /// Start of the window in which the traded energy was delivered (UTC timestamp).
+    #[clap(long, env)]
+    pub delivery_window_start: u64,
+
+    /// End of the window in which the traded energy was delivered (UTC timestamp).
+    #[clap(long, env)]
+    pub delivery_window_end: u64,
+
+    /// Merkle root of the (post-trade) meter readings of the seller's smart meter.
+    #[clap(long, env)]
+    pub delivery_merkle_root: B256,
+
+    /// Path to the JSON file of the readings of the cumulative (export) register of the seller's smart meter in the delivery
+    /// window (ordered by timestamp), with their sibling hashes up to the delivery merkle root.
+    #[clap(long, env)]
+    pub delivery_readings_file: PathBuf,
+
+    /// SEC1-encoded secp256k1 public key of the seller's smart meter (hex). Must be the smart meter of the sell order.
+    #[clap(long, env)]
+    pub meter_public_key: Bytes,
+
+    /// Signature (r || s) of the delivery merkle root, signed by the seller's smart meter (hex).
+    #[clap(long, env)]
+    pub delivery_meter_signature: Bytes,
+
+    #[clap(flatten)]
+    pub image: ImageArgs,
+
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Arguments of the `settle-aggregate` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct SettleAggregateArgs {
+    /// The ID of the matched aggregate sell order to be settled.
+    #[clap(long, env)]
+    pub sell_order_id: u64,
+
+    /// Path to the JSON file of the inputs of the settlement guest program (an array of SettlementInput), one per smart
+    /// meter of the aggregate sell order which delivered a part of the traded energy. Every input must be bound to the
+    /// sell order and its (whole) traded amount, the operator's address (= seller) and this chain.
+    #[clap(long, env)]
+    pub settlement_inputs_file: PathBuf,
+
+    #[clap(flatten)]
+    pub image: ImageArgs,
+
+    // @dev - The wallet is the one of the operator of the microgrid.
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+}
+
+/// Run the `settle` subcommand with the proof backend picked by the --prover flag.
+pub async fn run(args: SettleArgs, prover: ProverArgs) -> Result<()> {
+    // The EnergyAggregator contract is called by the wallet of the seller, whichever backend generates the proof.
+    let seller: Address = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key.clone()))
+        .on_http(args.wallet.rpc_url.clone());
+    let backend = prover.into_backend(args.wallet.rpc_url.clone(), args.wallet.wallet_private_key.clone())?;
+    let energy_aggregator = IEnergyAggregatorInstance::new(
+        args.wallet.energy_aggregator_address,
+        provider.clone(),
+    );
+
+    let (traded_amount, valid_for) = check_settleable(&energy_aggregator, &args.image, seller, args.sell_order_id).await?;
+    let meter_public_key_hash = energy_aggregator
+        .sellOrderMeterPublicKeyHashes(U256::from(args.sell_order_id))
+        .call()
+        .await
+        .context("failed to get the smart meter of the sell order from contract")?
+        ._0;
+    ensure!(
+        meter_public_key_hash != B256::ZERO,
+        "the sell order {} is an aggregate sell order, which must be settled with the settle-aggregate subcommand",
+        args.sell_order_id
+    );
+
+    // The delivery is measured by the cumulative (export) register, so a single register is expected.
+    let meter_readings = match readings::load_meter_readings(&args.delivery_readings_file)? {
+        MeterReadings::Gross(meter_readings) => meter_readings,
+        MeterReadings::Net(_) => bail!("the delivery readings must have a single (export) register"),
+    };
+
+    // The seller (= caller) and the chain ID are bound into the journal, so that only the seller can settle the sell
+    // order on this chain.
+    let chain_id: u64 = provider.get_chain_id().await.context("failed to get the chain ID")?;
+
+    let input = SettlementInput {
+        order_id: args.sell_order_id,
+        traded_amount,
+        delivery_window_start: args.delivery_window_start,
+        delivery_window_end: args.delivery_window_end,
+        monitored_merkle_root: args.delivery_merkle_root.0,
+        meter_readings,
+        meter_public_key: args.meter_public_key.to_vec(),
+        meter_signature: args.delivery_meter_signature.to_vec(),
+        seller: seller.into_array(),
+        chain_id,
+    };
+    let (guest_env, execution, decoded_journal) = dry_run(&input)?;
+    if decoded_journal.shortfall > U256::ZERO {
+        tracing::warn!("Only {} kWh of the traded {} kWh were delivered; the shortfall is refunded to the buyer", decoded_journal.delivered, decoded_journal.tradedAmount);
+    }
+    let seal = prove_delivery(&backend, &guest_env, &execution, valid_for).await?;
+
+    // Interact with the EnergyAggregator contract by calling the settleSellOrder() function with the settlement
+    // proof attached, which releases the escrow of the sell order.
+    let settle_sell_order_tx = energy_aggregator
+        .settleSellOrder(
+            decoded_journal.orderId,
+            decoded_journal.delivered,
+            decoded_journal.deliveryWindowStart,
+            decoded_journal.deliveryWindowEnd,
+            decoded_journal.monitoredMerkleRoot,
+            decoded_journal.meterPublicKeyHash,
+            seal
+        )
+        .from(seller);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#settleSellOrder() function");
+    let pending_tx = settle_sell_order_tx.send().await.context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    let tx_hash = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .watch()
+        .await
+        .context("failed to confirm tx")?;
+    tracing::info!("Tx {:?} confirmed", tx_hash);
+
+    Ok(())
+}
+
+/// Run the `settle-aggregate` subcommand with the proof backend picked by the --prover flag.
+pub async fn run_aggregate(args: SettleAggregateArgs, prover: ProverArgs) -> Result<()> {
+    // The EnergyAggregator contract is called by the wallet of the operator, whichever backend generates the proofs.
+    let seller: Address = args.wallet.wallet_private_key.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(args.wallet.wallet_private_key.clone()))
+        .on_http(args.wallet.rpc_url.clone());
+    let backend = prover.into_backend(args.wallet.rpc_url.clone(), args.wallet.wallet_private_key.clone())?;
+    let energy_aggregator = IEnergyAggregatorInstance::new(args.wallet.energy_aggregator_address, provider.clone());
+
+    let (traded_amount, valid_for) = check_settleable(&energy_aggregator, &args.image, seller, args.sell_order_id).await?;
+    let chain_id: u64 = provider.get_chain_id().await.context("failed to get the chain ID")?;
+
+    // Load the inputs of the smart meters which delivered a part of the traded energy.
+    let file = fs::read_to_string(&args.settlement_inputs_file)
+        .with_context(|| format!("failed to read the settlement inputs file {}", args.settlement_inputs_file.display()))?;
+    let settlement_inputs: Vec<SettlementInput> = serde_json::from_str(&file)
+        .with_context(|| format!("failed to parse the settlement inputs file {}", args.settlement_inputs_file.display()))?;
+    ensure!(!settlement_inputs.is_empty(), "at least 1 settlement input is required to settle the aggregate sell order");
+
+    // Dry run the settlement guest program for every smart meter, and check the deliveries before proving, since the
+    // settlement would be rejected by the contract otherwise.
+    let mut dry_runs = Vec::with_capacity(settlement_inputs.len());
+    let mut meter_public_key_hashes = HashSet::new();
+    for settlement_input in &settlement_inputs {
+        ensure!(
+            settlement_input.order_id == args.sell_order_id && settlement_input.traded_amount == traded_amount,
+            "every settlement input must be bound to the sell order {} and its traded amount {}",
+            args.sell_order_id,
+            traded_amount
+        );
+        ensure!(
+            Address::from(settlement_input.seller) == seller && settlement_input.chain_id == chain_id,
+            "every settlement input must be bound to the operator {} and the chain ID {}",
+            seller,
+            chain_id
+        );
+        let (guest_env, execution, decoded_journal) = dry_run(settlement_input)?;
+        // @dev - Each smart meter can only deliver once, and must be one of the smart meters of the aggregate sell order.
+        ensure!(
+            meter_public_key_hashes.insert(decoded_journal.meterPublicKeyHash),
+            "the smart meter {} has more than 1 settlement input",
+            decoded_journal.meterPublicKeyHash
+        );
+        let aggregated = energy_aggregator
+            .aggregateMeterPublicKeyHashes(U256::from(args.sell_order_id), decoded_journal.meterPublicKeyHash)
+            .call()
+            .await
+            .context("failed to get the smart meters of the aggregate sell order from contract")?
+            ._0;
+        ensure!(
+            aggregated,
+            "the smart meter {} is not aggregated into the sell order {}",
+            decoded_journal.meterPublicKeyHash,
+            args.sell_order_id
+        );
+        dry_runs.push((guest_env, execution, decoded_journal));
+    }
+
+    // Prove the delivery of every smart meter. The deliveries are sorted by the meter public key hash, since the
+    // contract requires them in that order (so that a smart meter is not counted twice).
+    dry_runs.sort_by_key(|(_, _, decoded_journal)| decoded_journal.meterPublicKeyHash);
+    let mut deliveries = Vec::with_capacity(dry_runs.len());
+    for (index, (guest_env, execution, decoded_journal)) in dry_runs.iter().enumerate() {
+        tracing::info!("Proving the delivery of the smart meter {} ({}/{})", decoded_journal.meterPublicKeyHash, index + 1, dry_runs.len());
+        let seal = prove_delivery(&backend, guest_env, execution, valid_for).await?;
+        deliveries.push(IEnergyAggregator::Delivery {
+            delivered: decoded_journal.delivered,
+            deliveryWindowStart: decoded_journal.deliveryWindowStart,
+            deliveryWindowEnd: decoded_journal.deliveryWindowEnd,
+            monitoredMerkleRoot: decoded_journal.monitoredMerkleRoot,
+            meterPublicKeyHash: decoded_journal.meterPublicKeyHash,
+            seal,
+        });
+    }
+    let delivered = deliveries.iter().fold(U256::ZERO, |delivered, delivery| delivered + delivery.delivered);
+    if delivered < U256::from(traded_amount) {
+        tracing::warn!("Only {} kWh of the traded {} kWh were delivered; the shortfall is refunded to the buyer", delivered, traded_amount);
+    }
+
+    // Interact with the EnergyAggregator contract by calling the settleAggregateSellOrder() function with the
+    // settlement proofs attached, which releases the escrow of the aggregate sell order.
+    let settle_tx = energy_aggregator
+        .settleAggregateSellOrder(U256::from(args.sell_order_id), deliveries)
+        .from(seller);
+
+    tracing::info!("Broadcasting tx calling the EnergyAggregator#settleAggregateSellOrder() function");
+    let pending_tx = settle_tx.send().await.context("failed to broadcast tx")?;
+    tracing::info!("Sent tx {}", pending_tx.tx_hash());
+    let receipt = pending_tx
+        .with_timeout(Some(TX_TIMEOUT))
+        .get_receipt()
+        .await
+        .context("failed to confirm tx")?;
+    ensure!(
+        receipt.status(),
+        "the tx {} calling the EnergyAggregator#settleAggregateSellOrder() was reverted",
+        receipt.transaction_hash
+    );
+    tracing::info!("Tx {:?} confirmed", receipt.transaction_hash);
+
+    Ok(())
+}
+
+/// Check that the settlement guest program matches the image ID pinned by the contract, and that the sell order can be
+/// settled by the seller. Returns the traded amount of the sell order, and how long (Unit: seconds) a proof can still be
+/// used until its settlement deadline.
+async fn check_settleable<T, P>(
+    energy_aggregator: &IEnergyAggregatorInstance<T, P>,
+    image: &ImageArgs,
+    seller: Address,
+    sell_order_id: u64,
+) -> Result<(u64, u64)>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    // The proof would be rejected by the contract if the guest program has drifted from the image ID pinned by it.
+    let deployed_image_id = energy_aggregator
+        .settlementImageId()
+        .call()
+        .await
+        .context("failed to get the settlement image ID from contract")?
+        ._0;
+    image.check("settlement", SETTLEMENT_ID, deployed_image_id)?;
+
+    // The traded amount is read from the sell order itself, and the sell order must be matched (but not settled nor
+    // refunded yet) by this seller, so that a proof is not paid for a sell order which the contract would not settle.
+    let sell_order = energy_aggregator
+        .getSellOrder(U256::from(sell_order_id))
+        .call()
+        .await
+        .with_context(|| format!("failed to get the sell order {} from contract", sell_order_id))?
+        ._0;
+    ensure!(sell_order.energySeller == seller, "the sell order {} is not created by the seller {}", sell_order_id, seller);
+    ensure!(sell_order.orderMatched, "the sell order {} is not matched with a buy order yet", sell_order_id);
+    let escrow = energy_aggregator
+        .escrows(U256::from(sell_order_id))
+        .call()
+        .await
+        .with_context(|| format!("failed to get the escrow of the sell order {} from contract", sell_order_id))?;
+    ensure!(!escrow.settled, "the sell order {} has already been settled (or refunded)", sell_order_id);
+    let settlement_deadline = energy_aggregator
+        .settlementDeadline()
+        .call()
+        .await
+        .context("failed to get the settlement deadline from contract")?
+        ._0;
+    let latest_block = energy_aggregator
+        .provider()
+        .get_block_by_number(BlockNumberOrTag::Latest, BlockTransactionsKind::Hashes)
+        .await
+        .context("failed to get the latest block")?
+        .context("the latest block is not found")?;
+    let settle_by = escrow.matchedAt + settlement_deadline;
+    ensure!(
+        U256::from(latest_block.header.timestamp) <= settle_by,
+        "the settlement deadline of the sell order {} has passed",
+        sell_order_id
+    );
+    let valid_for = u64::try_from(settle_by - U256::from(latest_block.header.timestamp)).unwrap_or(u64::MAX);
+    let traded_amount = u64::try_from(sell_order.energyAmountToBeSold).context("the traded amount does not fit in u64")?;
+    Ok((traded_amount, valid_for))
+}
+
+/// Dry run the ELF with the input to get the journal, so that an unprovable proving request is not sent into the market
+/// (or the prover). Returns the guest env to be proven, the execution and the decoded journal.
+fn dry_run(input: &SettlementInput) -> Result<(GuestEnv, Execution, SettlementJournal)> {
+    let guest_env = InputBuilder::new().write(input)?.build_env()?;
+    let execution = execute(SETTLEMENT_ELF, &guest_env)?;
+
+    let decoded_journal = SettlementJournal::decode(&execution.journal).context("failed to decode the journal")?;
+    tracing::info!("decoded journal: {:?}\n", decoded_journal);
+    ensure!(
+        decoded_journal.orderId == U256::from(input.order_id) && decoded_journal.tradedAmount == U256::from(input.traded_amount),
+        "the journal does not commit to the sell order and its traded amount"
+    );
+    ensure!(
+        decoded_journal.seller == Address::from(input.seller) && decoded_journal.chainId == U256::from(input.chain_id),
+        "the journal does not commit to the seller and the chain ID"
+    );
+    Ok((guest_env, execution, decoded_journal))
+}
+
+/// Generate the proof of a dry run with the backend picked by the --prover flag (See: ./apps/src/backend.rs), and
+/// return its seal.
+async fn prove_delivery(backend: &impl ProofBackend, guest_env: &GuestEnv, execution: &Execution, valid_for: u64) -> Result<Bytes> {
+    let task = ProofTask { execution, valid_for };
+    let (proven_journal, seal) = backend.prove(SETTLEMENT_ELF, SETTLEMENT_ID, guest_env, task, &mut ()).await?;
+    ensure!(proven_journal == execution.journal, "the journal of the proof does not match the dry run");
+    Ok(seal)
+}
@@ -0,0 +1,598 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generate synthetic smart-meter data for testing and demos: the solar production and the household consumption of
+//! prosumers' smart meters, netted into their cumulative export and import registers, with noise, outages and clock
+//! skew. The readings of each day are signed by a generated key of the smart meter.
+//!
+//! (NOTE: The generated meter keys are only for testing. They are written in plaintext into the output directory)
+
+use std::{
+    f64::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::{Address, Bytes, B256};
+use anyhow::{ensure, Context, Result};
+use energy_types::{compute_nullifier, MeterReadings, SmartMeterInput, INITIAL_SOLD_COMMITMENT};
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::readings::{self, IngestedMeterReadings, RawMeterReading};
+
+/// Length of a window, i.e. of the readings file of a day (in seconds).
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Arguments of the `simulate` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct SimulateArgs {
+    /// Number of the smart meters to be simulated.
+    #[clap(long, default_value_t = 1)]
+    pub meters: usize,
+
+    /// Number of the days to be simulated. The readings of each day are a window (i.e. one readings file).
+    #[clap(long, default_value_t = 1)]
+    pub days: u64,
+
+    /// Start of the first day (UTC timestamp). By default, `days` days before the latest multiple of the interval, so
+    /// that the window of the last day has just ended.
+    #[clap(long)]
+    pub start: Option<u64>,
+
+    /// The current time committed by the generated smart-meter inputs (UTC timestamp). An input can only be proven if
+    /// its window has ended within the `max_monitored_time_age` before it, and only be sold while it is within the
+    /// `max_monitored_time_age` before the block time. By default, the time when the simulation is run.
+    #[clap(long)]
+    pub current_time: Option<u64>,
+
+    /// Interval between the meter readings (in seconds). A day must be a multiple of it.
+    #[clap(long, default_value_t = 900)]
+    pub interval: u64,
+
+    /// Power of the solar panels of a smart meter at noon on a clear day (Unit: W).
+    #[clap(long, default_value_t = 5000)]
+    pub peak_production: u64,
+
+    /// Average power of the household consumption of a smart meter (Unit: W).
+    #[clap(long, default_value_t = 500)]
+    pub base_consumption: u64,
+
+    /// Relative noise of the production and the consumption in each interval (e.g. 0.1 = up to ±10%).
+    #[clap(long, default_value_t = 0.1)]
+    pub noise: f64,
+
+    /// Probability that an outage starts in an interval. No meter reading is reported during an outage, while the
+    /// registers keep counting (The first and the last readings of a day are always reported).
+    #[clap(long, default_value_t = 0.0)]
+    pub outage_rate: f64,
+
+    /// Maximum length of an outage (in intervals).
+    #[clap(long, default_value_t = 8)]
+    pub max_outage_intervals: u64,
+
+    /// Maximum clock skew of a smart meter (in seconds). The clock of each smart meter is off by a random offset
+    /// within ± this value, which shifts the timestamps of all its readings.
+    #[clap(long, default_value_t = 0)]
+    pub max_clock_skew: u64,
+
+    /// The seller (e.g. the operator of the microgrid) to which the generated smart-meter inputs are bound.
+    #[clap(long, default_value_t = Address::ZERO)]
+    pub seller: Address,
+
+    /// The chain ID to which the generated smart-meter inputs are bound (Default: Ethereum Sepolia).
+    #[clap(long, default_value_t = 11155111)]
+    pub chain_id: u64,
+
+    /// Maximum age (in seconds) of the meter reading, set in the generated smart-meter inputs.
+    #[clap(long, default_value_t = 3600)]
+    pub max_monitored_time_age: u64,
+
+    /// Seed of the random generator. The same seed (and arguments) always generates the same data and meter keys.
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Directory into which the generated files are written.
+    #[clap(short, long, default_value = "simulated")]
+    pub output_dir: PathBuf,
+}
+
+/// The key of a simulated smart meter, as written in the `meters.json`.
+#[derive(Serialize, Debug)]
+struct SimulatedMeter {
+    meter: usize,
+    /// The secret key of the smart meter (NOTE: Only for testing).
+    meter_secret_key: B256,
+    /// SEC1-encoded (compressed) secp256k1 public key of the smart meter.
+    meter_public_key: Bytes,
+    /// The offset of the clock of the smart meter (in seconds).
+    clock_skew: i64,
+}
+
+/// The signed window of a simulated smart meter, as written in the `meter.json` of each day.
+#[derive(Serialize, Debug)]
+struct SignedWindow {
+    monitored_merkle_root: B256,
+    meter_public_key: Bytes,
+    /// Signature (r || s) of the monitored merkle root, signed by the smart meter.
+    meter_signature: Bytes,
+    window_start: u64,
+    window_end: u64,
+    /// The net surplus in the window, which can be sold (Unit: kWh).
+    available: u64,
+}
+
+/// A meter reading with its hash path, in the same format as the meter readings file (See: ./apps/src/readings.rs).
+#[derive(Serialize, Debug)]
+struct MeterReadingEntry {
+    timestamp: u64,
+    register: u64,
+    import_register: u64,
+    hash_path: Vec<B256>,
+}
+
+/// The state of a simulated smart meter, which is carried over from one day to the next.
+struct MeterState {
+    signing_key: SigningKey,
+    clock_skew: i64,
+    /// The size of the solar panels relative to the `peak_production`.
+    system_size: f64,
+    /// The cumulative export register (Unit: Wh).
+    export_register: u64,
+    /// The cumulative import register (Unit: Wh).
+    import_register: u64,
+    /// The remaining intervals of the ongoing outage.
+    outage_remaining: u64,
+}
+
+/// Run the `simulate` subcommand.
+///
+/// The output directory is laid out as:
+/// - `meters.json`: The keys (and the clock skews) of the simulated smart meters.
+/// - `day-<D>/meter-<M>/readings.csv`: The raw meter readings of the day (i.e. the `--readings` of the `sell` subcommand).
+/// - `day-<D>/meter-<M>/meter_readings.json`: The meter readings with their hash paths (i.e. the `--meter-readings-file`).
+/// - `day-<D>/meter-<M>/meter.json`: The monitored merkle root, its signature, the window and the energy available.
+/// - `day-<D>/meter-<M>/smart_meter_input.json`: The input of the smart-meter guest program, which sells all the energy
+///   available in the window (e.g. to be loaded by the tests of the guest programs).
+/// - `day-<D>/smart_meter_inputs.json`: The inputs of all the smart meters of the day (i.e. the `--smart-meter-inputs-file`
+///   of the `aggregate` subcommand).
+pub fn run(args: SimulateArgs) -> Result<()> {
+    ensure!(args.meters > 0 && args.days > 0, "at least 1 smart meter and 1 day must be simulated");
+    ensure!(
+        args.interval > 0 && SECONDS_PER_DAY % args.interval == 0,
+        "a day ({} seconds) must be a multiple of the interval",
+        SECONDS_PER_DAY
+    );
+    ensure!((0.0..=1.0).contains(&args.noise), "the noise must be between 0 and 1");
+    ensure!((0.0..=1.0).contains(&args.outage_rate), "the outage rate must be between 0 and 1");
+    ensure!(args.max_outage_intervals > 0, "the max outage length must be at least 1 interval");
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).context("the system clock is before the UNIX epoch")?.as_secs();
+    let start = args.start.unwrap_or_else(|| (now / args.interval * args.interval).saturating_sub(args.days * SECONDS_PER_DAY));
+    let current_time = args.current_time.unwrap_or(now);
+    ensure!(start >= args.max_clock_skew, "the start must not be before the max clock skew (after the UNIX epoch)");
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut meters: Vec<MeterState> = (0..args.meters)
+        .map(|_| MeterState {
+            signing_key: SigningKey::random(&mut rng),
+            clock_skew: rng.gen_range(-(args.max_clock_skew as i64)..=args.max_clock_skew as i64),
+            system_size: rng.gen_range(0.8..1.2),
+            // @dev - The registers of a smart meter do not start from 0, since it has been running before the simulation.
+            export_register: rng.gen_range(0..10_000_000),
+            import_register: rng.gen_range(0..10_000_000),
+            outage_remaining: 0,
+        })
+        .collect();
+
+    fs::create_dir_all(&args.output_dir).with_context(|| format!("failed to create the output directory {}", args.output_dir.display()))?;
+    let simulated_meters: Vec<SimulatedMeter> = meters
+        .iter()
+        .enumerate()
+        .map(|(meter, state)| SimulatedMeter {
+            meter,
+            meter_secret_key: B256::from_slice(&state.signing_key.to_bytes()),
+            meter_public_key: public_key(&state.signing_key),
+            clock_skew: state.clock_skew,
+        })
+        .collect();
+    write_json(&args.output_dir.join("meters.json"), &simulated_meters, true)?;
+
+    for day in 0..args.days {
+        let day_start = start + day * SECONDS_PER_DAY;
+        // @dev - The weather of a day is shared by all the smart meters (e.g. of a microgrid).
+        let clearness: f64 = rng.gen_range(0.4..1.0);
+        let day_dir = args.output_dir.join(format!("day-{:02}", day));
+
+        let mut smart_meter_inputs = Vec::with_capacity(meters.len());
+        for (meter, state) in meters.iter_mut().enumerate() {
+            let raw_meter_readings = simulate_day(&args, &mut rng, state, day_start, clearness);
+            let ingested = readings::ingest_meter_readings(&raw_meter_readings)?;
+            let signature: Signature = state.signing_key.sign(ingested.monitored_merkle_root.as_slice());
+            let signed_window = SignedWindow {
+                monitored_merkle_root: ingested.monitored_merkle_root,
+                meter_public_key: public_key(&state.signing_key),
+                meter_signature: Bytes::from(signature.to_bytes().to_vec()),
+                window_start: ingested.window_start,
+                window_end: ingested.window_end,
+                available: ingested.available,
+            };
+
+            let meter_dir = day_dir.join(format!("meter-{:02}", meter));
+            fs::create_dir_all(&meter_dir).with_context(|| format!("failed to create the directory {}", meter_dir.display()))?;
+            write_csv(&meter_dir.join("readings.csv"), &raw_meter_readings)?;
+            write_json(&meter_dir.join("meter_readings.json"), &meter_reading_entries(&ingested), true)?;
+            write_json(&meter_dir.join("meter.json"), &signed_window, true)?;
+            // @dev - A smart meter without surplus has nothing to be sold, so no smart-meter input is generated for it.
+            if ingested.available == 0 {
+                tracing::warn!("day {} / meter {}: no energy is available to be sold, so no smart-meter input is generated", day, meter);
+            } else {
+                let input_current_time = fresh_current_time(ingested.window_end, current_time, args.max_monitored_time_age).unwrap_or_else(|| {
+                    tracing::warn!(
+                        "day {} / meter {}: the window end {} is not within the max_monitored_time_age before the current time {}, so the smart-meter input commits to the window end (i.e. it can be proven, but not sold on-chain)",
+                        day,
+                        meter,
+                        ingested.window_end,
+                        current_time
+                    );
+                    ingested.window_end
+                });
+                let smart_meter_input = smart_meter_input(&args, ingested, &signed_window, input_current_time);
+                write_json(&meter_dir.join("smart_meter_input.json"), &smart_meter_input, false)?;
+                smart_meter_inputs.push(smart_meter_input);
+            }
+
+            println!(
+                "day {:>2} / meter {:>2}: {} readings, window {}-{}, {} kWh available",
+                day,
+                meter,
+                raw_meter_readings.len(),
+                signed_window.window_start,
+                signed_window.window_end,
+                signed_window.available
+            );
+        }
+        if !smart_meter_inputs.is_empty() {
+            write_json(&day_dir.join("smart_meter_inputs.json"), &smart_meter_inputs, false)?;
+        }
+    }
+    println!("Simulated data written into {}", args.output_dir.display());
+
+    Ok(())
+}
+
+/// Simulate the readings of a smart meter over a day, and advance its registers to the end of the day.
+/// The readings are taken every `interval` seconds from the start to the end of the day (both inclusive).
+fn simulate_day(args: &SimulateArgs, rng: &mut StdRng, state: &mut MeterState, day_start: u64, clearness: f64) -> Vec<RawMeterReading> {
+    let steps = SECONDS_PER_DAY / args.interval;
+    let mut raw_meter_readings = Vec::with_capacity(steps as usize + 1);
+    for step in 0..=steps {
+        let time = day_start + step * args.interval;
+        let is_reported = if step == 0 || step == steps {
+            true
+        } else if state.outage_remaining > 0 {
+            state.outage_remaining -= 1;
+            false
+        } else if rng.gen_bool(args.outage_rate) {
+            state.outage_remaining = rng.gen_range(1..=args.max_outage_intervals) - 1;
+            false
+        } else {
+            true
+        };
+        if is_reported {
+            raw_meter_readings.push(RawMeterReading {
+                timestamp: time.saturating_add_signed(state.clock_skew),
+                register: state.export_register,
+                import_register: Some(state.import_register),
+            });
+        }
+        if step == steps {
+            break;
+        }
+
+        // The production and the consumption in the interval are netted by the smart meter, i.e. only the surplus
+        // is exported and only the shortfall is imported.
+        let hour = (((time + args.interval / 2) % SECONDS_PER_DAY) as f64) / 3600.0;
+        let production = args.peak_production as f64 * state.system_size * clearness * solar_profile(hour) * jitter(rng, args.noise);
+        let consumption = args.base_consumption as f64 * consumption_profile(hour) * jitter(rng, args.noise);
+        let energy = |power: f64| (power * args.interval as f64 / 3600.0).round() as u64; // @dev - W -> Wh in the interval
+        state.export_register += energy((production - consumption).max(0.0));
+        state.import_register += energy((consumption - production).max(0.0));
+    }
+    raw_meter_readings
+}
+
+/// The solar production relative to the peak at the given hour of the day (UTC), from the sunrise at 6:00 to the
+/// sunset at 18:00.
+fn solar_profile(hour: f64) -> f64 {
+    if (6.0..18.0).contains(&hour) {
+        (PI * (hour - 6.0) / 12.0).sin()
+    } else {
+        0.0
+    }
+}
+
+/// The household consumption relative to the average at the given hour of the day (UTC), with the morning and the
+/// evening peaks.
+fn consumption_profile(hour: f64) -> f64 {
+    match hour {
+        hour if hour < 6.0 => 0.6,
+        hour if hour < 9.0 => 1.4,
+        hour if hour < 17.0 => 0.8,
+        hour if hour < 22.0 => 1.8,
+        _ => 0.9,
+    }
+}
+
+/// A random factor of 1 ± `noise`.
+fn jitter(rng: &mut StdRng, noise: f64) -> f64 {
+    if noise == 0.0 {
+        1.0
+    } else {
+        1.0 + rng.gen_range(-noise..=noise)
+    }
+}
+
+/// The SEC1-encoded (compressed) public key of the smart meter.
+fn public_key(signing_key: &SigningKey) -> Bytes {
+    Bytes::from(signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec())
+}
+
+/// The meter readings with their hash paths, in the same format as the meter readings file.
+fn meter_reading_entries(ingested: &IngestedMeterReadings) -> Vec<MeterReadingEntry> {
+    match &ingested.meter_readings {
+        MeterReadings::Net(meter_readings) => meter_readings
+            .iter()
+            .map(|proof| MeterReadingEntry {
+                timestamp: proof.reading.timestamp,
+                register: proof.reading.export_register,
+                import_register: proof.reading.import_register,
+                hash_path: proof.hash_path.iter().map(|hash| B256::from(*hash)).collect(),
+            })
+            .collect(),
+        MeterReadings::Gross(_) => unreachable!("the simulated meter readings are net meter readings"),
+    }
+}
+
+/// The current time to be committed by the smart-meter input of a window, if the window has ended within the
+/// max_monitored_time_age before it (i.e. the guest program accepts it as fresh).
+fn fresh_current_time(window_end: u64, current_time: u64, max_monitored_time_age: u64) -> Option<u64> {
+    (window_end <= current_time && current_time - window_end <= max_monitored_time_age).then_some(current_time)
+}
+
+/// The input of the smart-meter guest program, which sells all the energy available in the window at the current time.
+fn smart_meter_input(args: &SimulateArgs, ingested: IngestedMeterReadings, signed_window: &SignedWindow, current_time: u64) -> SmartMeterInput {
+    let monitored_merkle_root = ingested.monitored_merkle_root.0;
+    let seller = args.seller.into_array();
+    SmartMeterInput {
+        amount_of_energy_to_be_sold: ingested.available,
+        window_start: ingested.window_start,
+        window_end: ingested.window_end,
+        current_time,
+        max_monitored_time_age: args.max_monitored_time_age,
+        monitored_merkle_root,
+        meter_readings: ingested.meter_readings,
+        meter_public_key: signed_window.meter_public_key.to_vec(),
+        meter_signature: signed_window.meter_signature.to_vec(),
+        seller,
+        chain_id: args.chain_id,
+        monitored_nullifier: compute_nullifier(
+            ingested.available,
+            ingested.window_start,
+            ingested.window_end,
+            &monitored_merkle_root,
+            &seller,
+            args.chain_id,
+            &INITIAL_SOLD_COMMITMENT,
+        ),
+        previously_sold: 0,
+        previous_sold_commitment: INITIAL_SOLD_COMMITMENT,
+    }
+}
+
+/// Write the raw meter readings into a CSV file, which is loaded by `readings::load_raw_meter_readings()`.
+fn write_csv(path: &Path, raw_meter_readings: &[RawMeterReading]) -> Result<()> {
+    let mut csv = String::from("timestamp,register,import_register\n");
+    for reading in raw_meter_readings {
+        csv.push_str(&format!("{},{},{}\n", reading.timestamp, reading.register, reading.import_register.unwrap_or_default()));
+    }
+    fs::write(path, csv).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Write the value into a JSON file. The smart-meter inputs are not pretty-printed, since their byte arrays (e.g. the
+/// hash paths) would take a line per byte.
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T, pretty: bool) -> Result<()> {
+    let json = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) }
+        .context("failed to serialize the simulated data")?;
+    fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Midnight (UTC) of 2025-02-27.
+    const START: u64 = 1740614400;
+
+    fn args() -> SimulateArgs {
+        SimulateArgs {
+            meters: 1,
+            days: 1,
+            start: Some(START),
+            current_time: None,
+            interval: 900,
+            peak_production: 5000,
+            base_consumption: 500,
+            noise: 0.1,
+            outage_rate: 0.0,
+            max_outage_intervals: 8,
+            max_clock_skew: 0,
+            seller: Address::ZERO,
+            chain_id: 11155111,
+            max_monitored_time_age: 3600,
+            seed: 0,
+            output_dir: PathBuf::from("simulated"),
+        }
+    }
+
+    fn meter_state(rng: &mut StdRng, clock_skew: i64) -> MeterState {
+        MeterState {
+            signing_key: SigningKey::random(rng),
+            clock_skew,
+            system_size: 1.0,
+            export_register: 1_000_000,
+            import_register: 2_000_000,
+            outage_remaining: 0,
+        }
+    }
+
+    #[test]
+    fn simulates_a_day_of_ingestible_meter_readings() {
+        let args = args();
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut state = meter_state(&mut rng, 0);
+        let raw_meter_readings = simulate_day(&args, &mut rng, &mut state, START, 1.0);
+
+        // @dev - Every 15 minutes from the start to the end of the day (both inclusive).
+        assert_eq!(raw_meter_readings.len(), 97);
+        assert_eq!(raw_meter_readings[0].timestamp, START);
+        assert_eq!(raw_meter_readings[96].timestamp, START + SECONDS_PER_DAY);
+        assert_eq!(raw_meter_readings[96].register, state.export_register);
+        assert_eq!(raw_meter_readings[96].import_register, Some(state.import_register));
+
+        // @dev - A clear day produces a surplus around noon, and the household imports at night.
+        let ingested = readings::ingest_meter_readings(&raw_meter_readings).unwrap();
+        assert!(ingested.available > 0);
+        assert!(state.import_register > 2_000_000);
+        assert_eq!((ingested.window_start, ingested.window_end), (START, START + SECONDS_PER_DAY));
+    }
+
+    #[test]
+    fn simulates_the_same_readings_from_the_same_seed() {
+        let args = args();
+        let simulate = || {
+            let mut rng = StdRng::seed_from_u64(args.seed);
+            let mut state = meter_state(&mut rng, 0);
+            registers(&simulate_day(&args, &mut rng, &mut state, START, 0.7))
+        };
+        assert_eq!(simulate(), simulate());
+    }
+
+    #[test]
+    fn skips_the_readings_in_outages_but_keeps_the_window() {
+        let args = SimulateArgs { outage_rate: 1.0, max_outage_intervals: 4, ..args() };
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut state = meter_state(&mut rng, 0);
+        let raw_meter_readings = simulate_day(&args, &mut rng, &mut state, START, 1.0);
+        assert!(raw_meter_readings.len() < 97);
+        assert_eq!(raw_meter_readings[0].timestamp, START);
+        assert_eq!(raw_meter_readings[raw_meter_readings.len() - 1].timestamp, START + SECONDS_PER_DAY);
+        assert!(readings::ingest_meter_readings(&raw_meter_readings).is_ok());
+    }
+
+    #[test]
+    fn shifts_the_timestamps_by_the_clock_skew() {
+        let args = args();
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut state = meter_state(&mut rng, -30);
+        let raw_meter_readings = simulate_day(&args, &mut rng, &mut state, START, 1.0);
+        assert_eq!(raw_meter_readings[0].timestamp, START - 30);
+        assert_eq!(raw_meter_readings[1].timestamp, START + 900 - 30);
+    }
+
+    #[test]
+    fn produces_no_surplus_without_solar_panels() {
+        let args = SimulateArgs { peak_production: 0, ..args() };
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut state = meter_state(&mut rng, 0);
+        let raw_meter_readings = simulate_day(&args, &mut rng, &mut state, START, 1.0);
+        assert_eq!(readings::ingest_meter_readings(&raw_meter_readings).unwrap().available, 0);
+        assert_eq!(state.export_register, 1_000_000);
+    }
+
+    #[test]
+    fn follows_the_solar_and_the_consumption_profiles() {
+        assert_eq!(solar_profile(3.0), 0.0);
+        assert_eq!(solar_profile(20.0), 0.0);
+        assert!((solar_profile(12.0) - 1.0).abs() < 1e-9);
+        assert!(consumption_profile(19.0) > consumption_profile(12.0));
+    }
+
+    #[test]
+    fn commits_a_fresh_current_time_only() {
+        let window_end = START + SECONDS_PER_DAY;
+        assert_eq!(fresh_current_time(window_end, window_end + 3600, 3600), Some(window_end + 3600));
+        assert_eq!(fresh_current_time(window_end, window_end + 3601, 3600), None);
+        assert_eq!(fresh_current_time(window_end, window_end - 1, 3600), None);
+    }
+
+    #[test]
+    fn generates_a_smart_meter_input_selling_all_the_available_energy() {
+        let args = SimulateArgs { seller: Address::repeat_byte(0x11), ..args() };
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut state = meter_state(&mut rng, 0);
+        let ingested = readings::ingest_meter_readings(&simulate_day(&args, &mut rng, &mut state, START, 1.0)).unwrap();
+        let signature: Signature = state.signing_key.sign(ingested.monitored_merkle_root.as_slice());
+        let signed_window = SignedWindow {
+            monitored_merkle_root: ingested.monitored_merkle_root,
+            meter_public_key: public_key(&state.signing_key),
+            meter_signature: Bytes::from(signature.to_bytes().to_vec()),
+            window_start: ingested.window_start,
+            window_end: ingested.window_end,
+            available: ingested.available,
+        };
+        let current_time = ingested.window_end + 60;
+        let input = smart_meter_input(&args, ingested.clone(), &signed_window, current_time);
+
+        assert_eq!(input.amount_of_energy_to_be_sold, ingested.available);
+        assert_eq!(input.current_time, current_time);
+        assert_eq!(input.seller, args.seller.into_array());
+        assert_eq!(
+            input.monitored_nullifier,
+            compute_nullifier(
+                ingested.available,
+                ingested.window_start,
+                ingested.window_end,
+                &ingested.monitored_merkle_root.0,
+                &args.seller.into_array(),
+                args.chain_id,
+                &INITIAL_SOLD_COMMITMENT
+            )
+        );
+        assert_eq!(meter_reading_entries(&ingested).len(), 97);
+    }
+
+    #[test]
+    fn skips_the_smart_meter_input_of_a_meter_without_surplus() {
+        let output_dir = std::env::temp_dir().join(format!("simulated-{}", std::process::id()));
+        let current_time = START + SECONDS_PER_DAY + 60;
+        run(SimulateArgs { meters: 2, peak_production: 0, current_time: Some(current_time), output_dir: output_dir.clone(), ..args() }).unwrap();
+        assert!(output_dir.join("meters.json").exists());
+        assert!(output_dir.join("day-00/meter-01/readings.csv").exists());
+        assert!(!output_dir.join("day-00/meter-01/smart_meter_input.json").exists());
+        assert!(!output_dir.join("day-00/smart_meter_inputs.json").exists());
+
+        run(SimulateArgs { current_time: Some(current_time), output_dir: output_dir.clone(), ..args() }).unwrap();
+        let input: SmartMeterInput =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("day-00/meter-00/smart_meter_input.json")).unwrap()).unwrap();
+        assert_eq!(input.current_time, current_time);
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    fn registers(raw_meter_readings: &[RawMeterReading]) -> Vec<(u64, u64, Option<u64>)> {
+        raw_meter_readings.iter().map(|reading| (reading.timestamp, reading.register, reading.import_register)).collect()
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimate of a proof request, which is printed by the `estimate` subcommand instead of proving the guest program,
+//! so that the inputs and the cost can be checked before spending ETH.
+
+use std::fmt::Debug;
+
+use alloy::primitives::{utils::format_ether, U256};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::backend::{offer_price_range, Execution};
+
+/// Output format of the estimate.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// The decoded journal and the cost of proving a guest program with the current offer policy.
+#[derive(Serialize, Debug)]
+pub struct Estimate<J> {
+    /// The decoded journal of the dry run.
+    pub journal: J,
+    pub segments: usize,
+    pub user_cycles: u64,
+    pub total_cycles: u64,
+    pub mcycles: u64,
+    /// The min price (Unit: wei) that the offer of the proof request would post.
+    pub min_price: U256,
+    /// The max price (Unit: wei) that the offer of the proof request would post.
+    pub max_price: U256,
+}
+
+impl<J: Serialize + Debug> Estimate<J> {
+    /// Estimate the cost of proving from the execution (= dry run) of the guest program.
+    pub fn new(journal: J, execution: &Execution) -> Result<Self> {
+        let (min_price, max_price) = offer_price_range(execution.mcycles_count)?;
+        Ok(Self {
+            journal,
+            segments: execution.segments,
+            user_cycles: execution.user_cycles,
+            total_cycles: execution.total_cycles,
+            mcycles: execution.mcycles_count,
+            min_price,
+            max_price,
+        })
+    }
+
+    /// Print the estimate to the stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("decoded journal: {:#?}", self.journal);
+                println!("segments: {}", self.segments);
+                println!("user cycles: {}", self.user_cycles);
+                println!("total cycles: {}", self.total_cycles);
+                println!("mcycles: {}", self.mcycles);
+                println!("offer min price: {} ETH", format_ether(self.min_price));
+                println!("offer max price: {} ETH", format_ether(self.max_price));
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parser of the Green Button (ESPI) XML files, i.e. the interval data exported by a utility ("Download My Data"),
+//! into the raw meter readings (i.e. the cumulative register series) which the smart-meter guest program proves over.
+//!
+//! An ESPI file is an Atom feed, whose entries are linked by their hrefs:
+//! `MeterReading` --(related)--> `ReadingType` (the unit, the multiplier and the flow direction of the values), and
+//! `IntervalBlock` --(up)--> `MeterReading` (the `IntervalReading`s, i.e. the values per interval).
+//! (NOTE: The ESPI elements are matched by their local names, whichever namespace prefix is used)
+
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use roxmltree::{Document, Node};
+
+use crate::readings::RawMeterReading;
+
+/// ESPI unit of measure (`uom`) of the real energy (Wh).
+const UOM_WH: u32 = 72;
+/// ESPI unit of measure (`uom`) of the real power (W), whose values are the average power over the interval.
+const UOM_W: u32 = 38;
+/// ESPI flow direction of the energy delivered to the customer (= import).
+const FLOW_DIRECTION_FORWARD: u32 = 1;
+/// ESPI flow direction of the energy received from the customer (= export, e.g. the production of a solar panel).
+const FLOW_DIRECTION_REVERSE: u32 = 19;
+/// ESPI accumulation behaviours whose values are already the cumulative register values (bulkQuantity,
+/// continuousCumulative and cumulative). The other values are the delta (deltaData) of each interval.
+const CUMULATIVE_ACCUMULATION_BEHAVIOURS: [u32; 3] = [1, 2, 3];
+
+/// The ReadingType of a MeterReading, which tells how to convert its values into Wh.
+#[derive(Clone, Copy, Debug)]
+struct ReadingType {
+    uom: u32,
+    power_of_ten_multiplier: i32,
+    flow_direction: Option<u32>,
+    cumulative: bool,
+}
+
+/// A series of (timestamp, cumulative register value in Wh), ordered by timestamp.
+type RegisterSeries = Vec<(u64, u64)>;
+
+/// A value of an interval (IntervalReading).
+#[derive(Clone, Copy, Debug)]
+struct IntervalReading {
+    start: u64,
+    duration: u64,
+    value: i64,
+}
+
+impl IntervalReading {
+    /// End of the interval (i.e. start + duration), which is refused if it overflows.
+    fn end(&self) -> Result<u64> {
+        self.start.checked_add(self.duration).with_context(|| format!("the end of the IntervalReading at {} overflows", self.start))
+    }
+}
+
+/// Load the raw meter readings from a Green Button (ESPI) XML file.
+pub fn load_green_button(path: &Path) -> Result<Vec<RawMeterReading>> {
+    let file = fs::read_to_string(path).with_context(|| format!("failed to read the Green Button file {}", path.display()))?;
+    parse_green_button(&file).with_context(|| format!("failed to parse the Green Button file {}", path.display()))
+}
+
+/// Parse the raw meter readings from a Green Button (ESPI) XML document.
+///
+/// If the document has both the reverse (export) and the forward (import) MeterReadings, they are read in the
+/// net-metering mode (i.e. the export is the `register` and the import is the `import_register`). Otherwise, the only
+/// MeterReading is read as the (export) `register`.
+pub fn parse_green_button(xml: &str) -> Result<Vec<RawMeterReading>> {
+    let document = Document::parse(xml).context("invalid XML")?;
+
+    let mut reading_types: HashMap<String, ReadingType> = HashMap::new();
+    let mut meter_reading_links: HashMap<String, Vec<String>> = HashMap::new(); // @dev - MeterReading href -> related hrefs
+    let mut interval_readings: HashMap<String, Vec<IntervalReading>> = HashMap::new(); // @dev - MeterReading href -> IntervalReadings
+    for entry in document.descendants().filter(|node| is(node, "entry")) {
+        let self_href = link(&entry, "self");
+        if let Some(reading_type) = entry.descendants().find(|node| is(node, "ReadingType")) {
+            let href = self_href.context("a ReadingType entry has no self link")?;
+            reading_types.insert(href, parse_reading_type(&reading_type)?);
+        } else if entry.descendants().any(|node| is(&node, "MeterReading")) {
+            // @dev - A MeterReading may also be related to its IntervalBlocks (or ReadingQualities), so all the related
+            //        links are kept, and the one of a ReadingType is picked once all the entries are read.
+            if let Some(href) = self_href {
+                meter_reading_links.insert(href, links(&entry, "related"));
+            }
+        } else if entry.descendants().any(|node| is(&node, "IntervalBlock")) {
+            // @dev - The up link of an IntervalBlock is the collection of the IntervalBlocks of its MeterReading.
+            let up_href = link(&entry, "up").unwrap_or_default();
+            let meter_reading_href = up_href.trim_end_matches('/').trim_end_matches("/IntervalBlock").to_string();
+            let readings = interval_readings.entry(meter_reading_href).or_default();
+            for interval_reading in entry.descendants().filter(|node| is(node, "IntervalReading")) {
+                readings.push(parse_interval_reading(&interval_reading)?);
+            }
+        }
+    }
+    ensure!(!interval_readings.is_empty(), "no IntervalReading is found");
+
+    // Convert the interval readings of each MeterReading into a cumulative register series (Unit: Wh).
+    let mut series: Vec<(Option<u32>, RegisterSeries)> = Vec::new();
+    for (meter_reading_href, readings) in interval_readings {
+        let linked_reading_type = meter_reading_links
+            .get(&meter_reading_href)
+            .and_then(|hrefs| hrefs.iter().find_map(|href| reading_types.get(href)));
+        let reading_type = match linked_reading_type {
+            Some(reading_type) => *reading_type,
+            // @dev - A file of a single MeterReading often omits the links, so its only ReadingType is used.
+            None if reading_types.len() == 1 => *reading_types.values().next().unwrap(),
+            None => bail!("the ReadingType of the MeterReading {:?} is not found", meter_reading_href),
+        };
+        series.push((reading_type.flow_direction, to_register_series(reading_type, readings)?));
+    }
+
+    match series.len() {
+        1 => {
+            let (flow_direction, registers) = series.remove(0);
+            // @dev - A single MeterReading is sold as the export, so it must not be the import (or another flow direction).
+            ensure!(
+                flow_direction.is_none() || flow_direction == Some(FLOW_DIRECTION_REVERSE),
+                "a single MeterReading must be of the reverse (export) flow direction, not {:?}",
+                flow_direction
+            );
+            Ok(registers
+                .into_iter()
+                .map(|(timestamp, register)| RawMeterReading { timestamp, register, import_register: None })
+                .collect())
+        }
+        2 => {
+            let export = series.iter().position(|(flow_direction, _)| *flow_direction == Some(FLOW_DIRECTION_REVERSE));
+            let import = series.iter().position(|(flow_direction, _)| *flow_direction == Some(FLOW_DIRECTION_FORWARD));
+            let (Some(export), Some(import)) = (export, import) else {
+                bail!("2 MeterReadings must be the reverse (export) and the forward (import) flow directions");
+            };
+            let (export, import) = (&series[export].1, &series[import].1);
+            ensure!(
+                export.len() == import.len() && export.iter().zip(import).all(|(export, import)| export.0 == import.0),
+                "the export and the import MeterReadings must have the same intervals"
+            );
+            Ok(export
+                .iter()
+                .zip(import)
+                .map(|((timestamp, register), (_, import_register))| RawMeterReading {
+                    timestamp: *timestamp,
+                    register: *register,
+                    import_register: Some(*import_register),
+                })
+                .collect())
+        }
+        count => bail!("{} MeterReadings are found, while 1 (or 2 in the net-metering mode) are supported", count),
+    }
+}
+
+/// Convert the interval readings into a cumulative register series.
+///
+/// The delta values are accumulated from 0 at the start of the first interval. The values are accumulated in their own
+/// unit (with the multiplier), and each cumulative value is floored into Wh, so that the register never decreases.
+fn to_register_series(reading_type: ReadingType, mut readings: Vec<IntervalReading>) -> Result<RegisterSeries> {
+    ensure!(!readings.is_empty(), "a MeterReading has no IntervalReading");
+    readings.sort_by_key(|reading| reading.start);
+    for pair in readings.windows(2) {
+        ensure!(pair[0].end()? <= pair[1].start, "the IntervalReadings overlap (at {})", pair[1].start);
+    }
+
+    // @dev - (numerator, denominator) to convert the accumulated value into Wh: value * 10^multiplier (/ 3600 for W * s).
+    let (mut numerator, mut denominator): (i128, i128) = match reading_type.uom {
+        UOM_WH => (1, 1),
+        UOM_W => (1, 3600),
+        uom => bail!("the unit of measure (uom) {} is not supported (only Wh (72) and W (38) are)", uom),
+    };
+    let scale = 10i128.checked_pow(reading_type.power_of_ten_multiplier.unsigned_abs()).context("the powerOfTenMultiplier is too large")?;
+    if reading_type.power_of_ten_multiplier >= 0 {
+        numerator *= scale;
+    } else {
+        denominator *= scale;
+    }
+    let to_wh = |value: i128| -> Result<u64> { u64::try_from(value * numerator / denominator).context("the register overflows") };
+
+    let mut series = Vec::with_capacity(readings.len() + 1);
+    if reading_type.cumulative {
+        ensure!(reading_type.uom == UOM_WH, "the cumulative values must be in Wh");
+        for reading in &readings {
+            ensure!(reading.value >= 0, "the cumulative value must not be negative (at {})", reading.start);
+            series.push((reading.end()?, to_wh(i128::from(reading.value))?));
+        }
+    } else {
+        let mut accumulated: i128 = 0;
+        series.push((readings[0].start, 0));
+        for reading in &readings {
+            ensure!(reading.value >= 0, "the interval value must not be negative (at {})", reading.start);
+            // @dev - The average power (W) over the interval is accumulated as W * s.
+            accumulated += match reading_type.uom {
+                UOM_W => i128::from(reading.value) * i128::from(reading.duration),
+                _ => i128::from(reading.value),
+            };
+            series.push((reading.end()?, to_wh(accumulated)?));
+        }
+    }
+    Ok(series)
+}
+
+fn parse_reading_type(node: &Node) -> Result<ReadingType> {
+    let uom = child_value(node, "uom")?.context("the ReadingType has no uom")?;
+    Ok(ReadingType {
+        uom: uom.try_into().context("invalid uom")?,
+        power_of_ten_multiplier: child_value(node, "powerOfTenMultiplier")?.unwrap_or(0).try_into().context("invalid powerOfTenMultiplier")?,
+        flow_direction: child_value(node, "flowDirection")?.map(u32::try_from).transpose().context("invalid flowDirection")?,
+        cumulative: child_value(node, "accumulationBehaviour")?
+            .is_some_and(|behaviour| CUMULATIVE_ACCUMULATION_BEHAVIOURS.iter().any(|cumulative| i64::from(*cumulative) == behaviour)),
+    })
+}
+
+fn parse_interval_reading(node: &Node) -> Result<IntervalReading> {
+    let time_period = node.children().find(|child| is(child, "timePeriod")).context("the IntervalReading has no timePeriod")?;
+    Ok(IntervalReading {
+        start: child_value(&time_period, "start")?.context("the timePeriod has no start")?.try_into().context("invalid start")?,
+        duration: child_value(&time_period, "duration")?.context("the timePeriod has no duration")?.try_into().context("invalid duration")?,
+        value: child_value(node, "value")?.context("the IntervalReading has no value")?,
+    })
+}
+
+/// Whether the node is the element of the given local name (whichever namespace it is in).
+fn is(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// The href of the (first) link of the given relation of an Atom entry.
+fn link(entry: &Node, rel: &str) -> Option<String> {
+    links(entry, rel).into_iter().next()
+}
+
+/// The hrefs of all the links of the given relation of an Atom entry.
+fn links(entry: &Node, rel: &str) -> Vec<String> {
+    entry
+        .children()
+        .filter(|child| is(child, "link") && child.attribute("rel") == Some(rel))
+        .filter_map(|link| link.attribute("href"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The integer value of the child element of the given local name, if any.
+fn child_value(node: &Node, name: &str) -> Result<Option<i64>> {
+    node.children()
+        .find(|child| is(child, name))
+        .map(|child| {
+            let text = child.text().unwrap_or_default().trim();
+            text.parse().with_context(|| format!("invalid {}: {:?}", name, text))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1740638030;
+
+    fn interval_reading(start: u64, duration: u64, value: i64) -> String {
+        format!(
+            "<espi:IntervalReading><espi:timePeriod><espi:duration>{duration}</espi:duration><espi:start>{start}</espi:start></espi:timePeriod><espi:value>{value}</espi:value></espi:IntervalReading>"
+        )
+    }
+
+    /// Hourly IntervalReadings of the given values from START.
+    fn hourly(values: &[i64]) -> String {
+        values.iter().enumerate().map(|(index, value)| interval_reading(START + 3600 * index as u64, 3600, *value)).collect()
+    }
+
+    /// The entries of a MeterReading (related to its IntervalBlocks first, and then to its ReadingType, as exported by
+    /// some utilities), its ReadingType and its IntervalBlock.
+    fn meter_reading_entries(id: u32, reading_type: &str, interval_readings: &str) -> String {
+        let meter_reading = format!("/espi/1_1/resource/Subscription/1/UsagePoint/1/MeterReading/{id}");
+        format!(
+            r#"<entry><link rel="self" href="/espi/1_1/resource/ReadingType/{id}"/><content><espi:ReadingType>{reading_type}</espi:ReadingType></content></entry>
+<entry><link rel="self" href="{meter_reading}"/><link rel="related" href="{meter_reading}/IntervalBlock"/><link rel="related" href="/espi/1_1/resource/ReadingType/{id}"/><content><espi:MeterReading/></content></entry>
+<entry><link rel="self" href="{meter_reading}/IntervalBlock/1"/><link rel="up" href="{meter_reading}/IntervalBlock"/><content><espi:IntervalBlock>{interval_readings}</espi:IntervalBlock></content></entry>"#
+        )
+    }
+
+    fn feed(entries: &[String]) -> String {
+        format!(r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:espi="http://naesb.org/espi">{}</feed>"#, entries.concat())
+    }
+
+    fn registers(readings: &[RawMeterReading]) -> Vec<(u64, u64, Option<u64>)> {
+        readings.iter().map(|reading| (reading.timestamp, reading.register, reading.import_register)).collect()
+    }
+
+    #[test]
+    fn accumulates_delta_values_in_wh() {
+        let reading_type = "<espi:accumulationBehaviour>4</espi:accumulationBehaviour><espi:uom>72</espi:uom>";
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[500, 700]))])).unwrap();
+        assert_eq!(registers(&readings), vec![(START, 0, None), (START + 3600, 500, None), (START + 7200, 1200, None)]);
+    }
+
+    #[test]
+    fn applies_a_positive_power_of_ten_multiplier() {
+        // @dev - kWh (= Wh * 10^3)
+        let reading_type = "<espi:powerOfTenMultiplier>3</espi:powerOfTenMultiplier><espi:uom>72</espi:uom>";
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[1, 2]))])).unwrap();
+        assert_eq!(registers(&readings), vec![(START, 0, None), (START + 3600, 1000, None), (START + 7200, 3000, None)]);
+    }
+
+    #[test]
+    fn applies_a_negative_power_of_ten_multiplier() {
+        // @dev - mWh (= Wh * 10^-3), which is accumulated before being floored into Wh.
+        let reading_type = "<espi:powerOfTenMultiplier>-3</espi:powerOfTenMultiplier><espi:uom>72</espi:uom>";
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[1500, 1600]))])).unwrap();
+        assert_eq!(registers(&readings), vec![(START, 0, None), (START + 3600, 1, None), (START + 7200, 3, None)]);
+    }
+
+    #[test]
+    fn converts_the_average_power_in_w_into_wh() {
+        let reading_type = "<espi:uom>38</espi:uom>";
+        let interval_readings = [interval_reading(START, 3600, 1000), interval_reading(START + 3600, 1800, 500)].concat();
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &interval_readings)])).unwrap();
+        assert_eq!(registers(&readings), vec![(START, 0, None), (START + 3600, 1000, None), (START + 5400, 1250, None)]);
+    }
+
+    #[test]
+    fn rejects_an_unsupported_uom() {
+        // @dev - 33 = kVArh (reactive energy)
+        let xml = feed(&[meter_reading_entries(1, "<espi:uom>33</espi:uom>", &hourly(&[500]))]);
+        assert!(format!("{:#}", parse_green_button(&xml).unwrap_err()).contains("uom"));
+    }
+
+    #[test]
+    fn reads_cumulative_values_as_the_register() {
+        let reading_type = "<espi:accumulationBehaviour>1</espi:accumulationBehaviour><espi:uom>72</espi:uom>";
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[5000000, 5600000]))])).unwrap();
+        // @dev - Each cumulative value is the register at the end of its interval.
+        assert_eq!(registers(&readings), vec![(START + 3600, 5000000, None), (START + 7200, 5600000, None)]);
+
+        let reading_type = "<espi:accumulationBehaviour>1</espi:accumulationBehaviour><espi:uom>38</espi:uom>";
+        assert!(parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[1000, 1000]))])).is_err());
+
+        // @dev - 2 = continuousCumulative, which is also the register (and is not accumulated again).
+        let reading_type = "<espi:accumulationBehaviour>2</espi:accumulationBehaviour><espi:uom>72</espi:uom>";
+        let readings = parse_green_button(&feed(&[meter_reading_entries(1, reading_type, &hourly(&[5000000, 5600000]))])).unwrap();
+        assert_eq!(registers(&readings), vec![(START + 3600, 5000000, None), (START + 7200, 5600000, None)]);
+    }
+
+    #[test]
+    fn rejects_an_interval_whose_end_overflows() {
+        let reading_type = ReadingType { uom: UOM_WH, power_of_ten_multiplier: 0, flow_direction: None, cumulative: false };
+        let readings = vec![IntervalReading { start: START, duration: 3600, value: 500 }, IntervalReading { start: u64::MAX - 100, duration: 3600, value: 500 }];
+        assert!(format!("{:#}", to_register_series(reading_type, readings).unwrap_err()).contains("overflows"));
+    }
+
+    #[test]
+    fn pairs_the_export_and_the_import_in_the_net_metering_mode() {
+        let export_type = "<espi:flowDirection>19</espi:flowDirection><espi:uom>72</espi:uom>";
+        let import_type = "<espi:flowDirection>1</espi:flowDirection><espi:uom>72</espi:uom>";
+        let xml = feed(&[meter_reading_entries(1, import_type, &hourly(&[100, 300])), meter_reading_entries(2, export_type, &hourly(&[800, 400]))]);
+        let readings = parse_green_button(&xml).unwrap();
+        assert_eq!(
+            registers(&readings),
+            vec![(START, 0, Some(0)), (START + 3600, 800, Some(100)), (START + 7200, 1200, Some(400))]
+        );
+
+        // @dev - The export and the import must be read over the same intervals.
+        let xml = feed(&[meter_reading_entries(1, import_type, &hourly(&[100])), meter_reading_entries(2, export_type, &hourly(&[800, 400]))]);
+        assert!(format!("{:#}", parse_green_button(&xml).unwrap_err()).contains("same intervals"));
+    }
+
+    #[test]
+    fn picks_the_reading_type_among_the_related_links() {
+        // @dev - Both ReadingTypes are in Wh, but they are told apart by their multipliers, so the ReadingType must be
+        //        picked from the related link which is a ReadingType (not the first related link, i.e. the IntervalBlocks).
+        let export_type = "<espi:flowDirection>19</espi:flowDirection><espi:powerOfTenMultiplier>3</espi:powerOfTenMultiplier><espi:uom>72</espi:uom>";
+        let import_type = "<espi:flowDirection>1</espi:flowDirection><espi:uom>72</espi:uom>";
+        let xml = feed(&[meter_reading_entries(1, export_type, &hourly(&[2])), meter_reading_entries(2, import_type, &hourly(&[300]))]);
+        let readings = parse_green_button(&xml).unwrap();
+        assert_eq!(registers(&readings), vec![(START, 0, Some(0)), (START + 3600, 2000, Some(300))]);
+    }
+
+    #[test]
+    fn rejects_a_single_import_meter_reading() {
+        let import_type = "<espi:flowDirection>1</espi:flowDirection><espi:uom>72</espi:uom>";
+        let xml = feed(&[meter_reading_entries(1, import_type, &hourly(&[500]))]);
+        assert!(format!("{:#}", parse_green_button(&xml).unwrap_err()).contains("reverse (export)"));
+
+        // @dev - A single MeterReading without a flow direction is read as the export.
+        let xml = feed(&[meter_reading_entries(1, "<espi:uom>72</espi:uom>", &hourly(&[500]))]);
+        assert_eq!(registers(&parse_green_button(&xml).unwrap()), vec![(START, 0, None), (START + 3600, 500, None)]);
+    }
+}
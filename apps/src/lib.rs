@@ -0,0 +1,33 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host app of the energy marketplace: the subcommands of the CLI, the proof backends, which generate the proofs of
+//! the guest programs, the estimate of their cost, the pre-flight checks of the nullifiers, the loaders of the meter
+//! readings files (incl. the Green Button files) and the local store of the sells.
+
+pub mod backend;
+pub mod commands;
+pub mod estimate;
+pub mod green_button;
+pub mod nullifiers;
+pub mod readings;
+pub mod store;
+
+/// Rust bindings of the IEnergyAggregator interface, which is called by the subcommands.
+pub mod energy_aggregator {
+    alloy::sol!(
+        #![sol(rpc, all_derives)]
+        "../contracts/src/IEnergyAggregator.sol"
+    );
+}
@@ -0,0 +1,78 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pre-flight checks of the nullifier of a sell order, so that a reused nullifier is refused before paying for a proof
+//! (instead of after paying the market and broadcasting the createSellOrder() tx).
+//!
+//! The EnergyAggregator contract records every used nullifier (`usedNullifiers[nullifier]`), whichever seal it was
+//! submitted with, so the nullifier is looked up directly. The sells already submitted by this app are checked against
+//! the local store (See: ./apps/src/store.rs), which also covers a proof request that has been paid but whose sell
+//! order has not been created yet.
+
+use alloy::{
+    primitives::{B256, U256},
+    providers::Provider,
+    transports::Transport,
+};
+use anyhow::{Context, Result};
+
+use crate::energy_aggregator::IEnergyAggregator::IEnergyAggregatorInstance;
+
+/// Whether the nullifier has already been used by a sell order (including an aggregate one).
+pub async fn is_nullifier_used<T, P>(energy_aggregator: &IEnergyAggregatorInstance<T, P>, nullifier: B256) -> Result<bool>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    Ok(energy_aggregator
+        .usedNullifiers(nullifier)
+        .call()
+        .await
+        .context("failed to get whether the nullifier is used from contract")?
+        ._0)
+}
+
+/// Find the stored sell order which has the nullifier, if any, e.g. to recover the ID of a sell order whose tx was
+/// included after the app died. This scans the sell orders, so check [is_nullifier_used] first.
+/// (NOTE: A nullifier used by an aggregate sell order, which stores the hash of all its nullifiers, is not found)
+pub async fn find_sell_order_by_nullifier<T, P>(
+    energy_aggregator: &IEnergyAggregatorInstance<T, P>,
+    nullifier: B256,
+) -> Result<Option<u64>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let sell_order_id = energy_aggregator
+        .sellOrderId()
+        .call()
+        .await
+        .context("failed to get the latest sell order ID from contract")?
+        ._0;
+    let sell_order_count = u64::try_from(sell_order_id).context("the latest sell order ID does not fit in u64")?;
+
+    // @dev - The latest sell orders are checked first, since a resubmission is likely to be a recent one.
+    for id in (1..=sell_order_count).rev() {
+        let sell_order = energy_aggregator
+            .getSellOrder(U256::from(id))
+            .call()
+            .await
+            .with_context(|| format!("failed to get the sell order {} from contract", id))?
+            ._0;
+        if sell_order.monitoredNullifier == nullifier {
+            return Ok(Some(id));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,368 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load the meter readings of a smart meter, either with their hash paths up to a signed monitored merkle root, or as
+//! the raw readings exported by the smart meter (CSV, JSON or Green Button XML), from which the merkle tree, the
+//! window and the energy available to be sold are derived (See: ingest_meter_readings()).
+
+use std::{fs, path::Path};
+
+use alloy::primitives::B256;
+use anyhow::{bail, ensure, Context, Result};
+use energy_types::{merkle::MerkleTree, MeterReading, MeterReadingProof, MeterReadings, NetMeterReading, Reading};
+use serde::Deserialize;
+
+use crate::green_button;
+
+/// A meter reading with its merkle inclusion proof, as stored in the meter readings file.
+#[derive(Deserialize, Debug)]
+struct MeterReadingEntry {
+    /// The time when the register was read (UTC timestamp).
+    timestamp: u64,
+    /// The cumulative (export) register value (Unit: Wh).
+    register: u64,
+    /// (Optional) The cumulative import register value of a prosumer's smart meter (Unit: Wh).
+    import_register: Option<u64>,
+    /// Sibling hashes from the meter reading leaf up to the monitored merkle root.
+    hash_path: Vec<B256>,
+}
+
+/// Load the meter readings of the window from a JSON file, which is an array (ordered by timestamp) of:
+/// `{ "timestamp": 1740638030, "register": 5000000, "hash_path": ["0x...", ...] }`
+///
+/// If every meter reading also has an `"import_register"`, the readings are loaded in the net-metering mode,
+/// where the energy available to be sold is the net export minus the net import over the window.
+pub fn load_meter_readings(path: &Path) -> Result<MeterReadings> {
+    let file = fs::read_to_string(path).with_context(|| format!("failed to read the meter readings file {}", path.display()))?;
+    let entries: Vec<MeterReadingEntry> = serde_json::from_str(&file).with_context(|| format!("failed to parse the meter readings file {}", path.display()))?;
+
+    let net_metering = entries.iter().any(|entry| entry.import_register.is_some());
+    if net_metering && entries.iter().any(|entry| entry.import_register.is_none()) {
+        bail!("either all or none of the meter readings must have an import_register");
+    }
+
+    if net_metering {
+        Ok(MeterReadings::Net(
+            entries
+                .into_iter()
+                .map(|entry| MeterReadingProof {
+                    reading: NetMeterReading {
+                        timestamp: entry.timestamp,
+                        export_register: entry.register,
+                        import_register: entry.import_register.unwrap_or_default(),
+                    },
+                    hash_path: entry.hash_path.iter().map(|hash| hash.0).collect(),
+                })
+                .collect(),
+        ))
+    } else {
+        Ok(MeterReadings::Gross(
+            entries
+                .into_iter()
+                .map(|entry| MeterReadingProof {
+                    reading: MeterReading { timestamp: entry.timestamp, register: entry.register },
+                    hash_path: entry.hash_path.iter().map(|hash| hash.0).collect(),
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// A raw meter reading as exported by the smart meter, without its merkle inclusion proof.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RawMeterReading {
+    /// The time when the register was read (UTC timestamp).
+    pub timestamp: u64,
+    /// The cumulative (export) register value (Unit: Wh).
+    pub register: u64,
+    /// (Optional) The cumulative import register value of a prosumer's smart meter (Unit: Wh).
+    pub import_register: Option<u64>,
+}
+
+/// Load the raw meter readings from a CSV (`.csv`), a Green Button XML (`.xml`) or a JSON (any other extension) file.
+///
+/// The CSV file must have a header of the column names, e.g.:
+/// `timestamp,register` (or `timestamp,register,import_register` in the net-metering mode).
+/// The JSON file is an array of `{ "timestamp": 1740638030, "register": 5000000 }` (with an optional `"import_register"`).
+/// The Green Button file is parsed from its interval data (See: ./apps/src/green_button.rs).
+pub fn load_raw_meter_readings(path: &Path) -> Result<Vec<RawMeterReading>> {
+    let has_extension = |expected: &str| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(expected));
+    if has_extension("xml") {
+        return green_button::load_green_button(path);
+    }
+    let file = fs::read_to_string(path).with_context(|| format!("failed to read the readings file {}", path.display()))?;
+    let is_csv = has_extension("csv");
+    if !is_csv {
+        return serde_json::from_str(&file).with_context(|| format!("failed to parse the readings file {}", path.display()));
+    }
+
+    let mut lines = file.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().with_context(|| format!("the readings file {} is empty", path.display()))?;
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|column| *column == name);
+    let timestamp_column = column("timestamp").context("the CSV header must have a \"timestamp\" column")?;
+    let register_column = column("register").context("the CSV header must have a \"register\" column")?;
+    let import_register_column = column("import_register");
+
+    lines
+        .map(|(index, line)| {
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            let value = |column: usize| -> Result<u64> {
+                values
+                    .get(column)
+                    .with_context(|| format!("the line {} of the readings file has too few columns", index + 1))?
+                    .parse()
+                    .with_context(|| format!("the line {} of the readings file has an invalid value", index + 1))
+            };
+            Ok(RawMeterReading {
+                timestamp: value(timestamp_column)?,
+                register: value(register_column)?,
+                import_register: import_register_column.map(value).transpose()?,
+            })
+        })
+        .collect()
+}
+
+/// The meter readings ingested from the raw meter readings, with all the values derived from them, which would
+/// otherwise be computed by hand.
+#[derive(Clone, Debug)]
+pub struct IngestedMeterReadings {
+    /// The root of the merkle tree built over all the meter readings (to be signed by the smart meter).
+    pub monitored_merkle_root: B256,
+    /// The timestamp of the first meter reading (= start of the window).
+    pub window_start: u64,
+    /// The timestamp of the last meter reading (= end of the window).
+    pub window_end: u64,
+    /// The energy available to be sold in the window, in the same way as the smart-meter guest program computes it
+    /// (Unit: kWh).
+    pub available: u64,
+    /// The meter readings with their hash paths up to the monitored merkle root.
+    pub meter_readings: MeterReadings,
+}
+
+/// Build the merkle tree over the raw meter readings, and derive the monitored merkle root, the window and the
+/// energy available to be sold from them. The readings are checked in the same way as the guest program (e.g. ordered
+/// by timestamp, non-decreasing registers), so that an unprovable input is caught before proving.
+pub fn ingest_meter_readings(raw_meter_readings: &[RawMeterReading]) -> Result<IngestedMeterReadings> {
+    ensure!(raw_meter_readings.len() >= 2, "at least 2 meter readings are required to compute the energy in the window");
+    let net_metering = raw_meter_readings.iter().any(|reading| reading.import_register.is_some());
+    ensure!(
+        !net_metering || raw_meter_readings.iter().all(|reading| reading.import_register.is_some()),
+        "either all or none of the meter readings must have an import_register"
+    );
+    for pair in raw_meter_readings.windows(2) {
+        ensure!(pair[0].timestamp < pair[1].timestamp, "the meter readings must be ordered by timestamp (at {})", pair[1].timestamp);
+        ensure!(
+            pair[0].register <= pair[1].register && pair[0].import_register <= pair[1].import_register,
+            "the cumulative registers of the meter readings must not decrease (at {})",
+            pair[1].timestamp
+        );
+    }
+
+    let first = raw_meter_readings[0];
+    let last = raw_meter_readings[raw_meter_readings.len() - 1];
+    let (meter_readings, tree, available) = if net_metering {
+        let readings: Vec<NetMeterReading> = raw_meter_readings
+            .iter()
+            .map(|reading| NetMeterReading {
+                timestamp: reading.timestamp,
+                export_register: reading.register,
+                import_register: reading.import_register.unwrap_or_default(),
+            })
+            .collect();
+        let tree = MerkleTree::new(readings.iter().map(Reading::leaf).collect()).context("no meter readings")?;
+        let proofs = with_hash_paths(readings, &tree);
+        // @dev - The net surplus, i.e. the net export minus the net import over the window (See: the smart-meter guest program).
+        let net_import = last.import_register.unwrap_or_default() - first.import_register.unwrap_or_default();
+        let available = (last.register - first.register).saturating_sub(net_import) / 1000;
+        (MeterReadings::Net(proofs), tree, available)
+    } else {
+        let readings: Vec<MeterReading> = raw_meter_readings
+            .iter()
+            .map(|reading| MeterReading { timestamp: reading.timestamp, register: reading.register })
+            .collect();
+        let tree = MerkleTree::new(readings.iter().map(Reading::leaf).collect()).context("no meter readings")?;
+        let proofs = with_hash_paths(readings, &tree);
+        (MeterReadings::Gross(proofs), tree, (last.register - first.register) / 1000)
+    };
+
+    Ok(IngestedMeterReadings {
+        monitored_merkle_root: B256::from(tree.root()),
+        window_start: first.timestamp,
+        window_end: last.timestamp,
+        available,
+        meter_readings,
+    })
+}
+
+/// Attach the hash path of each meter reading (in the same order as the leaves of the tree).
+fn with_hash_paths<R>(readings: Vec<R>, tree: &MerkleTree) -> Vec<MeterReadingProof<R>> {
+    readings
+        .into_iter()
+        .enumerate()
+        .map(|(index, reading)| MeterReadingProof {
+            reading,
+            hash_path: tree.hash_path(index).unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use energy_types::merkle::compute_merkle_root;
+
+    use super::*;
+
+    /// Write the contents to a temp file ending with the given name (i.e. of its format), which is removed when the
+    /// returned path is dropped.
+    fn readings_file(name: &str, contents: &str) -> tempfile::TempPath {
+        let mut file = tempfile::Builder::new().suffix(name).tempfile().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.into_temp_path()
+    }
+
+    fn raw(timestamp: u64, register: u64, import_register: Option<u64>) -> RawMeterReading {
+        RawMeterReading { timestamp, register, import_register }
+    }
+
+    /// Every hash path must lead from its reading up to the monitored merkle root.
+    fn assert_hash_paths<R: Reading>(proofs: &[MeterReadingProof<R>], monitored_merkle_root: B256) {
+        for proof in proofs {
+            assert_eq!(compute_merkle_root(proof.reading.leaf(), &proof.hash_path), monitored_merkle_root.0);
+        }
+    }
+
+    #[test]
+    fn loads_raw_meter_readings_from_csv() {
+        let path = readings_file("gross.csv", "timestamp, register\n1740638030,5000000\n\n1740639830,5600000\n1740641630,6100000\n");
+        let raw_meter_readings = load_raw_meter_readings(&path).unwrap();
+        assert_eq!(raw_meter_readings.len(), 3);
+        assert_eq!((raw_meter_readings[1].timestamp, raw_meter_readings[1].register), (1740639830, 5600000));
+        assert!(raw_meter_readings.iter().all(|reading| reading.import_register.is_none()));
+    }
+
+    #[test]
+    fn loads_raw_meter_readings_with_net_metering_columns_from_csv() {
+        // @dev - The columns are looked up by name, so their order does not matter.
+        let path = readings_file("net.csv", "import_register,timestamp,register\n200000,1740638030,5000000\n500000,1740641630,6100000\n");
+        let raw_meter_readings = load_raw_meter_readings(&path).unwrap();
+        assert_eq!(raw_meter_readings[0].import_register, Some(200000));
+        assert_eq!(raw_meter_readings[1].import_register, Some(500000));
+        assert_eq!(raw_meter_readings[1].register, 6100000);
+    }
+
+    #[test]
+    fn loads_raw_meter_readings_from_json() {
+        let path = readings_file(
+            "gross.json",
+            r#"[{ "timestamp": 1740638030, "register": 5000000 }, { "timestamp": 1740641630, "register": 6100000 }]"#,
+        );
+        let raw_meter_readings = load_raw_meter_readings(&path).unwrap();
+        assert_eq!(raw_meter_readings.len(), 2);
+        assert_eq!(raw_meter_readings[1].register, 6100000);
+        assert_eq!(raw_meter_readings[1].import_register, None);
+    }
+
+    #[test]
+    fn rejects_an_empty_readings_file() {
+        let error = load_raw_meter_readings(&readings_file("empty.csv", "")).unwrap_err();
+        assert!(error.to_string().contains("is empty"), "{error}");
+        assert!(load_raw_meter_readings(&readings_file("empty.json", "")).is_err());
+        // @dev - A header only (or an empty JSON array) has no readings, so there is nothing to ingest.
+        let raw_meter_readings = load_raw_meter_readings(&readings_file("header.csv", "timestamp,register\n")).unwrap();
+        assert!(ingest_meter_readings(&raw_meter_readings).is_err());
+    }
+
+    #[test]
+    fn rejects_a_csv_without_the_register_column() {
+        let error = load_raw_meter_readings(&readings_file("no-register.csv", "timestamp,value\n1740638030,5000000\n")).unwrap_err();
+        assert!(error.to_string().contains("\"register\" column"), "{error}");
+    }
+
+    #[test]
+    fn ingests_gross_meter_readings() {
+        let ingested = ingest_meter_readings(&[
+            raw(1740638030, 5000000, None),
+            raw(1740639830, 5600000, None),
+            raw(1740641630, 6100999, None),
+        ])
+        .unwrap();
+        assert_eq!((ingested.window_start, ingested.window_end), (1740638030, 1740641630));
+        // @dev - 1100.999 kWh are rounded down to whole kWh, like the guest program does.
+        assert_eq!(ingested.available, 1100);
+        let MeterReadings::Gross(proofs) = &ingested.meter_readings else { panic!("expected gross meter readings") };
+        assert_eq!(proofs.len(), 3);
+        assert_hash_paths(proofs, ingested.monitored_merkle_root);
+    }
+
+    #[test]
+    fn ingests_net_meter_readings() {
+        let ingested = ingest_meter_readings(&[
+            raw(1740638030, 5000000, Some(200000)),
+            raw(1740639830, 5600000, Some(300000)),
+            raw(1740641630, 6100000, Some(500000)),
+        ])
+        .unwrap();
+        // @dev - 1100 kWh exported minus 300 kWh imported over the window.
+        assert_eq!(ingested.available, 800);
+        let MeterReadings::Net(proofs) = &ingested.meter_readings else { panic!("expected net meter readings") };
+        assert_eq!(proofs[2].reading.import_register, 500000);
+        assert_hash_paths(proofs, ingested.monitored_merkle_root);
+
+        // @dev - A net importer has no surplus to be sold.
+        let ingested = ingest_meter_readings(&[raw(1740638030, 5000000, Some(0)), raw(1740641630, 5100000, Some(400000))]).unwrap();
+        assert_eq!(ingested.available, 0);
+    }
+
+    #[test]
+    fn binds_the_monitored_merkle_root_to_every_reading() {
+        let readings = [raw(1740638030, 5000000, None), raw(1740641630, 6100000, None)];
+        let mut tampered = readings;
+        tampered[1].register = 6200000;
+        assert_ne!(
+            ingest_meter_readings(&readings).unwrap().monitored_merkle_root,
+            ingest_meter_readings(&tampered).unwrap().monitored_merkle_root
+        );
+    }
+
+    #[test]
+    fn rejects_non_monotonic_registers() {
+        let error = ingest_meter_readings(&[raw(1740638030, 5000000, None), raw(1740641630, 4900000, None)]).unwrap_err();
+        assert!(error.to_string().contains("must not decrease"), "{error}");
+        let error =
+            ingest_meter_readings(&[raw(1740638030, 5000000, Some(300000)), raw(1740641630, 6100000, Some(200000))]).unwrap_err();
+        assert!(error.to_string().contains("must not decrease"), "{error}");
+    }
+
+    #[test]
+    fn rejects_unordered_or_too_few_readings() {
+        let error = ingest_meter_readings(&[raw(1740641630, 5000000, None), raw(1740638030, 6100000, None)]).unwrap_err();
+        assert!(error.to_string().contains("ordered by timestamp"), "{error}");
+        assert!(ingest_meter_readings(&[raw(1740638030, 5000000, None)]).is_err());
+    }
+
+    #[test]
+    fn rejects_partial_net_metering_columns() {
+        let error = ingest_meter_readings(&[raw(1740638030, 5000000, Some(0)), raw(1740641630, 6100000, None)]).unwrap_err();
+        assert!(error.to_string().contains("either all or none"), "{error}");
+
+        let path = readings_file(
+            "partial.json",
+            r#"[{ "timestamp": 1740638030, "register": 5000000, "import_register": 0, "hash_path": [] }, { "timestamp": 1740641630, "register": 6100000, "hash_path": [] }]"#,
+        );
+        assert!(load_meter_readings(&path).is_err());
+    }
+}
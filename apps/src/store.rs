@@ -0,0 +1,540 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local state of the app, stored in an embedded SQLite database, which records each step of the lifecycle of the
+//! sell orders submitted by this app: the input and the journal of the proof, the proof request in the Boundless market
+//! (ID and expiry), the seal, the createSellOrder() tx and the created sell order.
+//!
+//! The store survives a crash of the app, so that a paid proof request is not lost, the submitted nullifiers are
+//! refused before paying for another proof, and the sold energy can be queried without scanning the chain.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::{Bytes, B256, U256};
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use crate::backend::ProofObserver;
+
+/// Schema of the store. The `sell_steps` table is an append-only log of the steps of each sell (for the audit).
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sells (
+    id                          INTEGER PRIMARY KEY AUTOINCREMENT,
+    nullifier                   TEXT NOT NULL,
+    monitored_merkle_root       TEXT NOT NULL,
+    amount_of_energy_to_be_sold INTEGER NOT NULL,
+    window_start                INTEGER NOT NULL,
+    window_end                  INTEGER NOT NULL,
+    prover                      TEXT NOT NULL,
+    input                       TEXT NOT NULL,
+    journal                     BLOB NOT NULL,
+    status                      TEXT NOT NULL,
+    request_id                  TEXT,
+    expires_at                  INTEGER,
+    seal                        BLOB,
+    tx_hash                     TEXT,
+    sell_order_id               TEXT,
+    error                       TEXT,
+    created_at                  INTEGER NOT NULL,
+    updated_at                  INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sells_nullifier ON sells (nullifier);
+CREATE INDEX IF NOT EXISTS sells_request_id ON sells (request_id);
+CREATE TABLE IF NOT EXISTS sell_steps (
+    sell_id INTEGER NOT NULL REFERENCES sells (id),
+    status  TEXT NOT NULL,
+    detail  TEXT,
+    at      INTEGER NOT NULL
+);
+";
+
+/// CLI arguments of the local store.
+#[derive(clap::Args, Debug)]
+pub struct StoreArgs {
+    /// Path to the SQLite database of the local state of the app (Created if it does not exist yet).
+    #[clap(long, env, default_value = "energy_trading.db")]
+    pub store_file: PathBuf,
+}
+
+impl StoreArgs {
+    /// Open the store.
+    pub fn open(&self) -> Result<Store> {
+        Store::open(&self.store_file)
+    }
+}
+
+/// A step of the lifecycle of a sell, in the order in which they are reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SellStatus {
+    /// The input has been executed (= dry run), and its proof is being generated.
+    Proving,
+    /// The proof request has been submitted to the Boundless market, and is waiting to be fulfilled.
+    Requested,
+    /// The proof has been generated (i.e. the seal is stored).
+    Proven,
+    /// The createSellOrder() tx has been broadcast.
+    Submitted,
+    /// The sell order has been created in the EnergyAggregator contract.
+    Created,
+    /// The sell failed (e.g. the proof request expired, or the tx was reverted).
+    Failed,
+}
+
+impl SellStatus {
+    /// The status as stored in the store.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SellStatus::Proving => "proving",
+            SellStatus::Requested => "requested",
+            SellStatus::Proven => "proven",
+            SellStatus::Submitted => "submitted",
+            SellStatus::Created => "created",
+            SellStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for SellStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for SellStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "proving" => SellStatus::Proving,
+            "requested" => SellStatus::Requested,
+            "proven" => SellStatus::Proven,
+            "submitted" => SellStatus::Submitted,
+            "created" => SellStatus::Created,
+            "failed" => SellStatus::Failed,
+            _ => bail!("unknown sell status {:?} in the store", s),
+        })
+    }
+}
+
+/// A new sell to be recorded, once its input has been executed.
+#[derive(Debug)]
+pub struct NewSell<'a> {
+    pub nullifier: B256,
+    pub monitored_merkle_root: B256,
+    pub amount_of_energy_to_be_sold: u64,
+    pub window_start: u64,
+    pub window_end: u64,
+    /// The proof backend (See: the `--prover` flag).
+    pub prover: &'a str,
+    /// The input of the smart-meter guest program (JSON of the SmartMeterInput).
+    pub input: &'a str,
+    /// The journal of the dry run, to which the proof must commit.
+    pub journal: &'a [u8],
+}
+
+/// A sell recorded in the store.
+#[derive(Clone, Debug)]
+pub struct SellRecord {
+    pub id: i64,
+    pub nullifier: B256,
+    pub monitored_merkle_root: B256,
+    pub amount_of_energy_to_be_sold: u64,
+    pub window_start: u64,
+    pub window_end: u64,
+    pub prover: String,
+    pub input: String,
+    pub journal: Vec<u8>,
+    pub status: SellStatus,
+    /// The ID of the proof request in the Boundless market (Only with --prover boundless).
+    pub request_id: Option<U256>,
+    /// The time when the proof request expires (UTC timestamp).
+    pub expires_at: Option<u64>,
+    pub seal: Option<Bytes>,
+    pub tx_hash: Option<B256>,
+    pub sell_order_id: Option<U256>,
+    /// The reason why the sell failed.
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl SellRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            nullifier: parse_column(row, "nullifier")?,
+            monitored_merkle_root: parse_column(row, "monitored_merkle_root")?,
+            amount_of_energy_to_be_sold: row.get("amount_of_energy_to_be_sold")?,
+            window_start: row.get("window_start")?,
+            window_end: row.get("window_end")?,
+            prover: row.get("prover")?,
+            input: row.get("input")?,
+            journal: row.get("journal")?,
+            status: parse_column(row, "status")?,
+            request_id: parse_optional_column(row, "request_id")?,
+            expires_at: row.get("expires_at")?,
+            seal: row.get::<_, Option<Vec<u8>>>("seal")?.map(Bytes::from),
+            tx_hash: parse_optional_column(row, "tx_hash")?,
+            sell_order_id: parse_optional_column(row, "sell_order_id")?,
+            error: row.get("error")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// The local store of the app.
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    /// Open the store (Created with its schema if it does not exist yet).
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path).with_context(|| format!("failed to open the store {}", path.display()))?;
+        Self::with_connection(connection)
+    }
+
+    /// The store on the connection (e.g. of an in-memory database), whose schema is created if it does not exist yet.
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA).context("failed to create the schema of the store")?;
+        Ok(Self { connection })
+    }
+
+    /// Record a new sell, and return its ID in the store.
+    pub fn insert_sell(&self, sell: &NewSell) -> Result<i64> {
+        let now = now()?;
+        let transaction = self.transaction()?;
+        self.connection
+            .execute(
+                "INSERT INTO sells (nullifier, monitored_merkle_root, amount_of_energy_to_be_sold, window_start, window_end,
+                                    prover, input, journal, status, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+                params![
+                    sell.nullifier.to_string(),
+                    sell.monitored_merkle_root.to_string(),
+                    sell.amount_of_energy_to_be_sold,
+                    sell.window_start,
+                    sell.window_end,
+                    sell.prover,
+                    sell.input,
+                    sell.journal,
+                    SellStatus::Proving.as_str(),
+                    now
+                ],
+            )
+            .context("failed to record the sell in the store")?;
+        let id = self.connection.last_insert_rowid();
+        self.log_step(id, SellStatus::Proving, None)?;
+        transaction.commit().context("failed to commit the sell to the store")?;
+        Ok(id)
+    }
+
+    /// Record the proof request of the sell, submitted to the Boundless market.
+    pub fn set_requested(&self, id: i64, request_id: U256, expires_at: u64) -> Result<()> {
+        let detail = format!("request 0x{:x} expires at {}", request_id, expires_at);
+        self.update(id, SellStatus::Requested, Some(&detail), "request_id = ?3, expires_at = ?4", params![id, now()?, format!("{:#x}", request_id), expires_at])
+    }
+
+    /// Record the seal of the proof of the sell.
+    pub fn set_proven(&self, id: i64, seal: &Bytes) -> Result<()> {
+        self.update(id, SellStatus::Proven, None, "seal = ?3", params![id, now()?, seal.to_vec()])
+    }
+
+    /// Record the createSellOrder() tx of the sell.
+    pub fn set_submitted(&self, id: i64, tx_hash: B256) -> Result<()> {
+        self.update(id, SellStatus::Submitted, Some(&format!("tx {}", tx_hash)), "tx_hash = ?3", params![id, now()?, tx_hash.to_string()])
+    }
+
+    /// Record the sell order created by the sell.
+    pub fn set_created(&self, id: i64, sell_order_id: U256) -> Result<()> {
+        self.update(id, SellStatus::Created, Some(&format!("sell order {}", sell_order_id)), "sell_order_id = ?3", params![id, now()?, sell_order_id.to_string()])
+    }
+
+    /// Record that the sell failed, with the reason.
+    pub fn set_failed(&self, id: i64, error: &str) -> Result<()> {
+        self.update(id, SellStatus::Failed, Some(error), "error = ?3", params![id, now()?, error])
+    }
+
+    /// Record the sell as failed if the result of its step is an error, which is returned as it is.
+    pub fn record_failure<T>(&self, id: i64, result: Result<T>) -> Result<T> {
+        if let Err(err) = &result {
+            self.set_failed(id, &format!("{:#}", err))?;
+        }
+        result
+    }
+
+    /// Get the sell of the ID in the store.
+    pub fn get_sell(&self, id: i64) -> Result<Option<SellRecord>> {
+        self.query_sell("SELECT * FROM sells WHERE id = ?1", params![id])
+    }
+
+    /// Get the latest sell of the nullifier, if it has already been submitted by this app.
+    pub fn find_sell_by_nullifier(&self, nullifier: B256) -> Result<Option<SellRecord>> {
+        self.query_sell("SELECT * FROM sells WHERE nullifier = ?1 ORDER BY id DESC LIMIT 1", params![nullifier.to_string()])
+    }
+
+    /// Get the sell of the proof request in the Boundless market.
+    pub fn find_sell_by_request_id(&self, request_id: U256) -> Result<Option<SellRecord>> {
+        self.query_sell("SELECT * FROM sells WHERE request_id = ?1 ORDER BY id DESC LIMIT 1", params![format!("{:#x}", request_id)])
+    }
+
+    /// List the sells (oldest first), optionally only the ones of the monitored merkle root.
+    pub fn list_sells(&self, monitored_merkle_root: Option<B256>) -> Result<Vec<SellRecord>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT * FROM sells WHERE ?1 IS NULL OR monitored_merkle_root = ?1 ORDER BY id")
+            .context("failed to query the sells in the store")?;
+        let sells = statement
+            .query_map(params![monitored_merkle_root.map(|root| root.to_string())], SellRecord::from_row)
+            .context("failed to query the sells in the store")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("failed to read the sells in the store")?;
+        Ok(sells)
+    }
+
+    /// List the steps of the sell (oldest first), as `(status, detail, at)`.
+    pub fn list_steps(&self, id: i64) -> Result<Vec<(SellStatus, Option<String>, u64)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT status, detail, at FROM sell_steps WHERE sell_id = ?1 ORDER BY rowid")
+            .context("failed to query the steps of the sell in the store")?;
+        let steps = statement
+            .query_map(params![id], |row| Ok((parse_column(row, "status")?, row.get("detail")?, row.get("at")?)))
+            .context("failed to query the steps of the sell in the store")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("failed to read the steps of the sell in the store")?;
+        Ok(steps)
+    }
+
+    /// The energy amount sold by the sell orders created by this app from the monitored merkle root (Unit: kWh).
+    pub fn sold(&self, monitored_merkle_root: B256) -> Result<u64> {
+        self.connection
+            .query_row(
+                "SELECT COALESCE(SUM(amount_of_energy_to_be_sold), 0) FROM sells WHERE monitored_merkle_root = ?1 AND status = ?2",
+                params![monitored_merkle_root.to_string(), SellStatus::Created.as_str()],
+                |row| row.get(0),
+            )
+            .context("failed to query the sold energy in the store")
+    }
+
+    /// Update the status (and the given columns) of the sell, and log the step with its detail in the same transaction,
+    /// so that the status and the steps of a sell never diverge. The `?1` and the `?2` of the params are the ID and the
+    /// update time, and the columns are set from `?3`.
+    fn update(&self, id: i64, status: SellStatus, detail: Option<&str>, columns: &str, params: &[&dyn rusqlite::ToSql]) -> Result<()> {
+        let transaction = self.transaction()?;
+        let updated = self
+            .connection
+            .execute(&format!("UPDATE sells SET status = '{}', updated_at = ?2, {} WHERE id = ?1", status, columns), params)
+            .with_context(|| format!("failed to update the sell {} in the store", id))?;
+        if updated == 0 {
+            bail!("the sell {} is not in the store", id);
+        }
+        self.log_step(id, status, detail)?;
+        transaction.commit().with_context(|| format!("failed to commit the step of the sell {} to the store", id))
+    }
+
+    /// Begin a transaction, which is rolled back unless it is committed.
+    /// (NOTE: The statements are executed on the connection itself, which is owned by the store)
+    fn transaction(&self) -> Result<Transaction<'_>> {
+        self.connection.unchecked_transaction().context("failed to begin a transaction of the store")
+    }
+
+    fn log_step(&self, id: i64, status: SellStatus, detail: Option<&str>) -> Result<()> {
+        self.connection
+            .execute("INSERT INTO sell_steps (sell_id, status, detail, at) VALUES (?1, ?2, ?3, ?4)", params![id, status.as_str(), detail, now()?])
+            .with_context(|| format!("failed to record the step of the sell {} in the store", id))?;
+        Ok(())
+    }
+
+    fn query_sell(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> Result<Option<SellRecord>> {
+        self.connection.query_row(sql, params, SellRecord::from_row).optional().context("failed to query the sell in the store")
+    }
+}
+
+/// Records the proof request of a sell in the store, as soon as it is submitted to the Boundless market (i.e. before
+/// waiting for it to be fulfilled), so that it can be resumed if the app dies while waiting.
+pub struct SellRecorder<'a> {
+    pub store: &'a Store,
+    pub id: i64,
+}
+
+impl ProofObserver for SellRecorder<'_> {
+    fn on_request_submitted(&mut self, request_id: U256, expires_at: u64) -> Result<()> {
+        self.store.set_requested(self.id, request_id, expires_at)
+    }
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH).context("the system time is before the UNIX epoch")?.as_secs())
+}
+
+/// Parse a column stored as text (e.g. a hash or a status).
+fn parse_column<T: FromStr>(row: &Row, column: &str) -> rusqlite::Result<T>
+where
+    T::Err: fmt::Display,
+{
+    let value: String = row.get(column)?;
+    value.parse().map_err(|err: T::Err| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, format!("invalid {}: {}", column, err).into())
+    })
+}
+
+fn parse_optional_column<T: FromStr>(row: &Row, column: &str) -> rusqlite::Result<Option<T>>
+where
+    T::Err: fmt::Display,
+{
+    match row.get::<_, Option<String>>(column)? {
+        Some(_) => parse_column(row, column).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Store {
+        Store::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn new_sell(nullifier: B256, monitored_merkle_root: B256, amount_of_energy_to_be_sold: u64) -> NewSell<'static> {
+        NewSell {
+            nullifier,
+            monitored_merkle_root,
+            amount_of_energy_to_be_sold,
+            window_start: 1740638030,
+            window_end: 1740641630,
+            prover: "boundless",
+            input: "{}",
+            journal: &[1, 2, 3],
+        }
+    }
+
+    fn statuses(store: &Store, id: i64) -> Vec<SellStatus> {
+        store.list_steps(id).unwrap().into_iter().map(|(status, _, _)| status).collect()
+    }
+
+    #[test]
+    fn records_a_new_sell() {
+        let store = store();
+        let id = store.insert_sell(&new_sell(B256::repeat_byte(1), B256::repeat_byte(2), 100)).unwrap();
+        let sell = store.get_sell(id).unwrap().unwrap();
+        assert_eq!(sell.status, SellStatus::Proving);
+        assert_eq!((sell.nullifier, sell.monitored_merkle_root), (B256::repeat_byte(1), B256::repeat_byte(2)));
+        assert_eq!((sell.amount_of_energy_to_be_sold, sell.window_start, sell.window_end), (100, 1740638030, 1740641630));
+        assert_eq!((sell.prover.as_str(), sell.input.as_str(), sell.journal.as_slice()), ("boundless", "{}", [1, 2, 3].as_slice()));
+        assert!(sell.request_id.is_none() && sell.seal.is_none() && sell.sell_order_id.is_none() && sell.error.is_none());
+        assert_eq!(statuses(&store, id), vec![SellStatus::Proving]);
+        assert!(store.get_sell(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn records_each_step_of_the_lifecycle() {
+        let store = store();
+        let id = store.insert_sell(&new_sell(B256::repeat_byte(1), B256::repeat_byte(2), 100)).unwrap();
+        let request_id = U256::from(0xabcdef_u64) << 100;
+        SellRecorder { store: &store, id }.on_request_submitted(request_id, 1740645230).unwrap();
+        store.set_proven(id, &Bytes::from(vec![9, 9])).unwrap();
+        store.set_submitted(id, B256::repeat_byte(3)).unwrap();
+        store.set_created(id, U256::from(7)).unwrap();
+
+        let sell = store.find_sell_by_request_id(request_id).unwrap().unwrap();
+        assert_eq!(sell.id, id);
+        assert_eq!(sell.status, SellStatus::Created);
+        assert_eq!(sell.expires_at, Some(1740645230));
+        assert_eq!(sell.seal, Some(Bytes::from(vec![9, 9])));
+        assert_eq!(sell.tx_hash, Some(B256::repeat_byte(3)));
+        assert_eq!(sell.sell_order_id, Some(U256::from(7)));
+        assert_eq!(
+            statuses(&store, id),
+            vec![SellStatus::Proving, SellStatus::Requested, SellStatus::Proven, SellStatus::Submitted, SellStatus::Created]
+        );
+        let steps = store.list_steps(id).unwrap();
+        assert_eq!(steps[1].1.as_deref(), Some(format!("request 0x{:x} expires at 1740645230", request_id).as_str()));
+        assert!(store.find_sell_by_request_id(U256::from(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn records_the_failure_of_a_step() {
+        let store = store();
+        let id = store.insert_sell(&new_sell(B256::repeat_byte(1), B256::repeat_byte(2), 100)).unwrap();
+        let result = store.record_failure::<()>(id, Err(anyhow::anyhow!("the request expired").context("failed to wait")));
+        assert!(result.is_err());
+        let sell = store.get_sell(id).unwrap().unwrap();
+        assert_eq!(sell.status, SellStatus::Failed);
+        assert_eq!(sell.error.as_deref(), Some("failed to wait: the request expired"));
+        assert_eq!(statuses(&store, id), vec![SellStatus::Proving, SellStatus::Failed]);
+
+        assert_eq!(store.record_failure(id, Ok(1)).unwrap(), 1);
+        assert_eq!(statuses(&store, id).len(), 2);
+    }
+
+    #[test]
+    fn rejects_the_step_of_an_unknown_sell() {
+        let store = store();
+        assert!(store.set_proven(1, &Bytes::from(vec![9, 9])).is_err());
+        assert!(store.list_steps(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rolls_back_the_status_if_its_step_is_not_logged() {
+        let store = store();
+        let id = store.insert_sell(&new_sell(B256::repeat_byte(1), B256::repeat_byte(2), 100)).unwrap();
+        // @dev - The step log can not be written, so the status update must not be committed either.
+        store.connection.execute_batch("DROP TABLE sell_steps").unwrap();
+        assert!(store.set_proven(id, &Bytes::from(vec![9, 9])).is_err());
+        let sell = store.get_sell(id).unwrap().unwrap();
+        assert_eq!(sell.status, SellStatus::Proving);
+        assert!(sell.seal.is_none());
+        assert!(store.insert_sell(&new_sell(B256::repeat_byte(3), B256::repeat_byte(2), 50)).is_err());
+        assert_eq!(store.list_sells(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn finds_the_latest_sell_of_a_nullifier() {
+        let store = store();
+        let nullifier = B256::repeat_byte(1);
+        let failed = store.insert_sell(&new_sell(nullifier, B256::repeat_byte(2), 100)).unwrap();
+        store.set_failed(failed, "the request expired").unwrap();
+        let resubmitted = store.insert_sell(&new_sell(nullifier, B256::repeat_byte(2), 100)).unwrap();
+        assert_eq!(store.find_sell_by_nullifier(nullifier).unwrap().unwrap().id, resubmitted);
+        assert!(store.find_sell_by_nullifier(B256::repeat_byte(9)).unwrap().is_none());
+    }
+
+    #[test]
+    fn lists_the_history_and_the_sold_energy_of_a_monitored_merkle_root() {
+        let store = store();
+        let (root, other_root) = (B256::repeat_byte(2), B256::repeat_byte(3));
+        let created = store.insert_sell(&new_sell(B256::repeat_byte(1), root, 100)).unwrap();
+        store.set_created(created, U256::from(1)).unwrap();
+        let failed = store.insert_sell(&new_sell(B256::repeat_byte(4), root, 30)).unwrap();
+        store.set_failed(failed, "the tx was reverted").unwrap();
+        let other = store.insert_sell(&new_sell(B256::repeat_byte(5), other_root, 70)).unwrap();
+        store.set_created(other, U256::from(2)).unwrap();
+
+        let ids = |sells: Vec<SellRecord>| sells.into_iter().map(|sell| sell.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.list_sells(None).unwrap()), vec![created, failed, other]);
+        assert_eq!(ids(store.list_sells(Some(root)).unwrap()), vec![created, failed]);
+        // @dev - Only the sell orders which have been created are sold.
+        assert_eq!(store.sold(root).unwrap(), 100);
+        assert_eq!(store.sold(other_root).unwrap(), 70);
+        assert_eq!(store.sold(B256::repeat_byte(9)).unwrap(), 0);
+    }
+}
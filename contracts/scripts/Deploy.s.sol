@@ -16,6 +16,8 @@ pragma solidity ^0.8.20;
 
 import { Script, console2 } from "forge-std/Script.sol";
 import { IRiscZeroVerifier } from "risc0/IRiscZeroVerifier.sol";
+import { ControlID, RiscZeroGroth16Verifier } from "risc0/groth16/RiscZeroGroth16Verifier.sol";
+import { RiscZeroMockVerifier } from "risc0/test/RiscZeroMockVerifier.sol";
 import { EnergyAggregator } from "../src/EnergyAggregator.sol";
 //import { EvenNumber } from "../src/EvenNumber.sol";
 
@@ -23,11 +25,23 @@ contract Deploy is Script {
     function run() external {
         // load ENV variables first
         uint256 key = vm.envUint("WALLET_PRIVATE_KEY");
-        address verifierAddress = vm.envAddress("VERIFIER_ROUTER_ADDRESS"); /// @dev - RISC Zero Verifier Router contract address on Ethereum Sepolia testnet. 
+        address verifierAddress = vm.envOr("VERIFIER_ROUTER_ADDRESS", address(0)); /// @dev - RISC Zero Verifier Router contract address on Ethereum Sepolia testnet. 
+        uint256 maxMonitoredTimeAge = vm.envOr("MAX_MONITORED_TIME_AGE", uint256(3600)); /// @dev - 1 hour by default (Must match the app's --max-monitored-time-age)
+        uint256 settlementDeadline = vm.envOr("SETTLEMENT_DEADLINE", uint256(7 days)); /// @dev - 7 days by default (After that, the buyer of an unsettled sell order can be refunded)
         vm.startBroadcast(key);
 
+        /// @dev - On a local chain (e.g. anvil), where no RISC Zero Verifier Router is deployed, a verifier is deployed together.
+        ///        With RISC0_DEV_MODE, the mock verifier accepts the seals of the app's mock prover (--prover mock).
+        if (verifierAddress == address(0)) {
+            if (vm.envOr("RISC0_DEV_MODE", false)) {
+                verifierAddress = address(new RiscZeroMockVerifier(bytes4(0xFFFFFFFF)));
+            } else {
+                verifierAddress = address(new RiscZeroGroth16Verifier(ControlID.CONTROL_ROOT, ControlID.BN254_CONTROL_ID));
+            }
+            console2.log("Deployed RiscZeroVerifier to", verifierAddress);
+        }
         IRiscZeroVerifier verifier = IRiscZeroVerifier(verifierAddress);
-        EnergyAggregator energyAggregator = new EnergyAggregator(verifier);
+        EnergyAggregator energyAggregator = new EnergyAggregator(verifier, maxMonitoredTimeAge, settlementDeadline);
         address energyAggregatorAddress = address(energyAggregator);
         console2.log("Deployed EnergyAggregator to", energyAggregatorAddress);
 
@@ -4,6 +4,7 @@ import { IRiscZeroVerifier } from "risc0/IRiscZeroVerifier.sol";
 import { ImageID } from "./ImageID.sol"; // auto-generated contract after running `cargo build`.
 
 import { DataTypes } from "./utils/DataTypes.sol";
+import { IEnergyAggregator } from "./IEnergyAggregator.sol";
 
 /// @title the Energy Aggregator contract
 contract EnergyAggregator {
@@ -17,6 +18,31 @@ contract EnergyAggregator {
     ///         (in this case, checking if a number is even) are considered valid.
     bytes32 public constant imageId = ImageID.SMART_METER_ID;
 
+    /// @notice Image ID of the aggregator guest program, which folds many smart-meter receipts (of a microgrid) into one.
+    bytes32 public constant aggregatorImageId = ImageID.AGGREGATOR_ID;
+
+    /// @notice Image ID of the consumption guest program, which proves that a buyer has the demand to consume the energy to be bought.
+    bytes32 public constant consumptionImageId = ImageID.CONSUMPTION_ID;
+
+    /// @notice Image ID of the settlement guest program, which proves how much of the traded energy of a matched sell order was delivered.
+    bytes32 public constant settlementImageId = ImageID.SETTLEMENT_ID;
+
+    /// @notice Maximum age (in seconds) of a meter reading, which is committed to the journal by the guest program.
+    ///         A proof is also rejected if its currentTime is older than this window at the block time.
+    uint256 public immutable maxMonitoredTimeAge;
+
+    /// @notice Time (in seconds) after a sell order is matched, within which it must be settled by the seller.
+    ///         After that, the buyer can get the escrowed payment refunded (See: refundExpiredOrder()).
+    uint256 public immutable settlementDeadline;
+
+    /// @notice The deployer, who can register smart meters.
+    address public immutable owner;
+
+    /// @notice Owners of the registered smart meters, by the hash (sha256) of the SEC1-encoded public key of the smart meter.
+    ///         An order is only accepted if its meter reading is signed by a registered smart meter, and is submitted by the owner of it
+    ///         (so that the signed meter readings of a smart meter can not be proven and sold from another address).
+    mapping(bytes32 => address) public meterOwners;
+
     uint256 public energyPricePerKwh = 1 * 1e10; /// @dev - The energy price per 1. At this point, we set 0.00000001 ETH/Kwh as a fixed-price. (However, in the future, this should be set by a Energy Producer.)
 
     uint256 public sellOrderId;
@@ -24,39 +50,77 @@ contract EnergyAggregator {
     mapping(uint256 => uint256) public energyAmountToBeSolds;  /// @dev - sellOrderId -> energyAmountToBeSold / This is the energy amount that a Producer want to sell (NOTE: This is "not" all amount of energy available in the Producer, which is measured by the Producer's smart meter).
     mapping(uint256 => address) public energySellers;          /// @dev - sellOrderId -> energySeller address
     mapping(address => uint256) public buyerBalances;          /// @dev - buyer address -> buyer's NativeETH balance in this contract.
+    mapping(uint256 => bytes32) public sellOrderMeterPublicKeyHashes; /// @dev - sellOrderId -> Hash of the public key of the smart meter, which must also measure the delivery of the sell order.
+    mapping(uint256 => DataTypes.Escrow) public escrows;       /// @dev - sellOrderId -> Escrow of the payment of the matched sell order, which is released when the delivery is settled.
+    mapping(uint256 => mapping(bytes32 => bool)) public aggregateMeterPublicKeyHashes; /// @dev - sellOrderId -> Hash of the public key of a smart meter -> Whether the smart meter is aggregated into the (aggregate) sell order, which measures a part of its delivery.
 
     mapping(bytes => mapping(bytes32 => bool)) public monitoredNullifiers; /// @dev - To prevent from a proof double-spending attack.
+    mapping(bytes32 => bool) public usedNullifiers;                        /// @dev - monitoredNullifier -> Whether the nullifier has already been used by a sell order (whichever seal it was submitted with).
+    mapping(bytes32 => bool) public usedConsumptionMerkleRoots;            /// @dev - monitoredMerkleRoot -> Whether the consumption baseline (meter readings) of a buyer has already been used for a buy order.
+    mapping(bytes32 => bytes32) public soldCommitments;                    /// @dev - monitoredMerkleRoot -> Latest commitment to the energy amount sold so far from the meter readings (bytes32(0) if nothing is sold yet).
+    mapping(bytes32 => bool) public usedDeliveries;                        /// @dev - keccak256(meterPublicKeyHash, deliveryMerkleRoot, deliveryWindowStart, deliveryWindowEnd) -> Whether the delivery readings have already settled a sell order.
+
+    /// @notice Emitted when a sell order is created, so that the seller can find the ID of the new sell order from the tx receipt.
+    event SellOrderCreated(uint256 indexed sellOrderId, address indexed energySeller, uint256 energyAmountToBeSold, bytes32 monitoredMerkleRoot, bytes32 monitoredNullifier);
+
+    /// @notice Emitted when a buy order is matched with a sell order, so that the buyer can find the matched sell order from the tx receipt.
+    event BuyOrderMatched(uint256 indexed sellOrderId, address indexed energyBuyer, uint256 energyAmountToBeBought, uint256 paymentAmount);
 
     /// @notice Initialize the contract, binding it to a specified RISC Zero verifier.
-    constructor(IRiscZeroVerifier _verifier) {
+    constructor(IRiscZeroVerifier _verifier, uint256 _maxMonitoredTimeAge, uint256 _settlementDeadline) {
         verifier = _verifier;
+        maxMonitoredTimeAge = _maxMonitoredTimeAge;
+        settlementDeadline = _settlementDeadline;
+        owner = msg.sender;
         //energyAmountToBeSold = 0;
     }
 
+    /// @notice - Register a smart meter by the hash of its public key, together with its owner (e.g. the prosumer, or the operator of a microgrid).
+    function registerMeter(bytes32 meterPublicKeyHash, address meterOwner) public {
+        require(msg.sender == owner, "Only the owner can register a smart meter");
+        require(meterOwner != address(0), "The owner of the smart meter must not be the zero address");
+        meterOwners[meterPublicKeyHash] = meterOwner;
+    }
+
     /// @notice - Create an energy sell order with a given energy amount to be sold.
     /// @notice - Store a given publicInputs into the contract. Requires a RISC Zero proof that the can prove whether or not an given energyAmountToBeSold exceed the all amount of energy avaiable in a producer's smart meter.
     function createSellOrder( /// [TODO]: Rename this function name with "createSellOrderOfEnergy()"
         uint256 _energyAmountToBeSold, 
-        uint256 _monitoredTime,
+        uint256 _windowStart,           /// @dev - Start of the window in which the energy was produced.
+        uint256 _windowEnd,             /// @dev - End of the window in which the energy was produced.
+        uint256 _currentTime,           /// @dev - The time when the freshness of the window was checked in the guest program.
         bytes32 _monitoredMerkleRoot,
         //uint256 _monitored_hash_path,
         bytes32 _monitoredNullifier,    /// @dev - Nullifier (Hash) is a unique identifier for a proof, which is used to prevent double-spending attacks.
+        bytes32 _meterPublicKeyHash,    /// @dev - Hash of the public key of the smart meter, which signed the meter readings.
+        bytes32 _soldCommitment,        /// @dev - Updated commitment to the energy amount sold so far (including this sell order) from the meter readings.
         bytes calldata seal
     ) public { /// @dev - Submitted by a Producer.
         // @dev - Validation in the smart contract level
         require(_energyAmountToBeSold > 0, "Energy amount to be sold must be greater than 0");
+        require(_currentTime <= block.timestamp, "Current time must not be in the future");
+        require(block.timestamp - _currentTime <= maxMonitoredTimeAge, "Meter reading is too old to be sold");
+        require(meterOwners[_meterPublicKeyHash] != address(0), "Meter reading is not signed by a registered smart meter");
+        require(meterOwners[_meterPublicKeyHash] == msg.sender, "The smart meter is not owned by the caller");
 
         // Construct the expected journal data. Verify will fail if journal does not match.
-        bytes memory journal = abi.encode(_energyAmountToBeSold, _monitoredTime, _monitoredMerkleRoot, _monitoredNullifier);
+        // (NOTE: The maxMonitoredTimeAge of this contract is used, so that a proof with a looser freshness rule is rejected)
+        // (NOTE: The msg.sender and block.chainid are used, so that a proof can not be front-run from another address or replayed on another chain)
+        // (NOTE: The latest sold commitment of the monitored merkle root is used, so that the proof must chain from all the previous sell orders of the same meter readings)
+        bytes memory journal = abi.encode(_energyAmountToBeSold, _windowStart, _windowEnd, _currentTime, maxMonitoredTimeAge, _monitoredMerkleRoot, _monitoredNullifier, _meterPublicKeyHash, soldCommitments[_monitoredMerkleRoot], _soldCommitment, msg.sender, block.chainid);
         verifier.verify(seal, imageId, sha256(journal)); /// @dev - "journal" is an "encoded-publicInputs" in bytes type data.
 
+        /// @dev - The next sell order of the same meter readings must chain from the updated commitment.
+        soldCommitments[_monitoredMerkleRoot] = _soldCommitment;
+
         /// @dev - Store the following sell order info into "on-chain".
         /// @dev - (NOTE: sellOrderId is counted from 1)
         sellOrderId++;
         DataTypes.SellOrder memory sellOrder = DataTypes.SellOrder({
             energyAmountToBeSold: _energyAmountToBeSold,
             energySeller: msg.sender,
-            monitoredTime: _monitoredTime,
+            windowStart: _windowStart,
+            windowEnd: _windowEnd,
             monitoredMerkleRoot: _monitoredMerkleRoot,
             monitoredNullifier: _monitoredNullifier,
             orderMatched: false,
@@ -65,10 +129,74 @@ contract EnergyAggregator {
             journal: sha256(journal)
         });
         sellOrders[sellOrderId] = sellOrder;
+        sellOrderMeterPublicKeyHashes[sellOrderId] = _meterPublicKeyHash;
 
         /// @dev - To prevent from a proof double-spending attack.
         require(monitoredNullifiers[seal][_monitoredNullifier] == false, "The proof has already been used");
         monitoredNullifiers[seal][_monitoredNullifier] = true;
+        require(usedNullifiers[_monitoredNullifier] == false, "The nullifier has already been used");
+        usedNullifiers[_monitoredNullifier] = true;
+
+        emit SellOrderCreated(sellOrderId, msg.sender, _energyAmountToBeSold, _monitoredMerkleRoot, _monitoredNullifier);
+    }
+
+    /// @notice - Create a single energy sell order of a whole microgrid (e.g. a community solar), with the sum of the energy amounts
+    ///           to be sold of its smart meters. Requires a RISC Zero proof of the aggregator guest program, which verifies the
+    ///           smart-meter receipts of all the smart meters.
+    function createAggregateSellOrder(
+        IEnergyAggregator.AggregateJournal calldata _aggregateJournal,
+        bytes calldata seal
+    ) public { /// @dev - Submitted by an operator of a microgrid.
+        // @dev - Validation in the smart contract level
+        require(_aggregateJournal.smartMeterImageId == imageId, "The aggregated receipts must be of the smart-meter guest program");
+        require(_aggregateJournal.energyAmountToBeSold > 0, "Energy amount to be sold must be greater than 0");
+        require(_aggregateJournal.currentTime <= block.timestamp, "Current time must not be in the future");
+        require(block.timestamp - _aggregateJournal.currentTime <= maxMonitoredTimeAge, "Meter reading is too old to be sold");
+        require(_aggregateJournal.maxMonitoredTimeAge == maxMonitoredTimeAge, "The max monitored time age does not match");
+        require(_aggregateJournal.seller == msg.sender, "The aggregated receipts must be bound to the caller");
+        require(_aggregateJournal.chainId == block.chainid, "The aggregated receipts must be bound to this chain");
+
+        // Verify the proof of the aggregator guest program. Verify will fail if journal does not match.
+        bytes32 journalDigest = sha256(abi.encode(_aggregateJournal));
+        verifier.verify(seal, aggregatorImageId, journalDigest);
+
+        /// @dev - Each smart meter must be registered to the caller (= operator of the microgrid), and each sell order must chain from the latest sold commitment of its meter readings.
+        for (uint256 i = 0; i < _aggregateJournal.monitoredMerkleRoots.length; i++) {
+            bytes32 monitoredMerkleRoot = _aggregateJournal.monitoredMerkleRoots[i];
+            require(meterOwners[_aggregateJournal.meterPublicKeyHashes[i]] != address(0), "Meter reading is not signed by a registered smart meter");
+            require(meterOwners[_aggregateJournal.meterPublicKeyHashes[i]] == msg.sender, "The smart meter is not owned by the caller");
+            require(soldCommitments[monitoredMerkleRoot] == _aggregateJournal.previousSoldCommitments[i], "The sell order does not chain from the latest sold commitment");
+            soldCommitments[monitoredMerkleRoot] = _aggregateJournal.soldCommitments[i];
+
+            /// @dev - To prevent from a proof double-spending attack.
+            require(monitoredNullifiers[seal][_aggregateJournal.monitoredNullifiers[i]] == false, "The proof has already been used");
+            monitoredNullifiers[seal][_aggregateJournal.monitoredNullifiers[i]] = true;
+            require(usedNullifiers[_aggregateJournal.monitoredNullifiers[i]] == false, "The nullifier has already been used");
+            usedNullifiers[_aggregateJournal.monitoredNullifiers[i]] = true;
+        }
+
+        /// @dev - Store the aggregated sell order info into "on-chain".
+        /// @dev - (NOTE: The monitoredMerkleRoot and monitoredNullifier are the hashes of those of all the smart meters)
+        /// @dev - (NOTE: No single meter public key hash is stored. Instead, the delivery of the whole microgrid is settled by the smart meters
+        ///        aggregated into the sell order, each of which proves its own part of the delivery (See: settleAggregateSellOrder()))
+        sellOrderId++;
+        for (uint256 i = 0; i < _aggregateJournal.meterPublicKeyHashes.length; i++) {
+            aggregateMeterPublicKeyHashes[sellOrderId][_aggregateJournal.meterPublicKeyHashes[i]] = true;
+        }
+        sellOrders[sellOrderId] = DataTypes.SellOrder({
+            energyAmountToBeSold: _aggregateJournal.energyAmountToBeSold,
+            energySeller: msg.sender,
+            windowStart: _aggregateJournal.windowStart,
+            windowEnd: _aggregateJournal.windowEnd,
+            monitoredMerkleRoot: sha256(abi.encodePacked(_aggregateJournal.monitoredMerkleRoots)),
+            monitoredNullifier: sha256(abi.encodePacked(_aggregateJournal.monitoredNullifiers)),
+            orderMatched: false,
+            seal: seal,
+            imageId: aggregatorImageId,
+            journal: journalDigest
+        });
+
+        emit SellOrderCreated(sellOrderId, msg.sender, _aggregateJournal.energyAmountToBeSold, sellOrders[sellOrderId].monitoredMerkleRoot, sellOrders[sellOrderId].monitoredNullifier);
     }
 
     /// @notice - Get a energy sell order by a given sellOrderId.
@@ -77,38 +205,213 @@ contract EnergyAggregator {
     }  
 
     /// @notice - Create an energy buy order /w the energy amount that the buyer want to buy.
+    /// @notice - Requires a RISC Zero proof of the consumption guest program, which can prove whether or not the energy consumed by the buyer
+    ///           in the window (= consumption baseline) exceeds the energyAmountToBeBought, without revealing the load profile of the buyer.
     /// @param energyAmountToBeBought - The energy amount that the buyer want to buy (Unit: kwh).
-    function createBuyOrder(uint256 energyAmountToBeBought) public {
-        // [TODO]: Matching logic that the buy order can automatically match with the sell order, which was submitted /w proof via the submitEnergyAmountToBeSold() above.
+    function createBuyOrder(
+        uint256 energyAmountToBeBought,
+        uint256 _windowStart,           /// @dev - Start of the window in which the energy was consumed.
+        uint256 _windowEnd,             /// @dev - End of the window in which the energy was consumed.
+        uint256 _currentTime,           /// @dev - The time when the freshness of the window was checked in the guest program.
+        bytes32 _monitoredMerkleRoot,
+        bytes32 _meterPublicKeyHash,    /// @dev - Hash of the public key of the buyer's smart meter, which signed the meter readings.
+        bytes calldata seal
+    ) public { /// @dev - Submitted by a Consumer.
+        // @dev - Validation in the smart contract level
+        require(energyAmountToBeBought > 0, "Energy amount to be bought must be greater than 0");
+        require(_currentTime <= block.timestamp, "Current time must not be in the future");
+        require(block.timestamp - _currentTime <= maxMonitoredTimeAge, "Meter reading is too old to be used");
+        require(meterOwners[_meterPublicKeyHash] != address(0), "Meter reading is not signed by a registered smart meter");
+        require(meterOwners[_meterPublicKeyHash] == msg.sender, "The smart meter is not owned by the caller");
+        require(usedConsumptionMerkleRoots[_monitoredMerkleRoot] == false, "The consumption baseline has already been used");
+
+        // Construct the expected journal data. Verify will fail if journal does not match.
+        // (NOTE: The msg.sender and block.chainid are used, so that a proof can not be front-run from another address or replayed on another chain)
+        bytes memory journal = abi.encode(energyAmountToBeBought, _windowStart, _windowEnd, _currentTime, maxMonitoredTimeAge, _monitoredMerkleRoot, _meterPublicKeyHash, msg.sender, block.chainid);
+        verifier.verify(seal, consumptionImageId, sha256(journal));
+
         // [TODO]: Ideally, it should be matched with 2 items (= "Asking Price" and "Asking Amount")
-        _matchBuyOrderWithSellOrder(energyAmountToBeBought);
+        /// @dev - A buy order is not stored, so it must be matched with an open sell order right away (Otherwise, the whole tx is reverted).
+        uint256 matchedSellOrderId = _matchBuyOrderWithSellOrder(energyAmountToBeBought);
+        require(matchedSellOrderId != 0, "No open sell order matches the amount");
+
+        /// @dev - The same consumption baseline can not back another buy order (once it has been matched).
+        usedConsumptionMerkleRoots[_monitoredMerkleRoot] = true;
+
+        emit BuyOrderMatched(matchedSellOrderId, msg.sender, energyAmountToBeBought, escrows[matchedSellOrderId].paymentAmount);
     }
-    
-    function _matchBuyOrderWithSellOrder(uint256 energyAmountToBeBought) internal {
-        // [TODO]: Implement the logic that the buy order can automatically match with the sell order, which was submitted /w proof via the submitEnergyAmountToBeSold() above.
-        // [TODO]: Ideally, it should be matched with 2 items (= "Asking Price" and "Asking Amount")
+
+    /// @dev - Match a buy order with the first open sell order of the same amount, and escrow the payment of the buyer.
+    ///        Returns the ID of the matched sell order, or 0 if no open sell order matches the amount (NOTE: sellOrderId is counted from 1).
+    function _matchBuyOrderWithSellOrder(uint256 energyAmountToBeBought) internal returns (uint256) {
         for (uint256 i = 1; i <= sellOrderId; i++) {
+            DataTypes.SellOrder storage sellOrder = sellOrders[i];
+
+            /// @dev - Skip the sell orders which have already been matched (incl. the settled ones).
+            if (sellOrder.orderMatched) {
+                continue;
+            }
+
             // Matched -> Execute the transaction (i.e. Pay a seller-matched for buying the energy amount).
-            if (getSellOrder(i).energyAmountToBeSold == energyAmountToBeBought) {
-                address energySeller = getSellOrder(i).energySeller;
-                DataTypes.SellOrder storage sellOrder = sellOrders[i];
-                require(sellOrder.orderMatched == false, "The order has already been matched");
+            if (sellOrder.energyAmountToBeSold == energyAmountToBeBought) {
                 sellOrder.orderMatched = true;
 
-                /// @dev - Paid from a buyer to a seller.
+                /// @dev - Paid from a buyer into the escrow, which is released to the seller when the delivery is settled (See: settleSellOrder()).
                 require(buyerBalances[msg.sender] >= energyPricePerKwh * energyAmountToBeBought, "Insufficient balance to buy this amount of energy");
-                energySeller.call{ value: energyPricePerKwh * energyAmountToBeBought }("");
                 buyerBalances[msg.sender] -= energyPricePerKwh * energyAmountToBeBought;
+                escrows[i] = DataTypes.Escrow({
+                    energyBuyer: msg.sender,
+                    paymentAmount: energyPricePerKwh * energyAmountToBeBought,
+                    matchedAt: block.timestamp,
+                    settled: false
+                });
+
+                /// @dev - A buy order is matched with (and paid for) the first open sell order of the same amount only.
+                return i;
             }
         }
+        return 0;
+    }
+
+    /// @notice - Settle a matched sell order with the energy amount actually delivered (= exported by the seller's smart meter) after the trade.
+    /// @notice - Requires a RISC Zero proof of the settlement guest program, which proves the delivered amount (capped at the traded amount)
+    ///           and the shortfall from the meter readings of the delivery window. The escrow is released to the seller for the delivered amount,
+    ///           and the shortfall is refunded to the buyer's balance.
+    function settleSellOrder(
+        uint256 _sellOrderId,
+        uint256 _delivered,             /// @dev - The energy amount delivered in the delivery window (Unit: kwh).
+        uint256 _deliveryWindowStart,   /// @dev - Start of the window in which the traded energy was delivered.
+        uint256 _deliveryWindowEnd,     /// @dev - End of the window in which the traded energy was delivered.
+        bytes32 _monitoredMerkleRoot,
+        bytes32 _meterPublicKeyHash,    /// @dev - Hash of the public key of the seller's smart meter, which signed the meter readings.
+        bytes calldata seal
+    ) public { /// @dev - Submitted by the seller of the matched sell order.
+        DataTypes.SellOrder memory sellOrder = sellOrders[_sellOrderId];
+        DataTypes.Escrow storage escrow = escrows[_sellOrderId];
+
+        // @dev - Validation in the smart contract level
+        require(sellOrder.orderMatched, "The sell order has not been matched");
+        require(escrow.settled == false, "The sell order has already been settled");
+        require(sellOrder.energySeller == msg.sender, "Only the seller can settle the sell order");
+        require(sellOrderMeterPublicKeyHashes[_sellOrderId] != bytes32(0), "The aggregate sell order must be settled via settleAggregateSellOrder()");
+        require(sellOrderMeterPublicKeyHashes[_sellOrderId] == _meterPublicKeyHash, "The delivery must be measured by the smart meter of the sell order");
+        require(meterOwners[_meterPublicKeyHash] == msg.sender, "The smart meter is not owned by the caller");
+        require(block.timestamp <= escrow.matchedAt + settlementDeadline, "The settlement deadline has passed");
+        require(_deliveryWindowStart >= escrow.matchedAt, "The delivery window must start after the sell order was matched");
+        require(_deliveryWindowEnd <= block.timestamp, "The delivery window must not end in the future");
+        require(_delivered <= sellOrder.energyAmountToBeSold, "The delivered amount must not exceed the traded amount");
+
+        // Construct the expected journal data. Verify will fail if journal does not match.
+        // (NOTE: The traded amount of the sell order is used, so that the shortfall is computed against the actual trade)
+        uint256 shortfall = sellOrder.energyAmountToBeSold - _delivered;
+        bytes memory journal = abi.encode(_sellOrderId, sellOrder.energyAmountToBeSold, _delivered, shortfall, _deliveryWindowStart, _deliveryWindowEnd, _monitoredMerkleRoot, _meterPublicKeyHash, msg.sender, block.chainid);
+        verifier.verify(seal, settlementImageId, sha256(journal));
+
+        /// @dev - The same delivery readings can not settle another matched sell order.
+        bytes32 delivery = keccak256(abi.encode(_meterPublicKeyHash, _monitoredMerkleRoot, _deliveryWindowStart, _deliveryWindowEnd));
+        require(usedDeliveries[delivery] == false, "The delivery has already been used to settle a sell order");
+        usedDeliveries[delivery] = true;
+
+        _releaseEscrow(_sellOrderId, _delivered);
+    }
+
+    /// @notice - Settle a matched aggregate sell order (of a microgrid) with the energy amount actually delivered by its smart meters after the trade.
+    /// @notice - Requires a RISC Zero proof of the settlement guest program per smart meter, each of which proves the delivery of the smart meter
+    ///           (capped at the traded amount of the whole sell order). The delivered amount of the sell order is the sum of them (capped at the
+    ///           traded amount), and the escrow is released in the same way as the settleSellOrder().
+    /// @param _deliveries - The deliveries of the distinct smart meters aggregated into the sell order, sorted by the meterPublicKeyHash (ascending).
+    ///                      A smart meter without a delivery may be omitted (i.e. it delivered nothing).
+    function settleAggregateSellOrder(
+        uint256 _sellOrderId,
+        IEnergyAggregator.Delivery[] calldata _deliveries
+    ) public { /// @dev - Submitted by the operator of the microgrid (= seller of the aggregate sell order).
+        DataTypes.SellOrder memory sellOrder = sellOrders[_sellOrderId];
+        DataTypes.Escrow storage escrow = escrows[_sellOrderId];
+
+        // @dev - Validation in the smart contract level
+        require(sellOrder.orderMatched, "The sell order has not been matched");
+        require(escrow.settled == false, "The sell order has already been settled");
+        require(sellOrder.energySeller == msg.sender, "Only the seller can settle the sell order");
+        require(sellOrderMeterPublicKeyHashes[_sellOrderId] == bytes32(0), "The sell order of a single smart meter must be settled via settleSellOrder()");
+        require(block.timestamp <= escrow.matchedAt + settlementDeadline, "The settlement deadline has passed");
+        require(_deliveries.length > 0, "At least 1 delivery is required to settle the sell order");
+
+        uint256 delivered;
+        bytes32 previousMeterPublicKeyHash;
+        for (uint256 i = 0; i < _deliveries.length; i++) {
+            /// @dev - Each smart meter can only deliver once, so that the same exported energy is not counted twice.
+            require(i == 0 || _deliveries[i].meterPublicKeyHash > previousMeterPublicKeyHash, "The deliveries must be of distinct smart meters (sorted by the meterPublicKeyHash)");
+            previousMeterPublicKeyHash = _deliveries[i].meterPublicKeyHash;
+            delivered += _verifyAggregateDelivery(_sellOrderId, sellOrder.energyAmountToBeSold, escrow.matchedAt, _deliveries[i]);
+        }
+
+        /// @dev - The energy delivered beyond the traded amount is not paid.
+        if (delivered > sellOrder.energyAmountToBeSold) {
+            delivered = sellOrder.energyAmountToBeSold;
+        }
+        _releaseEscrow(_sellOrderId, delivered);
+    }
+
+    /// @dev - Verify the delivery of a smart meter of a matched aggregate sell order, and returns its delivered amount.
+    function _verifyAggregateDelivery(
+        uint256 _sellOrderId,
+        uint256 _tradedAmount,
+        uint256 _matchedAt,
+        IEnergyAggregator.Delivery calldata _delivery
+    ) internal returns (uint256) {
+        require(aggregateMeterPublicKeyHashes[_sellOrderId][_delivery.meterPublicKeyHash], "The delivery must be measured by a smart meter of the sell order");
+        require(meterOwners[_delivery.meterPublicKeyHash] == msg.sender, "The smart meter is not owned by the caller");
+        require(_delivery.deliveryWindowStart >= _matchedAt, "The delivery window must start after the sell order was matched");
+        require(_delivery.deliveryWindowEnd <= block.timestamp, "The delivery window must not end in the future");
+        require(_delivery.delivered <= _tradedAmount, "The delivered amount must not exceed the traded amount");
+
+        // Construct the expected journal data. Verify will fail if journal does not match.
+        // (NOTE: The traded amount of the whole sell order is used, against which each smart meter proves its delivery)
+        bytes memory journal = abi.encode(_sellOrderId, _tradedAmount, _delivery.delivered, _tradedAmount - _delivery.delivered, _delivery.deliveryWindowStart, _delivery.deliveryWindowEnd, _delivery.monitoredMerkleRoot, _delivery.meterPublicKeyHash, msg.sender, block.chainid);
+        verifier.verify(_delivery.seal, settlementImageId, sha256(journal));
+
+        /// @dev - The same delivery readings can not settle another matched sell order.
+        bytes32 delivery = keccak256(abi.encode(_delivery.meterPublicKeyHash, _delivery.monitoredMerkleRoot, _delivery.deliveryWindowStart, _delivery.deliveryWindowEnd));
+        require(usedDeliveries[delivery] == false, "The delivery has already been used to settle a sell order");
+        usedDeliveries[delivery] = true;
+
+        return _delivery.delivered;
+    }
+
+    /// @dev - Close the escrow of a matched sell order, which is released to the seller pro rata to the delivered amount, and the rest is refunded to the buyer.
+    function _releaseEscrow(uint256 _sellOrderId, uint256 _delivered) internal {
+        DataTypes.SellOrder storage sellOrder = sellOrders[_sellOrderId];
+        DataTypes.Escrow storage escrow = escrows[_sellOrderId];
+        escrow.settled = true;
+
+        uint256 sellerPayment = escrow.paymentAmount * _delivered / sellOrder.energyAmountToBeSold;
+        buyerBalances[escrow.energyBuyer] += escrow.paymentAmount - sellerPayment;
+        if (sellerPayment > 0) {
+            (bool success, ) = sellOrder.energySeller.call{ value: sellerPayment }("");
+            require(success, "Payment to the seller failed");
+        }
+    }
+
+    /// @notice - Refund the escrowed payment of a matched sell order to the buyer's balance, once the sell order has not been settled
+    ///           by the seller within the settlementDeadline after it was matched.
+    function refundExpiredOrder(uint256 _sellOrderId) public { /// @dev - Submitted by the buyer of the matched sell order.
+        DataTypes.Escrow storage escrow = escrows[_sellOrderId];
+
+        require(sellOrders[_sellOrderId].orderMatched, "The sell order has not been matched");
+        require(escrow.settled == false, "The sell order has already been settled");
+        require(escrow.energyBuyer == msg.sender, "Only the buyer can refund the sell order");
+        require(block.timestamp > escrow.matchedAt + settlementDeadline, "The settlement deadline has not passed yet");
+
+        /// @dev - The escrow is closed (as settled), so that it can neither be settled nor refunded again.
+        escrow.settled = true;
+        buyerBalances[msg.sender] += escrow.paymentAmount;
     }
 
     /**
      * @notice - A buyer deposits native ETH into this contract.
      */
     function depositNativeETH() public payable returns (uint256) {
-        (bool success, ) = address(this).call{ value: msg.value }("");
-        require(success, "Deposit failed");
+        /// @dev - The msg.value is already transferred into this contract by the call itself, so only the balance is credited.
         buyerBalances[msg.sender] += msg.value;
         return buyerBalances[msg.sender];
     }
@@ -116,10 +419,11 @@ contract EnergyAggregator {
     /**
      * @notice - A buyer withdraws native ETH from this contract.
      */
-    function withdrawNativeETH(uint256 withdrawalEthAmount) public payable returns (uint256) {
-        (bool success, ) = address(this).call{ value: withdrawalEthAmount }("");
-        require(success, "Deposit failed");
+    function withdrawNativeETH(uint256 withdrawalEthAmount) public returns (uint256) {
+        require(buyerBalances[msg.sender] >= withdrawalEthAmount, "Insufficient balance to withdraw");
         buyerBalances[msg.sender] -= withdrawalEthAmount;
+        (bool success, ) = msg.sender.call{ value: withdrawalEthAmount }("");
+        require(success, "Withdrawal failed");
         return buyerBalances[msg.sender];
     }
 }
\ No newline at end of file
@@ -15,14 +15,125 @@
 pragma solidity ^0.8.20;
 
 interface IEnergyAggregator {
+    /// @dev - The journal committed by the aggregator guest program, which folds many smart-meter receipts into one.
+    struct AggregateJournal {
+        bytes32 smartMeterImageId;
+        uint256 energyAmountToBeSold;        /// @dev - Sum of the energy amounts to be sold of the smart meters.
+        uint256 windowStart;
+        uint256 windowEnd;
+        uint256 currentTime;                 /// @dev - The oldest current time of the smart-meter receipts.
+        uint256 maxMonitoredTimeAge;
+        bytes32[] monitoredMerkleRoots;
+        bytes32[] monitoredNullifiers;
+        bytes32[] meterPublicKeyHashes;
+        bytes32[] previousSoldCommitments;
+        bytes32[] soldCommitments;
+        address seller;
+        uint256 chainId;
+    }
+
+    /// @dev - The delivery of a smart meter of an aggregate sell order, which is proven by the settlement guest program against the
+    ///        traded amount of the whole sell order.
+    struct Delivery {
+        uint256 delivered;
+        uint256 deliveryWindowStart;
+        uint256 deliveryWindowEnd;
+        bytes32 monitoredMerkleRoot;
+        bytes32 meterPublicKeyHash;
+        bytes seal;
+    }
+
+    /// @dev - Emitted when a sell order (or an aggregate sell order) is created.
+    event SellOrderCreated(uint256 indexed sellOrderId, address indexed energySeller, uint256 energyAmountToBeSold, bytes32 monitoredMerkleRoot, bytes32 monitoredNullifier);
+
+    /// @dev - Emitted when a buy order is matched with a sell order (whose payment is escrowed until it is settled).
+    event BuyOrderMatched(uint256 indexed sellOrderId, address indexed energyBuyer, uint256 energyAmountToBeBought, uint256 paymentAmount);
+
     function createSellOrder(
         uint256 _energyAmountToBeSold, 
-        uint256 _monitoredTime,
+        uint256 _windowStart,
+        uint256 _windowEnd,
+        uint256 _currentTime,
         bytes32 _monitoredMerkleRoot,
         //uint256 _monitored_hash_path,
         bytes32 _monitoredNullifier,    /// @dev - Nullifier (Hash) is a unique identifier for a proof, which is used to prevent double-spending attacks.
+        bytes32 _meterPublicKeyHash,
+        bytes32 _soldCommitment,
+        bytes calldata seal) external;
+
+    function createAggregateSellOrder(AggregateJournal calldata _aggregateJournal, bytes calldata seal) external;
+
+    function createBuyOrder(
+        uint256 energyAmountToBeBought,
+        uint256 _windowStart,
+        uint256 _windowEnd,
+        uint256 _currentTime,
+        bytes32 _monitoredMerkleRoot,
+        bytes32 _meterPublicKeyHash,
+        bytes calldata seal) external;
+
+    function settleSellOrder(
+        uint256 _sellOrderId,
+        uint256 _delivered,
+        uint256 _deliveryWindowStart,
+        uint256 _deliveryWindowEnd,
+        bytes32 _monitoredMerkleRoot,
+        bytes32 _meterPublicKeyHash,
         bytes calldata seal) external;
 
+    function settleAggregateSellOrder(uint256 _sellOrderId, Delivery[] calldata _deliveries) external;
+
+    function refundExpiredOrder(uint256 _sellOrderId) external;
+
+    function soldCommitments(bytes32 monitoredMerkleRoot) external view returns (bytes32);
+
+    /// @dev - Whether the nullifier has already been used by a sell order (whichever seal it was submitted with).
+    function usedNullifiers(bytes32 monitoredNullifier) external view returns (bool);
+
+    /// @dev - Maximum age (in seconds) of a meter reading at the block time, which must also be committed by the journals.
+    function maxMonitoredTimeAge() external view returns (uint256);
+
+    /// @dev - Time (in seconds) after a sell order is matched, within which it must be settled.
+    function settlementDeadline() external view returns (uint256);
+
+    /// @dev - Same layout as the DataTypes.SellOrder, so that the sell orders can be read by the apps.
+    struct SellOrder {
+        uint256 energyAmountToBeSold;
+        address energySeller;
+        uint256 windowStart;
+        uint256 windowEnd;
+        bytes32 monitoredMerkleRoot;
+        bytes32 monitoredNullifier;
+        bool orderMatched;
+        bytes seal;
+        bytes32 imageId;
+        bytes32 journal;
+    }
+
+    /// @dev - Image IDs of the guest programs, which are pinned by the deployed contract.
+    function imageId() external view returns (bytes32);
+
+    function aggregatorImageId() external view returns (bytes32);
+
+    function consumptionImageId() external view returns (bytes32);
+
+    function settlementImageId() external view returns (bytes32);
+
+    function sellOrderId() external view returns (uint256);
+
+    function getSellOrder(uint256 _sellOrderId) external view returns (SellOrder memory);
+
+    /// @dev - Hash of the public key of the smart meter of the sell order (bytes32(0) for an aggregate sell order).
+    function sellOrderMeterPublicKeyHashes(uint256 _sellOrderId) external view returns (bytes32);
+
+    /// @dev - Whether the smart meter is aggregated into the aggregate sell order.
+    function aggregateMeterPublicKeyHashes(uint256 _sellOrderId, bytes32 meterPublicKeyHash) external view returns (bool);
+
+    function escrows(uint256 _sellOrderId) external view returns (address energyBuyer, uint256 paymentAmount, uint256 matchedAt, bool settled);
+
+    function buyerBalances(address buyer) external view returns (uint256);
+
+    function depositNativeETH() external payable returns (uint256);
 
-    function getEnergyAmountToBeSold() external view returns (uint256);
+    function withdrawNativeETH(uint256 withdrawalEthAmount) external returns (uint256);
 }
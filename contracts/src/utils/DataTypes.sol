@@ -7,7 +7,8 @@ library DataTypes {
     struct SellOrder { /// [Key]: sellOrderId
         uint256 energyAmountToBeSold; /// Asking amount of energy to be sold
         address energySeller;         /// Seller's address
-        uint256 monitoredTime;
+        uint256 windowStart;          /// Start of the window in which the energy was produced
+        uint256 windowEnd;            /// End of the window in which the energy was produced
         bytes32 monitoredMerkleRoot;
         bytes32 monitoredNullifier;
         bool orderMatched;
@@ -16,4 +17,11 @@ library DataTypes {
         bytes32 journal;
     }
 
+    struct Escrow { /// [Key]: sellOrderId
+        address energyBuyer;          /// Buyer's address, who is refunded for the shortfall of the delivery
+        uint256 paymentAmount;        /// Payment (in native ETH) held by the contract until the sell order is settled
+        uint256 matchedAt;            /// The time when the sell order was matched (= The earliest start of the delivery window)
+        bool settled;
+    }
+
 }
\ No newline at end of file
@@ -15,39 +15,540 @@
 pragma solidity ^0.8.20;
 
 import { console2 } from "forge-std/console2.sol";
-import { Test } from "forge-std/Test.sol";
+import { Test, Vm } from "forge-std/Test.sol";
 import { RiscZeroCheats } from "risc0/test/RiscZeroCheats.sol";
 import { Receipt as RiscZeroReceipt } from "risc0/IRiscZeroVerifier.sol";
 //import { IRiscZeroVerifier } from "risc0/IRiscZeroVerifier.sol";
 import { RiscZeroMockVerifier } from "risc0/test/RiscZeroMockVerifier.sol"; /// @dev - 'Prover' contract for testing.
 import { VerificationFailed } from "risc0/IRiscZeroVerifier.sol";
 import { EnergyAggregator } from "../src/EnergyAggregator.sol";
+import { IEnergyAggregator } from "../src/IEnergyAggregator.sol";
 import { ImageID } from "../src/ImageID.sol";
 
 contract EnergyAggregatorTest is RiscZeroCheats, Test {
     EnergyAggregator public energyAggregator;
     //IRiscZeroVerifier public verifier;
     RiscZeroMockVerifier public verifier;
+    uint256 public constant MAX_MONITORED_TIME_AGE = 3600; /// @dev - 1 hour
+    uint256 public constant SETTLEMENT_DEADLINE = 1 days;
+    bytes32 public constant METER_PUBLIC_KEY_HASH = 0x8a9b3d21c4e0f1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7081920; /// @dev - Hash of a (mock) smart meter's public key
+    bytes32 public constant ANOTHER_METER_PUBLIC_KEY_HASH = 0x9d2e4f6a8b0c1d3e5f7a9b1c3d5e7f9a0b2c4d6e8f0a1b3c5d7e9f1a2b4c6d8e; /// @dev - Hash of the public key of another (mock) smart meter in the same microgrid
+    bytes32 public constant SOLD_COMMITMENT = 0x5f1c7e3a9b2d4c6e8f0a1b3c5d7e9f1a2b4c6d8e0f1a3b5c7d9e1f2a4b6c8d0e; /// @dev - (Mock) commitment to the energy amount sold so far, after the first sell order
 
     function setUp() public {
         //address RISCZERO_VERIFIER = vm.envAddress("VERIFIER_ROUTER_ADDRESS"); /// @dev - Deployed-address of the 'RiscZeroVerifierRouter.sol' contract on Ethereum Sepolia.  
         //verifier = IRiscZeroVerifier(RISCZERO_VERIFIER);
         verifier = new RiscZeroMockVerifier(0); /// @dev - "Mock" Verifier (which is used for "proving" in this test)
-        energyAggregator = new EnergyAggregator(verifier);
+        energyAggregator = new EnergyAggregator(verifier, MAX_MONITORED_TIME_AGE, SETTLEMENT_DEADLINE);
+        energyAggregator.registerMeter(METER_PUBLIC_KEY_HASH, address(this));
+        energyAggregator.registerMeter(ANOTHER_METER_PUBLIC_KEY_HASH, address(this));
         assertEq(energyAggregator.getSellOrder(1).energyAmountToBeSold, 0);
     }
 
     function test_createSellOrder() public {
         uint256 energyAmountToBeSold = 100;  /// @dev - 100 kWh
-        uint256 monitoredTime = 1740641630;
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
         bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
         //uint256 monitored_hash_path,
         bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        vm.warp(currentTime + 60);
 
-        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, monitoredTime, monitoredMerkleRoot, monitoredNullifier)));
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
 
-        energyAggregator.createSellOrder(energyAmountToBeSold, monitoredTime, monitoredMerkleRoot, monitoredNullifier, receipt.seal);
+        vm.recordLogs();
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
         assertEq(energyAggregator.getSellOrder(1).energyAmountToBeSold, energyAmountToBeSold);
+        assertTrue(energyAggregator.usedNullifiers(monitoredNullifier));
+
+        /// @dev - The ID of the new sell order is emitted, so that the app can read it back from the tx receipt.
+        Vm.Log[] memory logs = vm.getRecordedLogs();
+        assertEq(logs.length, 1);
+        assertEq(logs[0].topics[0], IEnergyAggregator.SellOrderCreated.selector);
+        assertEq(logs[0].topics[1], bytes32(uint256(1)));
+        assertEq(logs[0].topics[2], bytes32(uint256(uint160(address(this)))));
+    }
+
+    function test_createSellOrderChainedFromSoldCommitment() public {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        bytes32 nextSoldCommitment = keccak256("sold commitment after the second sell order");
+        bytes32 nextMonitoredNullifier = keccak256("monitored nullifier of the second sell order"); /// @dev - The nullifier is bound to the previous sold commitment.
+        vm.warp(currentTime + 60);
+
+        /// @dev - The first sell order (from the initial commitment = bytes32(0)).
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+        energyAggregator.createSellOrder(100, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+        assertEq(energyAggregator.soldCommitments(monitoredMerkleRoot), SOLD_COMMITMENT);
+
+        /// @dev - The second sell order of the same meter readings chains from the commitment of the first one.
+        receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(50), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, nextMonitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, nextSoldCommitment, address(this), block.chainid)));
+        energyAggregator.createSellOrder(50, windowStart, windowEnd, currentTime, monitoredMerkleRoot, nextMonitoredNullifier, METER_PUBLIC_KEY_HASH, nextSoldCommitment, receipt.seal);
+        assertEq(energyAggregator.soldCommitments(monitoredMerkleRoot), nextSoldCommitment);
+    }
+
+    function test_rejectReusedNullifier() public {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        bytes32 nextSoldCommitment = keccak256("sold commitment after the second sell order");
+        vm.warp(currentTime + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+        energyAggregator.createSellOrder(100, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+
+        /// @dev - Another proof (i.e. seal) with the same nullifier is rejected, even if it chains from the latest sold commitment.
+        receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(50), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, nextSoldCommitment, address(this), block.chainid)));
+        vm.expectRevert("The nullifier has already been used");
+        energyAggregator.createSellOrder(50, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, nextSoldCommitment, receipt.seal);
+    }
+
+    function test_rejectSellOrderNotChainedFromSoldCommitment() public {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        vm.warp(currentTime + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+        energyAggregator.createSellOrder(100, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+
+        /// @dev - Another proof of the same meter readings, which ignores the first sell order (i.e. chains from bytes32(0) again), is rejected.
+        RiscZeroReceipt memory replayedReceipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(99), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), keccak256("another sold commitment"), address(this), block.chainid)));
+        vm.expectRevert(VerificationFailed.selector);
+        energyAggregator.createSellOrder(99, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, keccak256("another sold commitment"), replayedReceipt.seal);
+    }
+
+    function _aggregateJournal() internal view returns (IEnergyAggregator.AggregateJournal memory aggregateJournal) {
+        aggregateJournal.smartMeterImageId = ImageID.SMART_METER_ID;
+        aggregateJournal.energyAmountToBeSold = 150;  /// @dev - 100 kWh + 50 kWh of 2 smart meters in a microgrid
+        aggregateJournal.windowStart = 1740638030;
+        aggregateJournal.windowEnd = 1740641630;
+        aggregateJournal.currentTime = 1740641690;
+        aggregateJournal.maxMonitoredTimeAge = MAX_MONITORED_TIME_AGE;
+        aggregateJournal.monitoredMerkleRoots = new bytes32[](2);
+        aggregateJournal.monitoredMerkleRoots[0] = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        aggregateJournal.monitoredMerkleRoots[1] = keccak256("monitored merkle root of another smart meter");
+        aggregateJournal.monitoredNullifiers = new bytes32[](2);
+        aggregateJournal.monitoredNullifiers[0] = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        aggregateJournal.monitoredNullifiers[1] = keccak256("monitored nullifier of another smart meter");
+        aggregateJournal.meterPublicKeyHashes = new bytes32[](2);
+        aggregateJournal.meterPublicKeyHashes[0] = METER_PUBLIC_KEY_HASH;
+        aggregateJournal.meterPublicKeyHashes[1] = ANOTHER_METER_PUBLIC_KEY_HASH;
+        aggregateJournal.previousSoldCommitments = new bytes32[](2); /// @dev - Nothing is sold yet (= bytes32(0))
+        aggregateJournal.soldCommitments = new bytes32[](2);
+        aggregateJournal.soldCommitments[0] = SOLD_COMMITMENT;
+        aggregateJournal.soldCommitments[1] = keccak256("sold commitment of another smart meter");
+        aggregateJournal.seller = address(this);
+        aggregateJournal.chainId = block.chainid;
+    }
+
+    function test_createAggregateSellOrder() public {
+        IEnergyAggregator.AggregateJournal memory aggregateJournal = _aggregateJournal();
+        vm.warp(aggregateJournal.currentTime + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.AGGREGATOR_ID, sha256(abi.encode(aggregateJournal)));
+
+        energyAggregator.createAggregateSellOrder(aggregateJournal, receipt.seal);
+        assertEq(energyAggregator.getSellOrder(1).energyAmountToBeSold, 150);
+        assertEq(energyAggregator.soldCommitments(aggregateJournal.monitoredMerkleRoots[0]), aggregateJournal.soldCommitments[0]);
+        assertEq(energyAggregator.soldCommitments(aggregateJournal.monitoredMerkleRoots[1]), aggregateJournal.soldCommitments[1]);
+    }
+
+    function test_rejectAggregateSellOrderOfOtherGuestProgram() public {
+        IEnergyAggregator.AggregateJournal memory aggregateJournal = _aggregateJournal();
+        aggregateJournal.smartMeterImageId = keccak256("another guest program"); /// @dev - The aggregated receipts are not of the smart-meter guest program.
+        vm.warp(aggregateJournal.currentTime + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.AGGREGATOR_ID, sha256(abi.encode(aggregateJournal)));
+
+        vm.expectRevert("The aggregated receipts must be of the smart-meter guest program");
+        energyAggregator.createAggregateSellOrder(aggregateJournal, receipt.seal);
+    }
+
+    function test_createBuyOrder() public {
+        uint256 energyAmountToBeBought = 100;  /// @dev - 100 kWh
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter");
+        uint256 payment = energyAmountToBeBought * energyAggregator.energyPricePerKwh();
+        vm.warp(currentTime + 60);
+
+        /// @dev - An open sell order of the same amount, with which the buy order is matched.
+        bytes32 sellMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 sellNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeBought, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, sellMerkleRoot, sellNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+        energyAggregator.createSellOrder(energyAmountToBeBought, windowStart, windowEnd, currentTime, sellMerkleRoot, sellNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+        energyAggregator.depositNativeETH{ value: payment }();
+
+        receipt = verifier.mockProve(ImageID.CONSUMPTION_ID, sha256(abi.encode(energyAmountToBeBought, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+
+        vm.recordLogs();
+        energyAggregator.createBuyOrder(energyAmountToBeBought, windowStart, windowEnd, currentTime, monitoredMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertTrue(energyAggregator.usedConsumptionMerkleRoots(monitoredMerkleRoot));
+        assertTrue(energyAggregator.getSellOrder(1).orderMatched);
+
+        /// @dev - The matched sell order can be found from the BuyOrderMatched event.
+        Vm.Log[] memory logs = vm.getRecordedLogs();
+        assertEq(logs.length, 1);
+        assertEq(logs[0].topics[0], IEnergyAggregator.BuyOrderMatched.selector);
+        assertEq(logs[0].topics[1], bytes32(uint256(1)));
+        assertEq(logs[0].topics[2], bytes32(uint256(uint160(address(this)))));
+        assertEq(logs[0].data, abi.encode(energyAmountToBeBought, payment));
+    }
+
+    function test_rejectUnmatchedBuyOrder() public {
+        bytes32 consumptionMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter");
+        vm.warp(1740641690 + 60);
+
+        /// @dev - No sell order has been created, so the buy order is rejected (and its consumption baseline is not used up).
+        RiscZeroReceipt memory receipt = _consumptionReceipt(consumptionMerkleRoot);
+        vm.expectRevert("No open sell order matches the amount");
+        energyAggregator.createBuyOrder(100, 1740638030, 1740641630, 1740641690, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertFalse(energyAggregator.usedConsumptionMerkleRoots(consumptionMerkleRoot));
+    }
+
+    function test_rejectBuyOrderReusingConsumptionBaseline() public {
+        _createMatchedSellOrder();
+        _createSecondSellOrder();
+        energyAggregator.depositNativeETH{ value: 100 * energyAggregator.energyPricePerKwh() }();
+
+        /// @dev - Another buy order backed by the same consumption baseline (as the matched one) is rejected, even though the sell order 2 is open.
+        bytes32 consumptionMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter");
+        RiscZeroReceipt memory receipt = _consumptionReceipt(consumptionMerkleRoot);
+        vm.expectRevert("The consumption baseline has already been used");
+        energyAggregator.createBuyOrder(100, 1740638030, 1740641630, 1740641690, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    /// @dev - Create a sell order of 100 kWh, and match it with a buy order of a buyer who has deposited the payment (= This contract is both the seller and the buyer).
+    function _createMatchedSellOrder() internal returns (uint256 matchedAt) {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        bytes32 consumptionMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter");
+        uint256 payment = 100 * energyAggregator.energyPricePerKwh();
+        matchedAt = currentTime + 60;
+        vm.warp(matchedAt);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+        energyAggregator.createSellOrder(100, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+
+        /// @dev - The buyer deposits the payment into the contract.
+        energyAggregator.depositNativeETH{ value: payment }();
+
+        receipt = verifier.mockProve(ImageID.CONSUMPTION_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+        energyAggregator.createBuyOrder(100, windowStart, windowEnd, currentTime, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertTrue(energyAggregator.getSellOrder(1).orderMatched);
+        assertEq(energyAggregator.buyerBalances(address(this)), 0); /// @dev - The payment is held in the escrow.
+    }
+
+    /// @dev - Create a second (open) sell order of 100 kWh from the same meter readings, which chains from the first one.
+    function _createSecondSellOrder() internal {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = keccak256("monitored nullifier of the second sell order");
+        bytes32 nextSoldCommitment = keccak256("sold commitment after the second sell order");
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, nextSoldCommitment, address(this), block.chainid)));
+        energyAggregator.createSellOrder(100, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, nextSoldCommitment, receipt.seal);
+    }
+
+    /// @dev - The (mock) proof of the consumption baseline of 100 kWh of the given merkle root.
+    function _consumptionReceipt(bytes32 consumptionMerkleRoot) internal view returns (RiscZeroReceipt memory) {
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        return verifier.mockProve(ImageID.CONSUMPTION_ID, sha256(abi.encode(uint256(100), windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+    }
+
+    /// @dev - Create a buy order of 100 kWh, backed by the consumption baseline of the given merkle root.
+    function _createBuyOrder(bytes32 consumptionMerkleRoot) internal {
+        RiscZeroReceipt memory receipt = _consumptionReceipt(consumptionMerkleRoot);
+        energyAggregator.createBuyOrder(100, 1740638030, 1740641630, 1740641690, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    function test_matchBuyOrderWithFirstOpenSellOrderOnly() public {
+        _createMatchedSellOrder();
+        _createSecondSellOrder();
+        uint256 payment = 100 * energyAggregator.energyPricePerKwh();
+        energyAggregator.depositNativeETH{ value: 2 * payment }();
+
+        /// @dev - The already matched sell order 1 is skipped, and the buyer is escrowed only once for the sell order 2.
+        _createBuyOrder(keccak256("monitored merkle root of the buyer's smart meter (2nd baseline)"));
+        assertTrue(energyAggregator.getSellOrder(2).orderMatched);
+        assertEq(energyAggregator.buyerBalances(address(this)), payment);
+        (address energyBuyer, uint256 paymentAmount, , ) = energyAggregator.escrows(2);
+        assertEq(energyBuyer, address(this));
+        assertEq(paymentAmount, payment);
+
+        /// @dev - No open sell order is left, so another buy order of the same amount is rejected (and not paid).
+        bytes32 consumptionMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter (3rd baseline)");
+        RiscZeroReceipt memory receipt = _consumptionReceipt(consumptionMerkleRoot);
+        vm.expectRevert("No open sell order matches the amount");
+        energyAggregator.createBuyOrder(100, 1740638030, 1740641630, 1740641690, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertEq(energyAggregator.buyerBalances(address(this)), payment);
+        assertFalse(energyAggregator.usedConsumptionMerkleRoots(consumptionMerkleRoot));
+    }
+
+    function test_rejectSettlementReusingDelivery() public {
+        uint256 matchedAt = _createMatchedSellOrder();
+        _createSecondSellOrder();
+        energyAggregator.depositNativeETH{ value: 100 * energyAggregator.energyPricePerKwh() }();
+        _createBuyOrder(keccak256("monitored merkle root of the buyer's smart meter (2nd baseline)"));
+        uint256 deliveryWindowStart = matchedAt;
+        uint256 deliveryWindowEnd = matchedAt + 3600;
+        bytes32 deliveryMerkleRoot = keccak256("monitored merkle root of the delivery window");
+        vm.warp(deliveryWindowEnd + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(1), uint256(100), uint256(100), uint256(0), deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+        energyAggregator.settleSellOrder(1, 100, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+
+        /// @dev - The same delivery readings can not settle the sell order 2 as well.
+        receipt = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(2), uint256(100), uint256(100), uint256(0), deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+        vm.expectRevert("The delivery has already been used to settle a sell order");
+        energyAggregator.settleSellOrder(2, 100, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    function test_refundExpiredOrder() public {
+        uint256 matchedAt = _createMatchedSellOrder();
+        uint256 payment = 100 * energyAggregator.energyPricePerKwh();
+
+        vm.warp(matchedAt + SETTLEMENT_DEADLINE);
+        vm.expectRevert("The settlement deadline has not passed yet");
+        energyAggregator.refundExpiredOrder(1);
+
+        vm.warp(matchedAt + SETTLEMENT_DEADLINE + 1);
+        vm.prank(address(0xBEEF));
+        vm.expectRevert("Only the buyer can refund the sell order");
+        energyAggregator.refundExpiredOrder(1);
+
+        /// @dev - The sell order was not settled within the deadline, so the escrowed payment is refunded to the buyer.
+        energyAggregator.refundExpiredOrder(1);
+        assertEq(energyAggregator.buyerBalances(address(this)), payment);
+
+        vm.expectRevert("The sell order has already been settled");
+        energyAggregator.refundExpiredOrder(1);
+    }
+
+    function test_rejectSettlementAfterDeadline() public {
+        uint256 matchedAt = _createMatchedSellOrder();
+        uint256 deliveryWindowStart = matchedAt;
+        uint256 deliveryWindowEnd = matchedAt + 3600;
+        bytes32 deliveryMerkleRoot = keccak256("monitored merkle root of the delivery window");
+        vm.warp(matchedAt + SETTLEMENT_DEADLINE + 1);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(1), uint256(100), uint256(100), uint256(0), deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+        vm.expectRevert("The settlement deadline has passed");
+        energyAggregator.settleSellOrder(1, 100, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    /// @dev - Create an aggregate sell order of 150 kWh (of 2 smart meters), and match it with a buy order of the same amount.
+    function _createMatchedAggregateSellOrder() internal returns (uint256 matchedAt) {
+        IEnergyAggregator.AggregateJournal memory aggregateJournal = _aggregateJournal();
+        matchedAt = aggregateJournal.currentTime + 60;
+        vm.warp(matchedAt);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.AGGREGATOR_ID, sha256(abi.encode(aggregateJournal)));
+        energyAggregator.createAggregateSellOrder(aggregateJournal, receipt.seal);
+        assertTrue(energyAggregator.aggregateMeterPublicKeyHashes(1, METER_PUBLIC_KEY_HASH));
+        assertTrue(energyAggregator.aggregateMeterPublicKeyHashes(1, ANOTHER_METER_PUBLIC_KEY_HASH));
+
+        energyAggregator.depositNativeETH{ value: 150 * energyAggregator.energyPricePerKwh() }();
+        bytes32 consumptionMerkleRoot = keccak256("monitored merkle root of the buyer's smart meter");
+        receipt = verifier.mockProve(ImageID.CONSUMPTION_ID, sha256(abi.encode(uint256(150), aggregateJournal.windowStart, aggregateJournal.windowEnd, aggregateJournal.currentTime, MAX_MONITORED_TIME_AGE, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+        energyAggregator.createBuyOrder(150, aggregateJournal.windowStart, aggregateJournal.windowEnd, aggregateJournal.currentTime, consumptionMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertTrue(energyAggregator.getSellOrder(1).orderMatched);
+    }
+
+    /// @dev - The delivery of a smart meter of the aggregate sell order 1 (150 kWh), proven against the traded amount of the whole sell order.
+    function _aggregateDelivery(uint256 delivered, uint256 matchedAt, bytes32 deliveryMerkleRoot, bytes32 meterPublicKeyHash) internal view returns (IEnergyAggregator.Delivery memory delivery) {
+        delivery.delivered = delivered;
+        delivery.deliveryWindowStart = matchedAt;
+        delivery.deliveryWindowEnd = matchedAt + 3600;
+        delivery.monitoredMerkleRoot = deliveryMerkleRoot;
+        delivery.meterPublicKeyHash = meterPublicKeyHash;
+        delivery.seal = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(1), uint256(150), delivered, 150 - delivered, delivery.deliveryWindowStart, delivery.deliveryWindowEnd, deliveryMerkleRoot, meterPublicKeyHash, address(this), block.chainid))).seal;
+    }
+
+    function test_settleAggregateSellOrder() public {
+        uint256 matchedAt = _createMatchedAggregateSellOrder();
+        vm.warp(matchedAt + 3600 + 60);
+
+        /// @dev - The 2 smart meters delivered 100 kWh + 30 kWh of the traded 150 kWh (= 20 kWh of shortfall).
+        IEnergyAggregator.Delivery[] memory deliveries = new IEnergyAggregator.Delivery[](2);
+        deliveries[0] = _aggregateDelivery(100, matchedAt, keccak256("monitored merkle root of the delivery window"), METER_PUBLIC_KEY_HASH);
+        deliveries[1] = _aggregateDelivery(30, matchedAt, keccak256("monitored merkle root of the delivery window of another smart meter"), ANOTHER_METER_PUBLIC_KEY_HASH);
+
+        uint256 sellerBalanceBefore = address(this).balance;
+        energyAggregator.settleAggregateSellOrder(1, deliveries);
+        assertEq(address(this).balance - sellerBalanceBefore, 130 * energyAggregator.energyPricePerKwh());
+        assertEq(energyAggregator.buyerBalances(address(this)), 20 * energyAggregator.energyPricePerKwh());
+
+        vm.expectRevert("The sell order has already been settled");
+        energyAggregator.settleAggregateSellOrder(1, deliveries);
+    }
+
+    function test_settleAggregateSellOrderCapsDelivery() public {
+        uint256 matchedAt = _createMatchedAggregateSellOrder();
+        vm.warp(matchedAt + 3600 + 60);
+
+        /// @dev - The 2 smart meters delivered 100 kWh + 100 kWh, but only the traded 150 kWh are paid.
+        IEnergyAggregator.Delivery[] memory deliveries = new IEnergyAggregator.Delivery[](2);
+        deliveries[0] = _aggregateDelivery(100, matchedAt, keccak256("monitored merkle root of the delivery window"), METER_PUBLIC_KEY_HASH);
+        deliveries[1] = _aggregateDelivery(100, matchedAt, keccak256("monitored merkle root of the delivery window of another smart meter"), ANOTHER_METER_PUBLIC_KEY_HASH);
+
+        uint256 sellerBalanceBefore = address(this).balance;
+        energyAggregator.settleAggregateSellOrder(1, deliveries);
+        assertEq(address(this).balance - sellerBalanceBefore, 150 * energyAggregator.energyPricePerKwh());
+        assertEq(energyAggregator.buyerBalances(address(this)), 0);
+    }
+
+    function test_rejectAggregateSettlementCountingSmartMeterTwice() public {
+        uint256 matchedAt = _createMatchedAggregateSellOrder();
+        vm.warp(matchedAt + 3600 + 60);
+
+        /// @dev - The same smart meter can not deliver twice (e.g. from overlapping delivery windows).
+        IEnergyAggregator.Delivery[] memory deliveries = new IEnergyAggregator.Delivery[](2);
+        deliveries[0] = _aggregateDelivery(100, matchedAt, keccak256("monitored merkle root of the delivery window"), METER_PUBLIC_KEY_HASH);
+        deliveries[1] = _aggregateDelivery(50, matchedAt, keccak256("another monitored merkle root of the delivery window"), METER_PUBLIC_KEY_HASH);
+
+        vm.expectRevert("The deliveries must be of distinct smart meters (sorted by the meterPublicKeyHash)");
+        energyAggregator.settleAggregateSellOrder(1, deliveries);
+    }
+
+    function test_rejectSettlingAggregateSellOrderBySingleSmartMeter() public {
+        uint256 matchedAt = _createMatchedAggregateSellOrder();
+        vm.warp(matchedAt + 3600 + 60);
+        IEnergyAggregator.Delivery memory delivery = _aggregateDelivery(100, matchedAt, keccak256("monitored merkle root of the delivery window"), METER_PUBLIC_KEY_HASH);
+
+        vm.expectRevert("The aggregate sell order must be settled via settleAggregateSellOrder()");
+        energyAggregator.settleSellOrder(1, delivery.delivered, delivery.deliveryWindowStart, delivery.deliveryWindowEnd, delivery.monitoredMerkleRoot, delivery.meterPublicKeyHash, delivery.seal);
+    }
+
+    function test_settleSellOrderWithShortfall() public {
+        uint256 matchedAt = _createMatchedSellOrder();
+        uint256 deliveryWindowStart = matchedAt;
+        uint256 deliveryWindowEnd = matchedAt + 3600;
+        bytes32 deliveryMerkleRoot = keccak256("monitored merkle root of the delivery window");
+        vm.warp(deliveryWindowEnd + 60);
+
+        /// @dev - Only 80 kWh of the traded 100 kWh were delivered (= 20 kWh of shortfall).
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(1), uint256(100), uint256(80), uint256(20), deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+
+        uint256 sellerBalanceBefore = address(this).balance;
+        energyAggregator.settleSellOrder(1, 80, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+        assertEq(address(this).balance - sellerBalanceBefore, 80 * energyAggregator.energyPricePerKwh());
+        assertEq(energyAggregator.buyerBalances(address(this)), 20 * energyAggregator.energyPricePerKwh());
+
+        vm.expectRevert("The sell order has already been settled");
+        energyAggregator.settleSellOrder(1, 80, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    function test_rejectSettlementOverstatingDelivery() public {
+        uint256 matchedAt = _createMatchedSellOrder();
+        uint256 deliveryWindowStart = matchedAt;
+        uint256 deliveryWindowEnd = matchedAt + 3600;
+        bytes32 deliveryMerkleRoot = keccak256("monitored merkle root of the delivery window");
+        vm.warp(deliveryWindowEnd + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SETTLEMENT_ID, sha256(abi.encode(uint256(1), uint256(100), uint256(80), uint256(20), deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, address(this), block.chainid)));
+
+        /// @dev - The seller claims the whole traded amount, while the proof only backs 80 kWh.
+        vm.expectRevert(VerificationFailed.selector);
+        energyAggregator.settleSellOrder(1, 100, deliveryWindowStart, deliveryWindowEnd, deliveryMerkleRoot, METER_PUBLIC_KEY_HASH, receipt.seal);
+    }
+
+    /// @dev - This contract is also the seller, who receives the payment released from the escrow.
+    receive() external payable {}
+
+    function test_depositAndWithdrawNativeETH() public {
+        uint256 balanceBefore = address(this).balance;
+        assertEq(energyAggregator.depositNativeETH{ value: 1 ether }(), 1 ether);
+        assertEq(address(energyAggregator).balance, 1 ether);
+
+        assertEq(energyAggregator.withdrawNativeETH(0.4 ether), 0.6 ether);
+        assertEq(address(this).balance, balanceBefore - 0.6 ether);
+
+        vm.expectRevert("Insufficient balance to withdraw");
+        energyAggregator.withdrawNativeETH(1 ether);
+    }
+
+    function test_rejectUnregisteredMeter() public {
+        uint256 energyAmountToBeSold = 100;  /// @dev - 100 kWh
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        bytes32 unregisteredMeterPublicKeyHash = keccak256("unregistered smart meter");
+        vm.warp(currentTime + 60);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, unregisteredMeterPublicKeyHash, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+
+        vm.expectRevert("Meter reading is not signed by a registered smart meter");
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, unregisteredMeterPublicKeyHash, SOLD_COMMITMENT, receipt.seal);
+    }
+
+    function test_rejectMeterOwnedByAnother() public {
+        uint256 energyAmountToBeSold = 100;  /// @dev - 100 kWh
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        bytes32 otherMeterPublicKeyHash = keccak256("smart meter of another prosumer");
+        energyAggregator.registerMeter(otherMeterPublicKeyHash, address(0xBEEF));
+        vm.warp(currentTime + 60);
+
+        /// @dev - A valid proof of the signed meter readings of another prosumer's smart meter, which is bound to this contract (= seller).
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, otherMeterPublicKeyHash, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+
+        vm.expectRevert("The smart meter is not owned by the caller");
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, otherMeterPublicKeyHash, SOLD_COMMITMENT, receipt.seal);
+    }
+
+    function test_rejectFrontRunning() public {
+        uint256 energyAmountToBeSold = 100;  /// @dev - 100 kWh
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        vm.warp(currentTime + 60);
+
+        /// @dev - The proof is bound to this contract (= seller).
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+
+        /// @dev - Another address, which copied the seal from the mempool, can not use it.
+        vm.prank(address(0xBEEF));
+        vm.expectRevert(VerificationFailed.selector);
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
+    }
+
+    function test_rejectStaleProof() public {
+        uint256 energyAmountToBeSold = 100;  /// @dev - 100 kWh
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
+        bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
+        bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        vm.warp(currentTime + MAX_MONITORED_TIME_AGE + 1);
+
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
+
+        vm.expectRevert("Meter reading is too old to be sold");
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
     }
 
     // function test_createSellOrder_with_Zero() public {
@@ -66,19 +567,22 @@ contract EnergyAggregatorTest is RiscZeroCheats, Test {
     // Try using a proof for the evenness of 4 to set 1 on the contract.
     function test_rejectInvalidProof() public {
         uint256 energyAmountToBeSold = 0;  /// @dev - 0 kWh
-        uint256 monitoredTime = 1740641630;
+        uint256 windowStart = 1740638030;
+        uint256 windowEnd = 1740641630;
+        uint256 currentTime = 1740641690;
         bytes32 monitoredMerkleRoot = 0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7;
         //uint256 monitored_hash_path,
         bytes32 monitoredNullifier = 0x1efa9d6bb4dfdf86063cc77efdec90eb9262079230f1898049efad264835b6c8;
+        vm.warp(currentTime + 60);
 
         /// @dev - Generate (= Prove) a new mock proof.
-        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, monitoredTime, monitoredMerkleRoot, monitoredNullifier)));
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.SMART_METER_ID, sha256(abi.encode(energyAmountToBeSold, windowStart, windowEnd, currentTime, MAX_MONITORED_TIME_AGE, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, bytes32(0), SOLD_COMMITMENT, address(this), block.chainid)));
         //console2.log("Receipt ID:", receipt.id);
         console2.logBytes(receipt.seal); // [Log]: 0x000000002cfcebe8cc0eeb0dbd0d347d08fb5ee468cd9747c1920d0cb81222b1e8576962
 
         /// @dev - Verify the mock proof-generated via the function below.
         vm.expectRevert("Energy amount to be sold must be greater than 0"); /// @dev - This expected revert message must correspond to an error message in the SC level validation in the submitEnergyAmountToBeSold().
         //vm.expectRevert(VerificationFailed.selector);
-        energyAggregator.createSellOrder(energyAmountToBeSold, monitoredTime, monitoredMerkleRoot, monitoredNullifier, receipt.seal);
+        energyAggregator.createSellOrder(energyAmountToBeSold, windowStart, windowEnd, currentTime, monitoredMerkleRoot, monitoredNullifier, METER_PUBLIC_KEY_HASH, SOLD_COMMITMENT, receipt.seal);
     }
 }
@@ -0,0 +1,60 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input and journal types of the aggregator guest program, which folds many smart-meter receipts
+//! (e.g. of a community solar microgrid) into a single sell order.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+/// The input data of the aggregator guest program, written by the host as a single value.
+/// (NOTE: The smart-meter receipts themselves are added to the executor environment as assumptions)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregatorInput {
+    /// Image ID of the smart-meter guest program, whose receipts are aggregated.
+    pub smart_meter_image_id: [u32; 8],
+    /// The journals of the smart-meter receipts to be aggregated (See: [crate::SmartMeterJournal]).
+    pub journals: Vec<Vec<u8>>,
+}
+
+alloy_sol_types::sol! {
+    /// The journal committed by the aggregator guest program (Solidity ABI-encoded).
+    /// This must be the same struct as the IEnergyAggregator.AggregateJournal, which is abi.encode()-ed
+    /// in the EnergyAggregator#createAggregateSellOrder().
+    /// (NOTE: Only the sum of the energy amounts is committed, so that the amount of each member is not revealed)
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct AggregateJournal {
+        bytes32 smartMeterImageId;
+        uint256 energyAmountToBeSold;
+        uint256 windowStart;
+        uint256 windowEnd;
+        uint256 currentTime;
+        uint256 maxMonitoredTimeAge;
+        bytes32[] monitoredMerkleRoots;
+        bytes32[] monitoredNullifiers;
+        bytes32[] meterPublicKeyHashes;
+        bytes32[] previousSoldCommitments;
+        bytes32[] soldCommitments;
+        address seller;
+        uint256 chainId;
+    }
+}
+
+impl AggregateJournal {
+    /// Decode the journal bytes committed by the aggregator guest program.
+    pub fn decode(journal: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        <Self as alloy_sol_types::SolValue>::abi_decode(journal, true)
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Constraints on the meter readings, shared by the guest programs (the seller's and the buyer's).
+//! Like the other constraints in the guest programs, these panic with the message of the violated constraint.
+
+use crate::{merkle::compute_merkle_root, MeterReadingProof, Reading};
+
+/// Constraint: Check whether each meter reading (leaf) is included in the monitored merkle tree and is inside the window,
+/// and whether the series of meter readings is ordered by timestamp (strictly increasing).
+/// The leaves are recomputed from the readings themselves, so the readings can not be chosen independently
+/// from the set of readings committed to by the (signed) monitored_merkle_root.
+pub fn check_meter_readings<R: Reading>(meter_readings: &[MeterReadingProof<R>], monitored_merkle_root: &[u8; 32], window_start: u64, window_end: u64) {
+    assert!(meter_readings.len() >= 2, "At least 2 meter readings are required to compute the energy in the window.");
+    for meter_reading in meter_readings {
+        let computed_merkle_root = compute_merkle_root(meter_reading.reading.leaf(), &meter_reading.hash_path);
+        assert!(computed_merkle_root == *monitored_merkle_root, "The meter reading is not included in the monitored_merkle_root.");
+        let timestamp = meter_reading.reading.timestamp();
+        assert!(window_start <= timestamp && timestamp <= window_end, "The meter reading is outside of the window.");
+    }
+    for pair in meter_readings.windows(2) {
+        assert!(pair[0].reading.timestamp() < pair[1].reading.timestamp(), "The meter readings must be ordered by timestamp.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{merkle::MerkleTree, MeterReading};
+
+    /// The meter readings (with their hash paths) and the monitored merkle root of the given readings.
+    fn meter_readings(readings: &[MeterReading]) -> (Vec<MeterReadingProof>, [u8; 32]) {
+        let tree = MerkleTree::new(readings.iter().map(Reading::leaf).collect()).unwrap();
+        let meter_readings = readings
+            .iter()
+            .enumerate()
+            .map(|(index, reading)| MeterReadingProof { reading: *reading, hash_path: tree.hash_path(index).unwrap() })
+            .collect();
+        (meter_readings, tree.root())
+    }
+
+    fn reading(timestamp: u64, register: u64) -> MeterReading {
+        MeterReading { timestamp, register }
+    }
+
+    #[test]
+    fn accepts_readings_inside_the_window() {
+        let (meter_readings, root) = meter_readings(&[reading(1_000, 10_000), reading(1_500, 12_000), reading(2_000, 15_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "The meter reading is outside of the window.")]
+    fn rejects_readings_outside_of_the_window() {
+        let (meter_readings, root) = meter_readings(&[reading(1_000, 10_000), reading(1_500, 12_000), reading(2_500, 15_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "The meter reading is outside of the window.")]
+    fn rejects_readings_before_the_window() {
+        let (meter_readings, root) = meter_readings(&[reading(900, 10_000), reading(1_500, 12_000), reading(2_000, 15_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "The meter readings must be ordered by timestamp.")]
+    fn rejects_readings_out_of_order() {
+        let (meter_readings, root) = meter_readings(&[reading(1_000, 10_000), reading(2_000, 15_000), reading(1_500, 12_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "The meter readings must be ordered by timestamp.")]
+    fn rejects_duplicated_readings() {
+        let (meter_readings, root) = meter_readings(&[reading(1_000, 10_000), reading(1_000, 10_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "The meter reading is not included in the monitored_merkle_root.")]
+    fn rejects_readings_not_included_in_the_root() {
+        let (mut meter_readings, root) = meter_readings(&[reading(1_000, 10_000), reading(2_000, 15_000)]);
+        meter_readings[1].reading.register = 50_000;
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "At least 2 meter readings are required to compute the energy in the window.")]
+    fn rejects_a_single_reading() {
+        let (meter_readings, root) = meter_readings(&[reading(1_000, 10_000)]);
+        check_meter_readings(&meter_readings, &root, 1_000, 2_000);
+    }
+}
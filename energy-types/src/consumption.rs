@@ -0,0 +1,75 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input and journal types of the consumption guest program, which proves that a buyer has the demand to consume
+//! the energy to be bought (i.e. its consumption baseline is above the purchase amount).
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MeterReadingProof;
+
+/// The input data of the consumption guest program, written by the host as a single value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsumptionInput {
+    /// The energy amount that the buyer wants to buy (Unit: kWh).
+    pub amount_of_energy_to_be_bought: u64,
+    /// Start of the (baseline) window in which the energy was consumed (UTC timestamp).
+    pub window_start: u64,
+    /// End of the (baseline) window in which the energy was consumed (UTC timestamp).
+    pub window_end: u64,
+    /// The time when the freshness of the window is checked (UTC timestamp).
+    pub current_time: u64,
+    /// Maximum age (in seconds) of the window_end at the current_time.
+    pub max_monitored_time_age: u64,
+    /// Merkle root of the meter readings of the buyer's smart meter.
+    pub monitored_merkle_root: [u8; 32],
+    /// The series of readings of the cumulative (import) register in the window (ordered by timestamp), with their
+    /// merkle inclusion proofs. The energy consumed in the window is the difference between the last and the first register values.
+    pub meter_readings: Vec<MeterReadingProof>,
+    /// SEC1-encoded secp256k1 public key of the buyer's smart meter.
+    pub meter_public_key: Vec<u8>,
+    /// ECDSA signature (r || s) of the monitored_merkle_root, signed by the buyer's smart meter.
+    pub meter_signature: Vec<u8>,
+    /// Address of the buyer, who submits the buy order to the EnergyAggregator contract.
+    pub buyer: [u8; 20],
+    /// Chain ID of the chain where the EnergyAggregator contract is deployed.
+    pub chain_id: u64,
+}
+
+alloy_sol_types::sol! {
+    /// The journal committed by the consumption guest program (Solidity ABI-encoded).
+    /// The field order must match the abi.encode() in the EnergyAggregator#createBuyOrder().
+    /// (NOTE: The energy consumed in the window is not committed, so that the load profile of the buyer is not revealed)
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct ConsumptionJournal {
+        uint256 energyAmountToBeBought;
+        uint256 windowStart;
+        uint256 windowEnd;
+        uint256 currentTime;
+        uint256 maxMonitoredTimeAge;
+        bytes32 monitoredMerkleRoot;
+        bytes32 meterPublicKeyHash;
+        address buyer;
+        uint256 chainId;
+    }
+}
+
+impl ConsumptionJournal {
+    /// Decode the journal bytes committed by the consumption guest program.
+    pub fn decode(journal: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        <Self as alloy_sol_types::SolValue>::abi_decode(journal, true)
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input and journal types shared by the guest programs, the app and the tests.
+
+#![no_std]
+
+extern crate alloc;
+
+mod aggregate;
+mod constraints;
+mod consumption;
+pub mod merkle;
+mod nullifier;
+mod settlement;
+mod sold_commitment;
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub use aggregate::{AggregateJournal, AggregatorInput};
+pub use constraints::check_meter_readings;
+pub use consumption::{ConsumptionInput, ConsumptionJournal};
+pub use nullifier::compute_nullifier;
+pub use settlement::{SettlementInput, SettlementJournal};
+pub use sold_commitment::{compute_sold_commitment, INITIAL_SOLD_COMMITMENT};
+
+/// A reading of the smart meter, which is committed to as a leaf of the monitored merkle tree.
+pub trait Reading {
+    /// The time when the reading was taken (UTC timestamp).
+    fn timestamp(&self) -> u64;
+    /// Hash the reading into a leaf of the monitored merkle tree.
+    fn leaf(&self) -> [u8; 32];
+}
+
+/// A reading of the cumulative (export) register of the smart meter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterReading {
+    /// The time when the register was read (UTC timestamp).
+    pub timestamp: u64,
+    /// The cumulative register value (Unit: Wh).
+    pub register: u64,
+}
+
+impl Reading for MeterReading {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// sha256(timestamp || register), where both values are encoded as 8-byte big-endian integers.
+    fn leaf(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.timestamp.to_be_bytes());
+        hasher.update(self.register.to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// A reading of both cumulative registers of a (bidirectional) smart meter of a prosumer, taken at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetMeterReading {
+    /// The time when the registers were read (UTC timestamp).
+    pub timestamp: u64,
+    /// The cumulative export register value, i.e. the energy fed into the grid (Unit: Wh).
+    pub export_register: u64,
+    /// The cumulative import register value, i.e. the energy drawn from the grid (Unit: Wh).
+    pub import_register: u64,
+}
+
+impl Reading for NetMeterReading {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// sha256(timestamp || export_register || import_register), where all values are encoded as 8-byte big-endian integers.
+    fn leaf(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.timestamp.to_be_bytes());
+        hasher.update(self.export_register.to_be_bytes());
+        hasher.update(self.import_register.to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// A meter reading with the sibling path from its leaf up to the monitored merkle root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterReadingProof<R = MeterReading> {
+    /// The meter reading (leaf).
+    pub reading: R,
+    /// Sibling hashes from the leaf up to the monitored merkle root.
+    pub hash_path: Vec<[u8; 32]>,
+}
+
+/// The series of meter readings in the window (ordered by timestamp), with their merkle inclusion proofs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeterReadings {
+    /// Readings of the export register only. The energy available to be sold is the gross production,
+    /// i.e. the difference between the last and the first register values.
+    Gross(Vec<MeterReadingProof<MeterReading>>),
+    /// Readings of both the export and the import registers (net metering). The energy available to be sold
+    /// is the net surplus, i.e. the net export minus the net import over the window.
+    Net(Vec<MeterReadingProof<NetMeterReading>>),
+}
+
+/// The input data of the smart-meter guest program, written by the host as a single value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartMeterInput {
+    /// The energy amount that the producer wants to sell (Unit: kWh).
+    pub amount_of_energy_to_be_sold: u64,
+    /// Start of the window in which the energy was produced (UTC timestamp).
+    pub window_start: u64,
+    /// End of the window in which the energy was produced (UTC timestamp).
+    pub window_end: u64,
+    /// The time when the freshness of the window is checked (UTC timestamp).
+    pub current_time: u64,
+    /// Maximum age (in seconds) of the window_end at the current_time.
+    pub max_monitored_time_age: u64,
+    /// Merkle root of the meter readings.
+    pub monitored_merkle_root: [u8; 32],
+    /// The series of meter readings in the window (ordered by timestamp), with their merkle inclusion proofs.
+    pub meter_readings: MeterReadings,
+    /// SEC1-encoded secp256k1 public key of the smart meter.
+    pub meter_public_key: Vec<u8>,
+    /// ECDSA signature (r || s) of the monitored_merkle_root, signed by the smart meter.
+    pub meter_signature: Vec<u8>,
+    /// Address of the seller, who submits the sell order to the EnergyAggregator contract.
+    pub seller: [u8; 20],
+    /// Chain ID of the chain where the EnergyAggregator contract is deployed.
+    pub chain_id: u64,
+    /// Nullifier of the sell order (See: [compute_nullifier]).
+    pub monitored_nullifier: [u8; 32],
+    /// The energy amount sold so far from the meter readings of the monitored_merkle_root (Unit: kWh).
+    pub previously_sold: u64,
+    /// The latest commitment to the previously_sold, which is stored by the EnergyAggregator contract (See: [compute_sold_commitment]).
+    pub previous_sold_commitment: [u8; 32],
+}
+
+alloy_sol_types::sol! {
+    /// The journal committed by the smart-meter guest program (Solidity ABI-encoded).
+    /// The field order must match the abi.encode() in the EnergyAggregator#createSellOrder().
+    /// (NOTE: The energy available in the window is not committed, so that it is not revealed)
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct SmartMeterJournal {
+        uint256 energyAmountToBeSold;
+        uint256 windowStart;
+        uint256 windowEnd;
+        uint256 currentTime;
+        uint256 maxMonitoredTimeAge;
+        bytes32 monitoredMerkleRoot;
+        bytes32 monitoredNullifier;
+        bytes32 meterPublicKeyHash;
+        bytes32 previousSoldCommitment;
+        bytes32 soldCommitment;
+        address seller;
+        uint256 chainId;
+    }
+}
+
+impl SmartMeterJournal {
+    /// Decode the journal bytes committed by the smart-meter guest program.
+    pub fn decode(journal: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        <Self as alloy_sol_types::SolValue>::abi_decode(journal, true)
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The merkle tree over the meter readings, whose root is signed by the smart meter.
+//!
+//! Each pair of nodes is sorted before hashing, so a hash path does not need to carry left/right positions.
+//! A node without a sibling is carried up to the next level as it is.
+
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+/// Hash a pair of nodes into their parent node: sha256(min(a, b) || max(a, b)).
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Recompute the merkle root from a leaf and its sibling path (from the leaf level up to the root).
+pub fn compute_merkle_root(leaf: [u8; 32], hash_path: &[[u8; 32]]) -> [u8; 32] {
+    hash_path.iter().fold(leaf, |node, sibling| hash_pair(&node, sibling))
+}
+
+/// A merkle tree built by the host from all the leaves (Used to derive the root and the hash paths).
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    /// The levels of the tree, from the leaves up to the root.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build a merkle tree from the given leaves. Returns `None` if there are no leaves.
+    pub fn new(leaves: Vec<[u8; 32]>) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+        let mut levels = Vec::from([leaves]);
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_pair(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+        Some(Self { levels })
+    }
+
+    /// The merkle root of the tree.
+    pub fn root(&self) -> [u8; 32] {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// The sibling path of the leaf at the given index. Returns `None` if the index is out of range.
+    pub fn hash_path(&self, index: usize) -> Option<Vec<[u8; 32]>> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut hash_path = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                hash_path.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(hash_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(byte: u8) -> [u8; 32] {
+        [byte; 32]
+    }
+
+    #[test]
+    fn hash_pair_sorts_the_nodes() {
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&leaf(1));
+        preimage.extend_from_slice(&leaf(2));
+        let expected: [u8; 32] = Sha256::digest(&preimage).into();
+
+        assert_eq!(hash_pair(&leaf(1), &leaf(2)), expected);
+        assert_eq!(hash_pair(&leaf(2), &leaf(1)), expected);
+    }
+
+    #[test]
+    fn odd_node_is_carried_up() {
+        let tree = MerkleTree::new(Vec::from([leaf(1), leaf(2), leaf(3)])).unwrap();
+        // @dev - The 3rd leaf has no sibling on the leaf level, so it is hashed with the parent of the first two leaves.
+        assert_eq!(tree.root(), hash_pair(&hash_pair(&leaf(1), &leaf(2)), &leaf(3)));
+        assert_eq!(tree.hash_path(2).unwrap(), Vec::from([hash_pair(&leaf(1), &leaf(2))]));
+        assert_eq!(tree.hash_path(0).unwrap(), Vec::from([leaf(2), leaf(3)]));
+    }
+
+    #[test]
+    fn hash_paths_recompute_the_root() {
+        let leaves: Vec<[u8; 32]> = (1..=5).map(leaf).collect();
+        let tree = MerkleTree::new(leaves.clone()).unwrap();
+        for (index, leaf) in leaves.into_iter().enumerate() {
+            assert_eq!(compute_merkle_root(leaf, &tree.hash_path(index).unwrap()), tree.root());
+        }
+        assert!(tree.hash_path(5).is_none());
+    }
+
+    #[test]
+    fn single_leaf_is_the_root() {
+        let tree = MerkleTree::new(Vec::from([leaf(1)])).unwrap();
+        assert_eq!(tree.root(), leaf(1));
+        assert!(tree.hash_path(0).unwrap().is_empty());
+        assert!(MerkleTree::new(Vec::new()).is_none());
+    }
+}
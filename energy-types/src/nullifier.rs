@@ -0,0 +1,94 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The canonical nullifier of a sell order, computed in the same way by the guest program, the app and the tests.
+
+use sha2::{Digest, Sha256};
+
+/// Compute the nullifier of a sell order:
+/// sha256(amount_of_energy_to_be_sold || window_start || window_end || monitored_merkle_root || seller || chain_id || previous_sold_commitment),
+/// where the integers are encoded as 8-byte big-endian values, the merkle root and the commitment as their 32 raw bytes
+/// and the seller as its 20-byte address.
+///
+/// The seller and the chain ID are bound into the nullifier, so that a proof can neither be submitted
+/// from another address nor replayed on another chain. The previous sold commitment is bound as well, so that
+/// the chained sell orders of the same amount from the same meter readings have distinct nullifiers.
+pub fn compute_nullifier(
+    amount_of_energy_to_be_sold: u64,
+    window_start: u64,
+    window_end: u64,
+    monitored_merkle_root: &[u8; 32],
+    seller: &[u8; 20],
+    chain_id: u64,
+    previous_sold_commitment: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(amount_of_energy_to_be_sold.to_be_bytes());
+    hasher.update(window_start.to_be_bytes());
+    hasher.update(window_end.to_be_bytes());
+    hasher.update(monitored_merkle_root);
+    hasher.update(seller);
+    hasher.update(chain_id.to_be_bytes());
+    hasher.update(previous_sold_commitment);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use alloy_primitives::{Address, B256};
+    use alloy_sol_types::SolValue;
+
+    const ROOT: [u8; 32] = [0x11; 32];
+    const SELLER: [u8; 20] = [0x22; 20];
+    const PREVIOUS_SOLD_COMMITMENT: [u8; 32] = [0x33; 32];
+
+    #[test]
+    fn nullifier_hashes_the_fields_in_order() {
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&100u64.to_be_bytes());
+        preimage.extend_from_slice(&1_000u64.to_be_bytes());
+        preimage.extend_from_slice(&2_000u64.to_be_bytes());
+        preimage.extend_from_slice(&ROOT);
+        preimage.extend_from_slice(&SELLER);
+        preimage.extend_from_slice(&31_337u64.to_be_bytes());
+        preimage.extend_from_slice(&PREVIOUS_SOLD_COMMITMENT);
+        assert_eq!(preimage.len(), 8 + 8 + 8 + 32 + 20 + 8 + 32);
+
+        let expected: [u8; 32] = Sha256::digest(&preimage).into();
+        assert_eq!(compute_nullifier(100, 1_000, 2_000, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT), expected);
+    }
+
+    #[test]
+    fn nullifier_matches_the_solidity_packed_encoding() {
+        // @dev - sha256(abi.encodePacked(uint64, uint64, uint64, bytes32, address, uint64, bytes32)) in Solidity.
+        let packed = (100u64, 1_000u64, 2_000u64, B256::from(ROOT), Address::from(SELLER), 31_337u64, B256::from(PREVIOUS_SOLD_COMMITMENT)).abi_encode_packed();
+        let expected: [u8; 32] = Sha256::digest(&packed).into();
+        assert_eq!(compute_nullifier(100, 1_000, 2_000, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT), expected);
+    }
+
+    #[test]
+    fn nullifier_binds_every_field() {
+        let nullifier = compute_nullifier(100, 1_000, 2_000, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT);
+        assert_ne!(nullifier, compute_nullifier(99, 1_000, 2_000, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_001, 2_000, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_000, 2_001, &ROOT, &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_000, 2_000, &[0x12; 32], &SELLER, 31_337, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_000, 2_000, &ROOT, &[0x23; 20], 31_337, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_000, 2_000, &ROOT, &SELLER, 1, &PREVIOUS_SOLD_COMMITMENT));
+        assert_ne!(nullifier, compute_nullifier(100, 1_000, 2_000, &ROOT, &SELLER, 31_337, &[0u8; 32]));
+    }
+}
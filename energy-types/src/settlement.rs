@@ -0,0 +1,74 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input and journal types of the settlement guest program, which proves how much of the traded energy of a matched
+//! sell order was actually exported by the seller's smart meter in the delivery window.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MeterReadingProof;
+
+/// The input data of the settlement guest program, written by the host as a single value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementInput {
+    /// The ID of the matched sell order (= sellOrderId in the EnergyAggregator contract).
+    pub order_id: u64,
+    /// The energy amount traded by the matched sell order (Unit: kWh).
+    pub traded_amount: u64,
+    /// Start of the window in which the traded energy is delivered (UTC timestamp).
+    pub delivery_window_start: u64,
+    /// End of the window in which the traded energy is delivered (UTC timestamp).
+    pub delivery_window_end: u64,
+    /// Merkle root of the (post-trade) meter readings of the seller's smart meter.
+    pub monitored_merkle_root: [u8; 32],
+    /// The series of readings of the cumulative (export) register in the delivery window (ordered by timestamp), with their
+    /// merkle inclusion proofs. The energy exported in the delivery window is the difference between the last and the first register values.
+    pub meter_readings: Vec<MeterReadingProof>,
+    /// SEC1-encoded secp256k1 public key of the seller's smart meter.
+    pub meter_public_key: Vec<u8>,
+    /// ECDSA signature (r || s) of the monitored_merkle_root, signed by the seller's smart meter.
+    pub meter_signature: Vec<u8>,
+    /// Address of the seller, who settles the sell order in the EnergyAggregator contract.
+    pub seller: [u8; 20],
+    /// Chain ID of the chain where the EnergyAggregator contract is deployed.
+    pub chain_id: u64,
+}
+
+alloy_sol_types::sol! {
+    /// The journal committed by the settlement guest program (Solidity ABI-encoded).
+    /// The field order must match the abi.encode() in the EnergyAggregator#settleSellOrder().
+    /// (NOTE: The delivered amount is capped at the traded amount, so that the energy exported beyond the trade is not revealed)
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct SettlementJournal {
+        uint256 orderId;
+        uint256 tradedAmount;
+        uint256 delivered;
+        uint256 shortfall;
+        uint256 deliveryWindowStart;
+        uint256 deliveryWindowEnd;
+        bytes32 monitoredMerkleRoot;
+        bytes32 meterPublicKeyHash;
+        address seller;
+        uint256 chainId;
+    }
+}
+
+impl SettlementJournal {
+    /// Decode the journal bytes committed by the settlement guest program.
+    pub fn decode(journal: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        <Self as alloy_sol_types::SolValue>::abi_decode(journal, true)
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The commitment to the energy amount sold so far from the meter readings of a monitored merkle root.
+//!
+//! Each sell order must chain from the latest commitment of its monitored merkle root, which is stored by
+//! the EnergyAggregator contract, so that the same meter readings can not be sold more than once in total.
+
+use sha2::{Digest, Sha256};
+
+/// The commitment before any energy is sold from the meter readings of a monitored merkle root.
+/// (NOTE: This is bytes32(0), which is the default value of the EnergyAggregator#soldCommitments())
+pub const INITIAL_SOLD_COMMITMENT: [u8; 32] = [0u8; 32];
+
+/// Compute the commitment to the energy amount sold so far: sha256(monitored_merkle_root || sold_so_far),
+/// where the sold_so_far is encoded as an 8-byte big-endian value.
+/// If nothing is sold so far, this is the [INITIAL_SOLD_COMMITMENT].
+pub fn compute_sold_commitment(monitored_merkle_root: &[u8; 32], sold_so_far: u64) -> [u8; 32] {
+    if sold_so_far == 0 {
+        return INITIAL_SOLD_COMMITMENT;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(monitored_merkle_root);
+    hasher.update(sold_so_far.to_be_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use alloy_primitives::B256;
+    use alloy_sol_types::SolValue;
+
+    const ROOT: [u8; 32] = [0x11; 32];
+
+    #[test]
+    fn nothing_sold_is_the_initial_sold_commitment() {
+        assert_eq!(compute_sold_commitment(&ROOT, 0), INITIAL_SOLD_COMMITMENT);
+        assert_eq!(INITIAL_SOLD_COMMITMENT, [0u8; 32]);
+    }
+
+    #[test]
+    fn sold_commitment_hashes_the_root_and_the_sold_amount() {
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&ROOT);
+        preimage.extend_from_slice(&150u64.to_be_bytes());
+        let expected: [u8; 32] = Sha256::digest(&preimage).into();
+        assert_eq!(compute_sold_commitment(&ROOT, 150), expected);
+
+        // @dev - sha256(abi.encodePacked(bytes32, uint64)) in Solidity.
+        let packed = (B256::from(ROOT), 150u64).abi_encode_packed();
+        assert_eq!(Sha256::digest(&packed).as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn sold_commitment_binds_the_root_and_the_sold_amount() {
+        let sold_commitment = compute_sold_commitment(&ROOT, 150);
+        assert_ne!(sold_commitment, compute_sold_commitment(&ROOT, 151));
+        assert_ne!(sold_commitment, compute_sold_commitment(&[0x12; 32], 150));
+        assert_ne!(sold_commitment, INITIAL_SOLD_COMMITMENT);
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use alloy_primitives::{ B256, U256 };
+use alloy_sol_types::SolValue;
+use energy_types::{ AggregateJournal, AggregatorInput, SmartMeterJournal };
+use risc0_zkvm::{ guest::env, sha::Digest };
+
+fn main() {
+    // Read the input data for this application (= Host).
+    let input: AggregatorInput = env::read();
+
+    // Decode and parse the input
+    let AggregatorInput {
+        smart_meter_image_id,
+        journals,
+    } = input;
+
+    assert!(!journals.is_empty(), "At least 1 smart-meter journal is required to be aggregated.");
+
+    let mut smart_meter_journals: Vec<SmartMeterJournal> = Vec::with_capacity(journals.len());
+    for journal in &journals {
+        // Constraint: Verify the receipt of the smart-meter guest program with this journal (composition).
+        // (NOTE: The receipt itself is added as an assumption by the host, and is resolved when this receipt is proven)
+        env::verify(smart_meter_image_id, journal).expect("The smart-meter receipt is not verified.");
+        smart_meter_journals.push(SmartMeterJournal::decode(journal).expect("The smart-meter journal can not be decoded."));
+    }
+
+    // Constraint: Check whether all the smart-meter journals are bound to the same seller (= the operator of the microgrid),
+    // chain and freshness rule, which are checked only once by the EnergyAggregator contract.
+    let first_journal = &smart_meter_journals[0];
+    for smart_meter_journal in &smart_meter_journals {
+        assert!(smart_meter_journal.seller == first_journal.seller, "The smart-meter journals must be bound to the same seller.");
+        assert!(smart_meter_journal.chainId == first_journal.chainId, "The smart-meter journals must be bound to the same chain.");
+        assert!(smart_meter_journal.maxMonitoredTimeAge == first_journal.maxMonitoredTimeAge, "The smart-meter journals must have the same max_monitored_time_age.");
+    }
+
+    // Constraint: Check whether the monitored_nullifiers of the smart-meter journals are distinct,
+    // so that the same sell order can not be counted more than once in the aggregated amount.
+    let mut monitored_nullifiers = BTreeSet::new();
+    for smart_meter_journal in &smart_meter_journals {
+        assert!(monitored_nullifiers.insert(smart_meter_journal.monitoredNullifier), "The monitored_nullifiers of the smart-meter journals must be distinct.");
+    }
+
+    // Sum the amounts of energy to be sold. The window covers all the windows, and the current_time is the oldest one,
+    // so that the freshness check of the EnergyAggregator contract holds for every smart-meter journal.
+    let energy_amount_to_be_sold = smart_meter_journals.iter().fold(U256::ZERO, |sum, smart_meter_journal| sum + smart_meter_journal.energyAmountToBeSold);
+    let window_start = smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.windowStart).min().unwrap();
+    let window_end = smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.windowEnd).max().unwrap();
+    let current_time = smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.currentTime).min().unwrap();
+
+    // Commit the "journal" that will be received by the application contract.
+    // Journal is encoded using Solidity ABI for easy decoding in the app contract.
+    // (NOTE: The smart_meter_image_id is committed, so that the EnergyAggregator contract can check it is the ImageID.SMART_METER_ID)
+    let journal = AggregateJournal {
+        smartMeterImageId: B256::from_slice(Digest::from(smart_meter_image_id).as_bytes()),
+        energyAmountToBeSold: energy_amount_to_be_sold,
+        windowStart: window_start,
+        windowEnd: window_end,
+        currentTime: current_time,
+        maxMonitoredTimeAge: first_journal.maxMonitoredTimeAge,
+        monitoredMerkleRoots: smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.monitoredMerkleRoot).collect(),
+        monitoredNullifiers: smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.monitoredNullifier).collect(),
+        meterPublicKeyHashes: smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.meterPublicKeyHash).collect(),
+        previousSoldCommitments: smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.previousSoldCommitment).collect(),
+        soldCommitments: smart_meter_journals.iter().map(|smart_meter_journal| smart_meter_journal.soldCommitment).collect(),
+        seller: first_journal.seller,
+        chainId: first_journal.chainId,
+    };
+    env::commit_slice(&journal.abi_encode());
+}
@@ -32,14 +32,37 @@ fn main() {
     });
 
     // Generate Rust source files for the methods crate.
-    let guests = embed_methods_with_options(HashMap::from([(
-        "smart-meter",
-        //"is-even",
-        GuestOptions {
-            features: Vec::new(),
-            use_docker,
-        },
-    )]));
+    let guests = embed_methods_with_options(HashMap::from([
+        (
+            "smart-meter",
+            //"is-even",
+            GuestOptions {
+                features: Vec::new(),
+                use_docker: use_docker.clone(),
+            },
+        ),
+        (
+            "aggregator",
+            GuestOptions {
+                features: Vec::new(),
+                use_docker: use_docker.clone(),
+            },
+        ),
+        (
+            "consumption",
+            GuestOptions {
+                features: Vec::new(),
+                use_docker: use_docker.clone(),
+            },
+        ),
+        (
+            "settlement",
+            GuestOptions {
+                features: Vec::new(),
+                use_docker,
+            },
+        ),
+    ]));
 
     // Generate Solidity source files for use with Forge.
     let solidity_opts = risc0_build_ethereum::Options::default()
@@ -0,0 +1,85 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy_primitives::{ Address, U256, B256 };
+use alloy_sol_types::SolValue;
+use energy_types::{ check_meter_readings, ConsumptionInput, ConsumptionJournal };
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use risc0_zkvm::guest::env;
+
+use sha2::{Digest, Sha256};
+
+fn main() {
+    // Read the input data for this application (= Host).
+    let input: ConsumptionInput = env::read();
+
+    // Decode and parse the input
+    let ConsumptionInput {
+        amount_of_energy_to_be_bought,
+        window_start,
+        window_end,
+        current_time,
+        max_monitored_time_age,
+        monitored_merkle_root,
+        meter_readings,
+        meter_public_key,
+        meter_signature,
+        buyer,
+        chain_id,
+    } = input;
+
+    // Constraint: Check whether the monitored_merkle_root is signed by the buyer's smart meter's key (device attestation).
+    // The hash of the meter's public key is committed, so that the contract can check it is a registered smart meter.
+    let meter_verifying_key = VerifyingKey::from_sec1_bytes(&meter_public_key).expect("The meter_public_key is not a valid secp256k1 public key.");
+    let meter_signature = Signature::from_slice(&meter_signature).expect("The meter_signature is not a valid ECDSA signature.");
+    assert!(meter_verifying_key.verify(&monitored_merkle_root, &meter_signature).is_ok(), "The meter reading is not signed by the smart meter.");
+    let meter_public_key_hash: [u8; 32] = Sha256::digest(meter_verifying_key.to_encoded_point(true).as_bytes()).into();
+
+    // Constraint: Check the inclusion, the window and the ordering of the meter readings (See: check_meter_readings()).
+    check_meter_readings(&meter_readings, &monitored_merkle_root, window_start, window_end);
+
+    // Constraint: Check whether the window is fresh, i.e. the window_end is not in the future and
+    // is within the max_monitored_time_age before the current_time.
+    assert!(window_end <= current_time, "The window_end must not be in the future.");
+    assert!(current_time - window_end <= max_monitored_time_age, "The meter reading is too old to be used.");
+
+    // Constraint: Check whether the cumulative (import) register never decreases.
+    for pair in meter_readings.windows(2) {
+        assert!(pair[0].reading.register <= pair[1].reading.register, "The cumulative register of the meter readings must not decrease.");
+    }
+
+    // Compute the energy consumed in the window (= the consumption baseline) from the cumulative register (Unit: Wh -> kWh).
+    let first_reading = &meter_readings[0].reading;
+    let last_reading = &meter_readings[meter_readings.len() - 1].reading;
+    let energy_consumed_in_window = (last_reading.register - first_reading.register) / 1000;
+
+    // Constraint: Check whether the energy consumed in the window is greater than the amount of energy to be bought.
+    assert!(energy_consumed_in_window >= amount_of_energy_to_be_bought, "energy consumed in the window must be greater than the amount of energy to be bought");
+
+    // Commit the "journal" that will be received by the application contract.
+    // Journal is encoded using Solidity ABI for easy decoding in the app contract.
+    // (NOTE: The buyer and chain_id are checked against the msg.sender and block.chainid by the EnergyAggregator contract)
+    let journal = ConsumptionJournal {
+        energyAmountToBeBought: U256::from(amount_of_energy_to_be_bought),
+        windowStart: U256::from(window_start),
+        windowEnd: U256::from(window_end),
+        currentTime: U256::from(current_time),
+        maxMonitoredTimeAge: U256::from(max_monitored_time_age),
+        monitoredMerkleRoot: B256::from(monitored_merkle_root),
+        meterPublicKeyHash: B256::from(meter_public_key_hash),
+        buyer: Address::from(buyer),
+        chainId: U256::from(chain_id),
+    };
+    env::commit_slice(&journal.abi_encode());
+}
@@ -0,0 +1,82 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy_primitives::{ Address, U256, B256 };
+use alloy_sol_types::SolValue;
+use energy_types::{ check_meter_readings, SettlementInput, SettlementJournal };
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use risc0_zkvm::guest::env;
+
+use sha2::{Digest, Sha256};
+
+fn main() {
+    // Read the input data for this application (= Host).
+    let input: SettlementInput = env::read();
+
+    // Decode and parse the input
+    let SettlementInput {
+        order_id,
+        traded_amount,
+        delivery_window_start,
+        delivery_window_end,
+        monitored_merkle_root,
+        meter_readings,
+        meter_public_key,
+        meter_signature,
+        seller,
+        chain_id,
+    } = input;
+
+    // Constraint: Check whether the monitored_merkle_root is signed by the seller's smart meter's key (device attestation).
+    // The hash of the meter's public key is committed, so that the contract can check it is the smart meter of the sell order.
+    let meter_verifying_key = VerifyingKey::from_sec1_bytes(&meter_public_key).expect("The meter_public_key is not a valid secp256k1 public key.");
+    let meter_signature = Signature::from_slice(&meter_signature).expect("The meter_signature is not a valid ECDSA signature.");
+    assert!(meter_verifying_key.verify(&monitored_merkle_root, &meter_signature).is_ok(), "The meter reading is not signed by the smart meter.");
+    let meter_public_key_hash: [u8; 32] = Sha256::digest(meter_verifying_key.to_encoded_point(true).as_bytes()).into();
+
+    // Constraint: Check the inclusion, the delivery window and the ordering of the meter readings (See: check_meter_readings()).
+    check_meter_readings(&meter_readings, &monitored_merkle_root, delivery_window_start, delivery_window_end);
+
+    // Constraint: Check whether the cumulative (export) register never decreases.
+    for pair in meter_readings.windows(2) {
+        assert!(pair[0].reading.register <= pair[1].reading.register, "The cumulative register of the meter readings must not decrease.");
+    }
+
+    // Compute the energy exported in the delivery window from the cumulative register (Unit: Wh -> kWh).
+    let first_reading = &meter_readings[0].reading;
+    let last_reading = &meter_readings[meter_readings.len() - 1].reading;
+    let energy_exported_in_delivery_window = (last_reading.register - first_reading.register) / 1000;
+
+    // The delivered amount is capped at the traded amount, and the rest of the traded amount is the shortfall,
+    // which is refunded to the buyer by the EnergyAggregator contract.
+    let delivered = energy_exported_in_delivery_window.min(traded_amount);
+    let shortfall = traded_amount - delivered;
+
+    // Commit the "journal" that will be received by the application contract.
+    // Journal is encoded using Solidity ABI for easy decoding in the app contract.
+    // (NOTE: The order_id and the traded_amount are checked against the matched sell order by the EnergyAggregator contract)
+    let journal = SettlementJournal {
+        orderId: U256::from(order_id),
+        tradedAmount: U256::from(traded_amount),
+        delivered: U256::from(delivered),
+        shortfall: U256::from(shortfall),
+        deliveryWindowStart: U256::from(delivery_window_start),
+        deliveryWindowEnd: U256::from(delivery_window_end),
+        monitoredMerkleRoot: B256::from(monitored_merkle_root),
+        meterPublicKeyHash: B256::from(meter_public_key_hash),
+        seller: Address::from(seller),
+        chainId: U256::from(chain_id),
+    };
+    env::commit_slice(&journal.abi_encode());
+}
@@ -12,54 +12,126 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::num;
-use std::io::Read;
-
-use alloy_primitives::{ Uint, U256 };
-use alloy_sol_types::{ SolValue, Error, SolType };
+use alloy_primitives::{ Address, U256, B256 };
+use alloy_sol_types::SolValue;
+use energy_types::{ check_meter_readings, compute_nullifier, compute_sold_commitment, MeterReadings, SmartMeterInput, SmartMeterJournal };
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
 use risc0_zkvm::guest::env;
-
-use hex;
-use sha2::{digest::generic_array::GenericArray, Digest, Sha256};
+use sha2::{Digest, Sha256};
 
 fn main() {
     // Read the input data for this application (= Host).
-    let input_amount_of_energy_to_be_sold: u64 = env::read();
-    let input_total_exact_amount_of_energy_available: u64 = env::read();
-    let input_current_time: u64 = env::read();
-    let input_monitored_time: u64 = env::read();
-    let input_monitored_merkle_root: String = env::read();
-    //let input_monitored_hash_path: Vec<String> = Vec::<String>::new();
-    let input_monitored_nullifier: String = env::read();
+    let input: SmartMeterInput = env::read();
 
     // Calculate the monitored_nullifier from the input data and store it into the variable.
-    let mut hasher = Sha256::new();
-    hasher.update(input_amount_of_energy_to_be_sold.to_string().as_bytes());
-    hasher.update(input_monitored_time.to_string().as_bytes());
-    hasher.update(input_monitored_merkle_root.as_bytes());
-    let hash = hasher.finalize(); // Note that calling `finalize()` consumes hasher
-    let nullifier: String = hex::encode(hash); // Convert GenericArray<u8, N> to a hexadecimal string
+    let nullifier = compute_nullifier(
+        input.amount_of_energy_to_be_sold,
+        input.window_start,
+        input.window_end,
+        &input.monitored_merkle_root,
+        &input.seller,
+        input.chain_id,
+        &input.previous_sold_commitment,
+    );
 
     // Constraint: Check the input data of the monitored_nullifier.
-    assert!(input_monitored_nullifier == nullifier, "The input_monitored_nullifier is not correct.");
+    assert!(input.monitored_nullifier == nullifier, "The input_monitored_nullifier is not correct.");
 
     // Decode and parse the input
-    let amount_of_energy_to_be_sold = input_amount_of_energy_to_be_sold;
-    let total_exact_amount_of_energy_available = input_total_exact_amount_of_energy_available;
-    let current_time = input_current_time;
-    let monitored_time = input_monitored_time;
-    let monitored_merkle_root = input_monitored_merkle_root;
-    //let monitored_hash_path = input_monitored_hash_path;
-    let monitored_nullifier = input_monitored_nullifier;
+    let SmartMeterInput {
+        amount_of_energy_to_be_sold,
+        window_start,
+        window_end,
+        current_time,
+        max_monitored_time_age,
+        monitored_merkle_root,
+        meter_readings,
+        meter_public_key,
+        meter_signature,
+        seller,
+        chain_id,
+        monitored_nullifier,
+        previously_sold,
+        previous_sold_commitment,
+    } = input;
+
+    // Constraint: Check whether the monitored_merkle_root is signed by the smart meter's key (device attestation).
+    // The hash of the meter's public key is committed, so that the contract can check it is a registered smart meter.
+    let meter_verifying_key = VerifyingKey::from_sec1_bytes(&meter_public_key).expect("The meter_public_key is not a valid secp256k1 public key.");
+    let meter_signature = Signature::from_slice(&meter_signature).expect("The meter_signature is not a valid ECDSA signature.");
+    assert!(meter_verifying_key.verify(&monitored_merkle_root, &meter_signature).is_ok(), "The meter reading is not signed by the smart meter.");
+    let meter_public_key_hash: [u8; 32] = Sha256::digest(meter_verifying_key.to_encoded_point(true).as_bytes()).into();
+
+    // Constraint: Check the inclusion, the window and the ordering of the meter readings (See: check_meter_readings()).
+    match &meter_readings {
+        MeterReadings::Gross(meter_readings) => check_meter_readings(meter_readings, &monitored_merkle_root, window_start, window_end),
+        MeterReadings::Net(meter_readings) => check_meter_readings(meter_readings, &monitored_merkle_root, window_start, window_end),
+    }
+
+    // Constraint: Check whether the window is fresh, i.e. the window_end is not in the future and
+    // is within the max_monitored_time_age before the current_time.
+    // (NOTE: The current_time and max_monitored_time_age are committed, so that the EnergyAggregator contract can compare them with the block time)
+    assert!(window_end <= current_time, "The window_end must not be in the future.");
+    assert!(current_time - window_end <= max_monitored_time_age, "The meter reading is too old to be sold.");
+
+    // Constraint: Check whether the previous_sold_commitment is the commitment to the previously_sold.
+    // (NOTE: The previous_sold_commitment is committed, so that the EnergyAggregator contract can check it is the latest one of the monitored_merkle_root)
+    assert!(previous_sold_commitment == compute_sold_commitment(&monitored_merkle_root, previously_sold), "The previous_sold_commitment is not the commitment to the previously_sold.");
+    let sold_so_far = previously_sold.checked_add(amount_of_energy_to_be_sold).expect("The energy amount sold so far overflows.");
+
+    // Compute the energy available to be sold in the window from the cumulative register(s) (Unit: Wh -> kWh).
+    match &meter_readings {
+        MeterReadings::Gross(meter_readings) => {
+            // Constraint: Check whether the cumulative register never decreases.
+            for pair in meter_readings.windows(2) {
+                assert!(pair[0].reading.register <= pair[1].reading.register, "The cumulative register of the meter readings must not decrease.");
+            }
+            let first_reading = &meter_readings[0].reading;
+            let last_reading = &meter_readings[meter_readings.len() - 1].reading;
+            let energy_produced_in_window = (last_reading.register - first_reading.register) / 1000;
+
+            // Constraint: Check whether the energy produced in the window is greater than the amount of energy to be sold (including the previously sold amount).
+            assert!(energy_produced_in_window >= sold_so_far, "energy produced in the window must be greater than the amount of energy to be sold");
+        }
+        MeterReadings::Net(meter_readings) => {
+            // Constraint: Check whether both of the cumulative registers never decrease.
+            for pair in meter_readings.windows(2) {
+                assert!(pair[0].reading.export_register <= pair[1].reading.export_register, "The cumulative register of the meter readings must not decrease.");
+                assert!(pair[0].reading.import_register <= pair[1].reading.import_register, "The cumulative register of the meter readings must not decrease.");
+            }
+            let first_reading = &meter_readings[0].reading;
+            let last_reading = &meter_readings[meter_readings.len() - 1].reading;
+            let net_export_in_window = last_reading.export_register - first_reading.export_register;
+            let net_import_in_window = last_reading.import_register - first_reading.import_register;
+
+            // @dev - The self-consumption (import) is subtracted from the production (export). If the prosumer imported
+            //        more than it exported in the window, there is no surplus to be sold.
+            let net_surplus_in_window = net_export_in_window.saturating_sub(net_import_in_window) / 1000;
+
+            // Constraint: Check whether the net surplus in the window (not the gross production) is greater than the amount of energy to be sold (including the previously sold amount).
+            assert!(net_surplus_in_window >= sold_so_far, "net surplus in the window must be greater than the amount of energy to be sold");
+        }
+    }
 
-    // Constraint: Check whether the total exact amount of energy available is greater than the amount of energy to be sold.
-    assert!(total_exact_amount_of_energy_available >= amount_of_energy_to_be_sold, "total exact amount of energy available must be greater than the amount of energy to be sold");
+    // The updated commitment, from which the next sell order of the monitored_merkle_root must chain.
+    let sold_commitment = compute_sold_commitment(&monitored_merkle_root, sold_so_far);
 
     // Commit the "journal" that will be received by the application contract.
     // Journal is encoded using Solidity ABI for easy decoding in the app contract.
-    env::commit(&amount_of_energy_to_be_sold);
-    env::commit(&monitored_time);
-    env::commit(&monitored_merkle_root);
-    //env::commit(&monitored_hash_path);
-    env::commit(&monitored_nullifier);
+    // (NOTE: The seller and chain_id are checked against the msg.sender and block.chainid by the EnergyAggregator contract)
+    let journal = SmartMeterJournal {
+        energyAmountToBeSold: U256::from(amount_of_energy_to_be_sold),
+        windowStart: U256::from(window_start),
+        windowEnd: U256::from(window_end),
+        currentTime: U256::from(current_time),
+        maxMonitoredTimeAge: U256::from(max_monitored_time_age),
+        monitoredMerkleRoot: B256::from(monitored_merkle_root),
+        monitoredNullifier: B256::from(monitored_nullifier),
+        meterPublicKeyHash: B256::from(meter_public_key_hash),
+        previousSoldCommitment: B256::from(previous_sold_commitment),
+        soldCommitment: B256::from(sold_commitment),
+        seller: Address::from(seller),
+        chainId: U256::from(chain_id),
+    };
+    env::commit_slice(&journal.abi_encode());
 }
@@ -12,4 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+// The ELF and the image ID of each guest program (i.e. SMART_METER_ELF / SMART_METER_ID, AGGREGATOR_ELF / AGGREGATOR_ID,
+// CONSUMPTION_ELF / CONSUMPTION_ID and SETTLEMENT_ELF / SETTLEMENT_ID), which are generated by the build.rs.
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+
+pub use energy_types::{AggregateJournal, AggregatorInput, ConsumptionInput, ConsumptionJournal, SettlementInput, SettlementJournal, SmartMeterInput, SmartMeterJournal};
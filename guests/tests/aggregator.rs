@@ -0,0 +1,79 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy_primitives::{Address, B256, U256};
+use common::*;
+use energy_types::{AggregateJournal, AggregatorInput, MeterReading, MeterReadings, SmartMeterInput};
+use guests::{AGGREGATOR_ELF, SMART_METER_ELF, SMART_METER_ID};
+use risc0_zkvm::{default_prover, sha::Digest, ExecutorEnv, ProverOpts, Receipt};
+
+/// Meter readings of another smart meter in the microgrid, which produced 500 kWh in the window.
+fn other_meter_readings_in_window() -> Vec<MeterReading> {
+    vec![
+        MeterReading { timestamp: WINDOW_START, register: 2_000_000 },
+        MeterReading { timestamp: WINDOW_END, register: 2_500_000 },
+    ]
+}
+
+/// Prove the smart-meter guest program with a succinct receipt, which is added as an assumption of the aggregator.
+fn prove_smart_meter_succinct(input: &SmartMeterInput) -> Receipt {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .unwrap()
+        .build()
+        .unwrap();
+    default_prover().prove_with_opts(env, SMART_METER_ELF, &ProverOpts::succinct()).unwrap().receipt
+}
+
+/// Prove the aggregator guest program, which verifies the given smart-meter receipts.
+fn prove_aggregator(smart_meter_receipts: Vec<Receipt>) -> Receipt {
+    let input = AggregatorInput {
+        smart_meter_image_id: SMART_METER_ID,
+        journals: smart_meter_receipts.iter().map(|receipt| receipt.journal.bytes.clone()).collect(),
+    };
+
+    let mut env_builder = ExecutorEnv::builder();
+    for receipt in smart_meter_receipts {
+        env_builder.add_assumption(receipt);
+    }
+    let env = env_builder.write(&input).unwrap().build().unwrap();
+    default_prover().prove(env, AGGREGATOR_ELF).unwrap().receipt
+}
+
+#[test]
+fn proves_aggregated_amount_of_smart_meters() {
+    let input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let other_input = build_input(300, &other_meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_aggregator(vec![prove_smart_meter_succinct(&input), prove_smart_meter_succinct(&other_input)]);
+
+    let decoded_journal = AggregateJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.smartMeterImageId, B256::from_slice(Digest::from(SMART_METER_ID).as_bytes()));
+    assert_eq!(decoded_journal.energyAmountToBeSold, U256::from(800 + 300)); // @dev - Only the sum is committed.
+    assert_eq!(decoded_journal.monitoredMerkleRoots, vec![B256::from(input.monitored_merkle_root), B256::from(other_input.monitored_merkle_root)]);
+    assert_eq!(decoded_journal.monitoredNullifiers, vec![B256::from(input.monitored_nullifier), B256::from(other_input.monitored_nullifier)]);
+    assert_eq!(decoded_journal.seller, Address::from(SELLER));
+    assert_eq!(decoded_journal.chainId, U256::from(CHAIN_ID));
+}
+
+#[test]
+#[should_panic(expected = "The monitored_nullifiers of the smart-meter journals must be distinct.")]
+fn rejects_same_smart_meter_receipt_aggregated_twice() {
+    let input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let receipt = prove_smart_meter_succinct(&input);
+
+    let _receipt = prove_aggregator(vec![receipt.clone(), receipt]); // @dev - The same sell order is counted twice.
+}
@@ -0,0 +1,138 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by the tests of the guest programs.
+
+#![allow(dead_code)]
+
+use energy_types::{compute_nullifier, merkle::MerkleTree, MeterReading, MeterReadingProof, MeterReadings, NetMeterReading, Reading, SmartMeterInput, INITIAL_SOLD_COMMITMENT};
+use guests::SMART_METER_ELF;
+use risc0_zkvm::{default_prover, ExecutorEnv, Receipt};
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+/// Secret key of the smart meter used in the tests (NOTE: Only for testing).
+pub const METER_SECRET_KEY: [u8; 32] = [0x11; 32];
+
+/// Address of the seller and the chain ID (Ethereum Sepolia) used in the tests.
+pub const SELLER: [u8; 20] = [0x22; 20];
+pub const CHAIN_ID: u64 = 11155111;
+
+/// The window in which the energy was produced, and the time when its freshness is checked.
+pub const WINDOW_START: u64 = 1740638030; // @dev - UTC timestamp (2025-02-27 / 06:33:50)
+pub const WINDOW_END: u64 = 1740641630;   // @dev - UTC timestamp (2025-02-27 / 07:33:50)
+pub const CURRENT_TIME: u64 = 1740641690; // @dev - UTC timestamp (2025-02-27 / 07:34:50)
+pub const MAX_MONITORED_TIME_AGE: u64 = 3600; // @dev - 1 hour
+
+/// Meter readings (cumulative register in Wh) every 20 minutes in the window, which produced 1100 kWh in total.
+pub fn meter_readings_in_window() -> Vec<MeterReading> {
+    vec![
+        MeterReading { timestamp: WINDOW_START, register: 5_000_000 },
+        MeterReading { timestamp: WINDOW_START + 1200, register: 5_400_000 },
+        MeterReading { timestamp: WINDOW_START + 2400, register: 5_700_000 },
+        MeterReading { timestamp: WINDOW_END, register: 6_100_000 },
+    ]
+}
+
+/// Net meter readings (cumulative export and import registers in Wh) of a prosumer every 20 minutes in the window,
+/// which exported 1100 kWh and imported 500 kWh in total (i.e. the net surplus is 600 kWh).
+pub fn net_meter_readings_in_window() -> Vec<NetMeterReading> {
+    vec![
+        NetMeterReading { timestamp: WINDOW_START, export_register: 5_000_000, import_register: 2_000_000 },
+        NetMeterReading { timestamp: WINDOW_START + 1200, export_register: 5_400_000, import_register: 2_100_000 },
+        NetMeterReading { timestamp: WINDOW_START + 2400, export_register: 5_700_000, import_register: 2_300_000 },
+        NetMeterReading { timestamp: WINDOW_END, export_register: 6_100_000, import_register: 2_500_000 },
+    ]
+}
+
+/// Sign the merkle root of the meter readings with the smart meter's key, returning the SEC1-encoded public key and the signature (r || s).
+pub fn sign_merkle_root(secret_key: [u8; 32], monitored_merkle_root: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
+    let signing_key = SigningKey::from_bytes(&secret_key.into()).unwrap();
+    let signature: Signature = signing_key.sign(monitored_merkle_root);
+    (signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec(), signature.to_bytes().to_vec())
+}
+
+/// Build the input data of the guest program, whose merkle root, meter signature and nullifier are consistent with the given meter readings.
+/// (The `into_meter_readings` selects the mode, i.e. `MeterReadings::Gross` or `MeterReadings::Net`)
+pub fn build_input<R: Reading + Copy>(
+    amount_of_energy_to_be_sold: u64,
+    meter_readings: &[R],
+    into_meter_readings: fn(Vec<MeterReadingProof<R>>) -> MeterReadings,
+    window_start: u64,
+    window_end: u64,
+    current_time: u64,
+) -> SmartMeterInput {
+    let tree = MerkleTree::new(meter_readings.iter().map(R::leaf).collect()).unwrap();
+    let monitored_merkle_root = tree.root();
+    let (meter_public_key, meter_signature) = sign_merkle_root(METER_SECRET_KEY, &monitored_merkle_root);
+    let monitored_nullifier = compute_nullifier(amount_of_energy_to_be_sold, window_start, window_end, &monitored_merkle_root, &SELLER, CHAIN_ID, &INITIAL_SOLD_COMMITMENT);
+
+    SmartMeterInput {
+        amount_of_energy_to_be_sold,
+        window_start,
+        window_end,
+        current_time,
+        max_monitored_time_age: MAX_MONITORED_TIME_AGE,
+        monitored_merkle_root,
+        meter_readings: into_meter_readings(
+            meter_readings
+                .iter()
+                .enumerate()
+                .map(|(index, reading)| MeterReadingProof { reading: *reading, hash_path: tree.hash_path(index).unwrap() })
+                .collect(),
+        ),
+        meter_public_key,
+        meter_signature,
+        seller: SELLER,
+        chain_id: CHAIN_ID,
+        monitored_nullifier,
+        previously_sold: 0,
+        previous_sold_commitment: INITIAL_SOLD_COMMITMENT,
+    }
+}
+
+/// Chain the input data from the previous sell orders of the same meter readings, which have sold the previously_sold.
+/// (The nullifier is recomputed, since it is bound to the previous_sold_commitment)
+pub fn chain_input(input: &mut SmartMeterInput, previously_sold: u64, previous_sold_commitment: [u8; 32]) {
+    input.previously_sold = previously_sold;
+    input.previous_sold_commitment = previous_sold_commitment;
+    input.monitored_nullifier = compute_nullifier(
+        input.amount_of_energy_to_be_sold,
+        input.window_start,
+        input.window_end,
+        &input.monitored_merkle_root,
+        &input.seller,
+        input.chain_id,
+        &input.previous_sold_commitment,
+    );
+}
+
+/// Load the input data of the guest program generated by the simulator of the app (i.e. a `smart_meter_input.json`
+/// written by `cargo run --bin app -- simulate`), from the fixtures of the tests.
+pub fn load_simulated_input(file_name: &str) -> SmartMeterInput {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(file_name);
+    serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+}
+
+/// Execute the guest program with the input data and produce a receipt.
+pub fn prove_smart_meter(input: &SmartMeterInput) -> Receipt {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    // NOTE: Use the prover to run tests with actual proving + Produce a receipt by proving the specified ELF binary.
+    let prover = default_prover();
+    prover.prove(env, SMART_METER_ELF).unwrap().receipt
+}
@@ -0,0 +1,104 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy_primitives::{Address, B256, U256};
+use common::*;
+use energy_types::{merkle::MerkleTree, ConsumptionInput, ConsumptionJournal, MeterReading, MeterReadingProof, Reading};
+use guests::CONSUMPTION_ELF;
+use risc0_zkvm::{default_prover, ExecutorEnv, Receipt};
+
+/// Address of the buyer used in the tests.
+const BUYER: [u8; 20] = [0x44; 20];
+
+/// Meter readings (cumulative import register in Wh) of the buyer's smart meter every 20 minutes in the window,
+/// which consumed 900 kWh in total.
+fn consumption_meter_readings_in_window() -> Vec<MeterReading> {
+    vec![
+        MeterReading { timestamp: WINDOW_START, register: 3_000_000 },
+        MeterReading { timestamp: WINDOW_START + 1200, register: 3_200_000 },
+        MeterReading { timestamp: WINDOW_START + 2400, register: 3_500_000 },
+        MeterReading { timestamp: WINDOW_END, register: 3_900_000 },
+    ]
+}
+
+/// Build the input data of the consumption guest program, whose merkle root and meter signature are consistent with the given meter readings.
+fn build_consumption_input(amount_of_energy_to_be_bought: u64, meter_readings: &[MeterReading], current_time: u64) -> ConsumptionInput {
+    let tree = MerkleTree::new(meter_readings.iter().map(MeterReading::leaf).collect()).unwrap();
+    let monitored_merkle_root = tree.root();
+    let (meter_public_key, meter_signature) = sign_merkle_root(METER_SECRET_KEY, &monitored_merkle_root);
+
+    ConsumptionInput {
+        amount_of_energy_to_be_bought,
+        window_start: WINDOW_START,
+        window_end: WINDOW_END,
+        current_time,
+        max_monitored_time_age: MAX_MONITORED_TIME_AGE,
+        monitored_merkle_root,
+        meter_readings: meter_readings
+            .iter()
+            .enumerate()
+            .map(|(index, reading)| MeterReadingProof { reading: *reading, hash_path: tree.hash_path(index).unwrap() })
+            .collect(),
+        meter_public_key,
+        meter_signature,
+        buyer: BUYER,
+        chain_id: CHAIN_ID,
+    }
+}
+
+/// Execute the consumption guest program with the input data and produce a receipt.
+fn prove_consumption(input: &ConsumptionInput) -> Receipt {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .unwrap()
+        .build()
+        .unwrap();
+    default_prover().prove(env, CONSUMPTION_ELF).unwrap().receipt
+}
+
+#[test]
+fn proves_consumption_baseline_above_amount_to_be_bought() {
+    let input_amount_of_energy_to_be_bought: u64 = 500; // @dev - Less than the 900 kWh consumed in the window.
+    let input = build_consumption_input(input_amount_of_energy_to_be_bought, &consumption_meter_readings_in_window(), CURRENT_TIME);
+
+    let _receipt = prove_consumption(&input);
+
+    let decoded_journal = ConsumptionJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.energyAmountToBeBought, U256::from(input_amount_of_energy_to_be_bought));
+    assert_eq!(decoded_journal.windowStart, U256::from(WINDOW_START));
+    assert_eq!(decoded_journal.windowEnd, U256::from(WINDOW_END));
+    assert_eq!(decoded_journal.monitoredMerkleRoot, B256::from(input.monitored_merkle_root));
+    assert_eq!(decoded_journal.buyer, Address::from(BUYER));
+    assert_eq!(decoded_journal.chainId, U256::from(CHAIN_ID));
+}
+
+#[test]
+#[should_panic(expected = "energy consumed in the window must be greater than the amount of energy to be bought")]
+fn rejects_amount_to_be_bought_above_consumption_baseline() {
+    let input_amount_of_energy_to_be_bought: u64 = 1000; // @dev - More than the 900 kWh consumed in the window.
+    let input = build_consumption_input(input_amount_of_energy_to_be_bought, &consumption_meter_readings_in_window(), CURRENT_TIME);
+
+    let _receipt = prove_consumption(&input);
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is not signed by the smart meter.")]
+fn rejects_consumption_meter_reading_not_signed_by_smart_meter() {
+    let mut input = build_consumption_input(500, &consumption_meter_readings_in_window(), CURRENT_TIME);
+    input.meter_signature = sign_merkle_root(METER_SECRET_KEY, &[0u8; 32]).1; // @dev - The smart meter signed another merkle root.
+
+    let _receipt = prove_consumption(&input);
+}
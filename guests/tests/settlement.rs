@@ -0,0 +1,95 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy_primitives::{Address, B256, U256};
+use common::*;
+use energy_types::{merkle::MerkleTree, MeterReading, MeterReadingProof, Reading, SettlementInput, SettlementJournal};
+use guests::SETTLEMENT_ELF;
+use risc0_zkvm::{default_prover, ExecutorEnv, Receipt};
+
+/// The ID of the matched sell order used in the tests.
+const ORDER_ID: u64 = 1;
+
+/// Build the input data of the settlement guest program, whose merkle root and meter signature are consistent with the given
+/// meter readings of the delivery window (= the window of the meter_readings_in_window(), which exported 1100 kWh).
+fn build_settlement_input(traded_amount: u64, meter_readings: &[MeterReading]) -> SettlementInput {
+    let tree = MerkleTree::new(meter_readings.iter().map(MeterReading::leaf).collect()).unwrap();
+    let monitored_merkle_root = tree.root();
+    let (meter_public_key, meter_signature) = sign_merkle_root(METER_SECRET_KEY, &monitored_merkle_root);
+
+    SettlementInput {
+        order_id: ORDER_ID,
+        traded_amount,
+        delivery_window_start: WINDOW_START,
+        delivery_window_end: WINDOW_END,
+        monitored_merkle_root,
+        meter_readings: meter_readings
+            .iter()
+            .enumerate()
+            .map(|(index, reading)| MeterReadingProof { reading: *reading, hash_path: tree.hash_path(index).unwrap() })
+            .collect(),
+        meter_public_key,
+        meter_signature,
+        seller: SELLER,
+        chain_id: CHAIN_ID,
+    }
+}
+
+/// Execute the settlement guest program with the input data and produce a receipt.
+fn prove_settlement(input: &SettlementInput) -> Receipt {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .unwrap()
+        .build()
+        .unwrap();
+    default_prover().prove(env, SETTLEMENT_ELF).unwrap().receipt
+}
+
+#[test]
+fn proves_traded_amount_fully_delivered() {
+    let input = build_settlement_input(800, &meter_readings_in_window()); // @dev - Less than the 1100 kWh exported in the delivery window.
+
+    let _receipt = prove_settlement(&input);
+
+    let decoded_journal = SettlementJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.orderId, U256::from(ORDER_ID));
+    assert_eq!(decoded_journal.tradedAmount, U256::from(800));
+    assert_eq!(decoded_journal.delivered, U256::from(800)); // @dev - Capped at the traded amount.
+    assert_eq!(decoded_journal.shortfall, U256::ZERO);
+    assert_eq!(decoded_journal.monitoredMerkleRoot, B256::from(input.monitored_merkle_root));
+    assert_eq!(decoded_journal.seller, Address::from(SELLER));
+    assert_eq!(decoded_journal.chainId, U256::from(CHAIN_ID));
+}
+
+#[test]
+fn proves_shortfall_of_traded_amount() {
+    let input = build_settlement_input(1500, &meter_readings_in_window()); // @dev - More than the 1100 kWh exported in the delivery window.
+
+    let _receipt = prove_settlement(&input);
+
+    let decoded_journal = SettlementJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.delivered, U256::from(1100));
+    assert_eq!(decoded_journal.shortfall, U256::from(1500 - 1100));
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is not included in the monitored_merkle_root.")]
+fn rejects_inflated_delivery_meter_reading() {
+    let mut input = build_settlement_input(1500, &meter_readings_in_window());
+    input.meter_readings[3].reading.register = 9_000_000; // @dev - The seller inflates the export to avoid the shortfall.
+
+    let _receipt = prove_settlement(&input);
+}
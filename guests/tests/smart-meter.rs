@@ -12,56 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloy_primitives::U256;
+mod common;
+
+use alloy_primitives::{Address, B256, U256};
 use alloy_sol_types::SolValue;
-use guests::SMART_METER_ELF;
+use common::*;
+use energy_types::{compute_sold_commitment, MeterReading, MeterReadings, SmartMeterJournal, INITIAL_SOLD_COMMITMENT};
 //use guests::IS_EVEN_ELF;
-use risc0_zkvm::{default_executor, default_prover, ExecutorEnv, Receipt};
+use risc0_zkvm::default_executor;
 use risc0_ethereum_contracts::encode_seal;
 
-use hex;
-use sha2::{digest::generic_array::GenericArray, Digest, Sha256};
-
+use sha2::{Digest, Sha256};
 
 #[test]
 fn proves_available_electricity_amount_from_smart_meter() {
     let input_amount_of_energy_to_be_sold: u64 = 800; // @dev - Input value to be loaded into the ZK circuit.
-    let input_total_exact_amount_of_energy_available: u64 = 1100;
-    let input_current_time: u64 = 1740641628;  // @dev - UTC timestamp (2025-02-27 / 07:33:45)
-    let input_monitored_time: u64 = 1740641630;
-    let input_monitored_merkle_root: String = "0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7".to_string();
-    //let input_monitored_hash_path: Vec<String> = vec!["0x8da9e1c820f9dbd1589fd6585872bc1063588625729e7ab0797cfc63a00bd950".to_string(),"0x995788ffc103b987ad50f5e5707fd094419eb12d9552cc423bd0cd86a3861433".to_string()];
-
-    // Calculate the monitored_nullifier from the input data and store it into the variable.
-    let mut hasher = Sha256::new();
-    hasher.update(input_amount_of_energy_to_be_sold.to_string().as_bytes());
-    hasher.update(input_monitored_time.to_string().as_bytes());
-    hasher.update(input_monitored_merkle_root.as_bytes());
-    let hash = hasher.finalize(); // Note that calling `finalize()` consumes hasher
-    let input_monitored_nullifier: String = hex::encode(hash); // Convert GenericArray<u8, N> to a hexadecimal string
-
-    // Execute the guest program with the input data.
-    let env = ExecutorEnv::builder()
-        .write(&input_amount_of_energy_to_be_sold)
-        .unwrap()
-        .write(&input_total_exact_amount_of_energy_available)
-        .unwrap()
-        .write(&input_current_time)
-        .unwrap()
-        .write(&input_monitored_time)
-        .unwrap()
-        .write(&input_monitored_merkle_root)
-        .unwrap()
-        //.write(&input_monitored_hash_path)
-        //.unwrap()
-        .write(&input_monitored_nullifier)
-        .unwrap()
-        .build()
-        .unwrap();
-
-    // NOTE: Use the prover to run tests with actual proving + Produce a receipt by proving the specified ELF binary.
-    let prover = default_prover();
-    let _receipt = prover.prove(env, SMART_METER_ELF).unwrap().receipt;
+    let input = build_input(input_amount_of_energy_to_be_sold, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
     //let _receipt = prover.prove(env, IS_EVEN_ELF).unwrap().receipt;
     println!("I know the factors of {:?}, and I can prove it!\n", _receipt);
 
@@ -72,47 +40,201 @@ fn proves_available_electricity_amount_from_smart_meter() {
     // Extract the journal from the receipt.
     let journal = _receipt.journal.bytes.clone();
     println!("journal: {:?}\n", journal);
+
+    // Decode the ABI-encoded journal, which must be the same bytes as the abi.encode() in the EnergyAggregator#createSellOrder().
+    let decoded_journal = SmartMeterJournal::decode(&journal).unwrap();
+    assert_eq!(decoded_journal.energyAmountToBeSold, U256::from(input_amount_of_energy_to_be_sold));
+    assert_eq!(decoded_journal.windowStart, U256::from(WINDOW_START));
+    assert_eq!(decoded_journal.windowEnd, U256::from(WINDOW_END));
+    assert_eq!(decoded_journal.currentTime, U256::from(CURRENT_TIME));
+    assert_eq!(decoded_journal.maxMonitoredTimeAge, U256::from(MAX_MONITORED_TIME_AGE));
+    assert_eq!(decoded_journal.monitoredMerkleRoot, B256::from(input.monitored_merkle_root));
+    assert_eq!(decoded_journal.monitoredNullifier, B256::from(input.monitored_nullifier));
+    assert_eq!(decoded_journal.meterPublicKeyHash, B256::from(<[u8; 32]>::from(Sha256::digest(&input.meter_public_key))));
+    assert_eq!(decoded_journal.previousSoldCommitment, B256::from(INITIAL_SOLD_COMMITMENT));
+    assert_eq!(decoded_journal.soldCommitment, B256::from(compute_sold_commitment(&input.monitored_merkle_root, input_amount_of_energy_to_be_sold)));
+    assert_eq!(decoded_journal.seller, Address::from(SELLER));
+    assert_eq!(decoded_journal.chainId, U256::from(CHAIN_ID));
+    assert_eq!(
+        journal,
+        (
+            U256::from(input_amount_of_energy_to_be_sold),
+            U256::from(WINDOW_START),
+            U256::from(WINDOW_END),
+            U256::from(CURRENT_TIME),
+            U256::from(MAX_MONITORED_TIME_AGE),
+            decoded_journal.monitoredMerkleRoot,
+            decoded_journal.monitoredNullifier,
+            decoded_journal.meterPublicKeyHash,
+            decoded_journal.previousSoldCommitment,
+            decoded_journal.soldCommitment,
+            Address::from(SELLER),
+            U256::from(CHAIN_ID)
+        ).abi_encode_params()
+    );
 }
 
 #[test]
-#[should_panic(expected = "total exact amount of energy available must be greater than the amount of energy to be sold")] // @dev - This expected-error message should correspond to the panice message in the constraint in the ZK circuit. 
+#[should_panic(expected = "energy produced in the window must be greater than the amount of energy to be sold")] // @dev - This expected-error message should correspond to the panice message in the constraint in the ZK circuit.
 //#[should_panic(expected = "number must be more than 0")]
 fn rejects_wrong_available_electricity_amount_from_smart_meter() {
-    let input_amount_of_energy_to_be_sold: u64 = 1304; // @dev - Input value to be loaded into the ZK circuit.
-    let wrong_input_total_exact_amount_of_energy_available: u64 = 300;
-    let input_current_time: u64 = 1740641628;  // @dev - UTC timestamp (2025-02-27 / 07:33:45)
-    let input_monitored_time: u64 = 1740641630;
-    let input_monitored_merkle_root: String = "0xcc086fcc038189b4641db2cc4f1de3bb132aefbd65d510d817591550937818c7".to_string();
-    //let input_monitored_hash_path: Vec<String> = vec!["0x8da9e1c820f9dbd1589fd6585872bc1063588625729e7ab0797cfc63a00bd950".to_string(),"0x995788ffc103b987ad50f5e5707fd094419eb12d9552cc423bd0cd86a3861433".to_string()];
-
-    // Calculate the monitored_nullifier from the input data and store it into the variable.
-    let mut hasher = Sha256::new();
-    hasher.update(input_amount_of_energy_to_be_sold.to_string().as_bytes());
-    hasher.update(input_monitored_time.to_string().as_bytes());
-    hasher.update(input_monitored_merkle_root.as_bytes());
-    let hash = hasher.finalize(); // Note that calling `finalize()` consumes hasher
-    let input_monitored_nullifier: String = hex::encode(hash); // Convert GenericArray<u8, N> to a hexadecimal string
-
-    // Execute the guest program with the input data.
-    let env = ExecutorEnv::builder()
-        .write(&input_amount_of_energy_to_be_sold)
-        .unwrap()
-        .write(&wrong_input_total_exact_amount_of_energy_available) // @dev - This is the fake input value.
-        .unwrap()
-        .write(&input_current_time)
-        .unwrap()
-        .write(&input_monitored_time)
-        .unwrap()
-        .write(&input_monitored_merkle_root)
-        .unwrap()
-        //.write(&input_monitored_hash_path)
-        //.unwrap()
-        .write(&input_monitored_nullifier)
-        .unwrap()
-        .build()
-        .unwrap();
-
-    // NOTE: Use the prover to run tests with actual proving + Produce a receipt by proving the specified ELF binary.
-    let prover = default_prover();
-    let _receipt = prover.prove(env, SMART_METER_ELF).unwrap().receipt;
+    let input_amount_of_energy_to_be_sold: u64 = 1304; // @dev - Only 1100 kWh was produced in the window.
+    let input = build_input(input_amount_of_energy_to_be_sold, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is not included in the monitored_merkle_root.")]
+fn rejects_meter_reading_not_included_in_monitored_merkle_root() {
+    let mut input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let MeterReadings::Gross(meter_readings) = &mut input.meter_readings else { unreachable!() };
+    meter_readings[3].reading.register = 9_000_000; // @dev - This is the fake input value.
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is not signed by the smart meter.")]
+fn rejects_meter_reading_not_signed_by_smart_meter() {
+    let signed_input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    // The meter readings are inflated, and the merkle root is recomputed from them.
+    let mut fake_meter_readings = meter_readings_in_window();
+    fake_meter_readings[3].register = 9_000_000;
+    let mut input = build_input(3000, &fake_meter_readings, MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    input.meter_signature = signed_input.meter_signature; // @dev - The smart meter only signed the merkle root of the actual meter readings.
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is outside of the window.")]
+fn rejects_meter_reading_outside_of_window() {
+    let mut meter_readings = meter_readings_in_window();
+    meter_readings.insert(0, MeterReading { timestamp: WINDOW_START - 1200, register: 4_600_000 }); // @dev - This reading is before the window_start.
+    let input = build_input(1400, &meter_readings, MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The cumulative register of the meter readings must not decrease.")]
+fn rejects_non_monotonic_meter_readings() {
+    let mut meter_readings = meter_readings_in_window();
+    meter_readings[0].register = 5_500_000; // @dev - Greater than the next register value.
+    let input = build_input(500, &meter_readings, MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The window_end must not be in the future.")]
+fn rejects_window_end_in_the_future() {
+    let wrong_input_current_time: u64 = WINDOW_END - 60; // @dev - 1 minute before the window_end
+    let input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, wrong_input_current_time);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The meter reading is too old to be sold.")]
+fn rejects_stale_meter_reading() {
+    let stale_input_current_time: u64 = WINDOW_END + 7200; // @dev - 2 hours after the window_end
+    let input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, stale_input_current_time);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The input_monitored_nullifier is not correct.")]
+fn rejects_monitored_nullifier_bound_to_another_seller() {
+    let mut input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    input.seller = [0x33; 20]; // @dev - An address which copied the input data of the SELLER.
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+fn proves_net_surplus_from_smart_meter() {
+    let input_amount_of_energy_to_be_sold: u64 = 600; // @dev - The net surplus is 600 kWh (= 1100 kWh exported - 500 kWh imported).
+    let input = build_input(input_amount_of_energy_to_be_sold, &net_meter_readings_in_window(), MeterReadings::Net, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
+
+    // The journal has the same layout as the one of the gross production, so it is accepted by the same EnergyAggregator#createSellOrder().
+    let decoded_journal = SmartMeterJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.energyAmountToBeSold, U256::from(input_amount_of_energy_to_be_sold));
+    assert_eq!(decoded_journal.monitoredMerkleRoot, B256::from(input.monitored_merkle_root));
+}
+
+#[test]
+#[should_panic(expected = "net surplus in the window must be greater than the amount of energy to be sold")]
+fn rejects_amount_larger_than_net_surplus_from_smart_meter() {
+    let input_amount_of_energy_to_be_sold: u64 = 800; // @dev - Less than the gross production (1100 kWh), but more than the net surplus (600 kWh).
+    let input = build_input(input_amount_of_energy_to_be_sold, &net_meter_readings_in_window(), MeterReadings::Net, WINDOW_START, WINDOW_END, CURRENT_TIME);
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+fn proves_sell_order_chained_from_previous_sold_commitment() {
+    let mut input = build_input(300, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let previous_sold_commitment = compute_sold_commitment(&input.monitored_merkle_root, 800);
+    chain_input(&mut input, 800, previous_sold_commitment); // @dev - 800 kWh of the 1100 kWh were already sold by the previous sell order.
+
+    let _receipt = prove_smart_meter(&input);
+
+    let decoded_journal = SmartMeterJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.previousSoldCommitment, B256::from(input.previous_sold_commitment));
+    assert_eq!(decoded_journal.soldCommitment, B256::from(compute_sold_commitment(&input.monitored_merkle_root, 1100)));
+}
+
+#[test]
+fn proves_chained_sell_orders_of_same_amount_with_distinct_nullifiers() {
+    let first_input = build_input(500, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let mut second_input = build_input(500, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let previous_sold_commitment = compute_sold_commitment(&second_input.monitored_merkle_root, 500);
+    chain_input(&mut second_input, 500, previous_sold_commitment); // @dev - The second sell order of the same 500 kWh from the same meter readings.
+    assert_ne!(first_input.monitored_nullifier, second_input.monitored_nullifier);
+
+    let _receipt = prove_smart_meter(&second_input);
+
+    let decoded_journal = SmartMeterJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.monitoredNullifier, B256::from(second_input.monitored_nullifier));
+}
+
+#[test]
+#[should_panic(expected = "energy produced in the window must be greater than the amount of energy to be sold")]
+fn rejects_sell_order_exceeding_energy_available_with_previously_sold() {
+    let mut input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let previous_sold_commitment = compute_sold_commitment(&input.monitored_merkle_root, 800);
+    chain_input(&mut input, 800, previous_sold_commitment); // @dev - 800 + 800 kWh is more than the 1100 kWh produced in the window.
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+#[should_panic(expected = "The previous_sold_commitment is not the commitment to the previously_sold.")]
+fn rejects_previously_sold_not_matching_previous_sold_commitment() {
+    let mut input = build_input(800, &meter_readings_in_window(), MeterReadings::Gross, WINDOW_START, WINDOW_END, CURRENT_TIME);
+    let previous_sold_commitment = compute_sold_commitment(&input.monitored_merkle_root, 800);
+    chain_input(&mut input, 0, previous_sold_commitment); // @dev - The seller hides the 800 kWh which was already sold.
+
+    let _receipt = prove_smart_meter(&input);
+}
+
+#[test]
+fn proves_simulated_meter_readings_from_smart_meter() {
+    // @dev - Generated by: cargo run --bin app -- simulate --meters 1 --days 1 --start 1740614400 --interval 3600 --outage-rate 0.1
+    //        --max-clock-skew 30 --seller 0x2222222222222222222222222222222222222222 --current-time 1740700817 (i.e. a day of hourly
+    //        net meter readings, with outages and a clock skew, signed by a generated meter key)
+    let input = load_simulated_input("simulated_smart_meter_input.json");
+    assert_eq!(input.seller, SELLER);
+
+    let _receipt = prove_smart_meter(&input);
+
+    let decoded_journal = SmartMeterJournal::decode(&_receipt.journal.bytes).unwrap();
+    assert_eq!(decoded_journal.energyAmountToBeSold, U256::from(input.amount_of_energy_to_be_sold));
+    assert_eq!(decoded_journal.monitoredMerkleRoot, B256::from(input.monitored_merkle_root));
 }